@@ -1,31 +1,44 @@
-//! This build script copies the `memory.x` file from the crate root into
-//! a directory where the linker can always find it at build time.
-//! For many projects this is optional, as the linker always searches the
-//! project root directory -- wherever `Cargo.toml` is. However, if you
-//! are using a workspace or have a more complicated build setup, this
-//! build script becomes required. Additionally, by requesting that
-//! Cargo re-run the build script whenever `memory.x` is changed,
-//! updating `memory.x` ensures a rebuild of the application with the
-//! new memory settings.
+//! This build script generates the `memory.x` for the selected boot mode, see
+//! [`memory::write_memory_x`], into a directory where the linker can always find it at build time.
+//! The regions come from `src/memory.rs`, so the linker script and the crate's memory map
+//! constants can't disagree.
+//!
+//! - no feature: run from internal flash
+//! - `under_bootloader`: run from QSPI flash under the Daisy bootloader
+//! - `under_bootloader_sram`: copied into AXI SRAM by the Daisy bootloader
+//!
+//! `use_sdram` (a default feature) adds the external SDRAM region.
 
 use std::env;
-use std::fs::File;
-use std::io::Write;
+use std::fs;
 use std::path::PathBuf;
 
+#[allow(dead_code)]
+#[path = "src/memory.rs"]
+mod memory;
+
 fn main() {
+    let qspi = env::var_os("CARGO_FEATURE_UNDER_BOOTLOADER").is_some();
+    let sram = env::var_os("CARGO_FEATURE_UNDER_BOOTLOADER_SRAM").is_some();
+    let boot = match (qspi, sram) {
+        (false, false) => memory::Boot::Flash,
+        (true, false) => memory::Boot::BootloaderQspi,
+        (false, true) => memory::Boot::BootloaderSram,
+        (true, true) => panic!("`under_bootloader` and `under_bootloader_sram` are exclusive"),
+    };
+    let layout = memory::LinkerLayout {
+        boot,
+        sdram: env::var_os("CARGO_FEATURE_USE_SDRAM").is_some(),
+    };
+    let mut memory_x = String::new();
+    memory::write_memory_x(&mut memory_x, layout).unwrap();
+
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
-    File::create(out.join("memory.x"))
-        .unwrap()
-        .write_all(include_bytes!("memory.x"))
-        .unwrap();
+    fs::write(out.join("memory.x"), memory_x).unwrap();
     println!("cargo:rustc-link-search={}", out.display());
 
-    // By default, Cargo will re-run a build script whenever
-    // any file in the project changes. By specifying `memory.x`
-    // here, we ensure the build script is only re-run when
-    // `memory.x` is changed.
-    println!("cargo:rerun-if-changed=memory.x");
+    // Only the memory map changes the output, features re-run the script anyway.
+    println!("cargo:rerun-if-changed=src/memory.rs");
 }
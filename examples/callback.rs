@@ -0,0 +1,26 @@
+//! Passthrough with the processing closure called directly from the SAI loop.
+#![no_std]
+#![no_main]
+
+use daisy_embassy::{hal, new_daisy_p, DaisyBoard};
+use defmt::debug;
+use embassy_executor::Spawner;
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    debug!("====program start====");
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    core.SCB.enable_icache();
+    let config = daisy_embassy::default_rcc();
+    let p = hal::init(config);
+    let daisy_p = new_daisy_p!(p);
+    let (board, _) = DaisyBoard::new(daisy_p, Default::default()).await.unwrap();
+    let mut interface = board.interface;
+
+    interface
+        .start_callback(|input, output| {
+            output.copy_from_slice(input);
+        })
+        .await;
+}
@@ -0,0 +1,57 @@
+//! Measures the cycles the block callback takes and logs the worst case once a second.
+#![no_std]
+#![no_main]
+
+use daisy_embassy::{
+    audio::{self, BLOCK_LENGTH},
+    hal, new_daisy_p,
+    perf::{block_load, cycles_to_micros, CycleCounter},
+    DaisyBoard,
+};
+use defmt::{debug, info};
+use embassy_executor::Spawner;
+use {defmt_rtt as _, panic_probe as _};
+
+// about a second of blocks at 48kHz
+const REPORT_INTERVAL: u32 = 48_000 / BLOCK_LENGTH as u32;
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    debug!("====program start====");
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    core.SCB.enable_icache();
+    let config = daisy_embassy::default_rcc();
+    let p = hal::init(config);
+    let daisy_p = new_daisy_p!(p);
+    let (board, _) = DaisyBoard::new(daisy_p, Default::default()).await.unwrap();
+    let mut interface = board.interface;
+
+    let mut counter = CycleCounter::new();
+    let mut blocks = 0;
+    let mut samples = [0.0; BLOCK_LENGTH * 2];
+    interface
+        .start_callback(|input, output| {
+            counter.start();
+            // the DSP under test
+            audio::to_f32_block(input, &mut samples);
+            for smp in samples.iter_mut() {
+                *smp = libm::tanhf(*smp * 4.0);
+            }
+            audio::from_f32_block(&samples, output);
+            counter.stop();
+
+            blocks += 1;
+            if blocks == REPORT_INTERVAL {
+                let max = counter.max();
+                info!(
+                    "worst block: {} cycles, {}us, {}% of the deadline",
+                    max,
+                    cycles_to_micros(max),
+                    block_load(max, BLOCK_LENGTH, 48_000) * 100.0
+                );
+                counter.reset_max();
+                blocks = 0;
+            }
+        })
+        .await;
+}
@@ -0,0 +1,71 @@
+//! Audio processing on an interrupt executor, so it preempts everything on the thread executor.
+//!
+//! `main` runs in thread mode, at the lowest priority. The audio task runs on an
+//! `InterruptExecutor` driven by an interrupt that nothing else uses (UART4 here), and is
+//! polled from that interrupt's handler whenever the SAI DMA wakes it. Long running work in
+//! `main` (USB, flash, UI) can then no longer delay a block and cause a dropout.
+//!
+//! Everything that touches the interface has to live in the high priority task: the block
+//! channels use `NoopRawMutex` and can't be shared between executors. That's why the task
+//! sets up the interface itself, from the pins and peripherals it is handed.
+#![no_std]
+#![no_main]
+
+use daisy_embassy::{
+    audio::{self, Interface},
+    hal::{
+        self, interrupt,
+        interrupt::{InterruptExt, Priority},
+    },
+    led::UserLed,
+    new_daisy_p,
+    pins::WM8731Pins,
+};
+use defmt::debug;
+use embassy_executor::{InterruptExecutor, Spawner};
+use embassy_time::Timer;
+use {defmt_rtt as _, panic_probe as _};
+
+static EXECUTOR_HIGH: InterruptExecutor = InterruptExecutor::new();
+
+#[interrupt]
+unsafe fn UART4() {
+    EXECUTOR_HIGH.on_interrupt()
+}
+
+#[embassy_executor::task]
+async fn audio_task(pins: WM8731Pins, p: audio::Peripherals) {
+    let (mut interface, _) = Interface::new(pins, p, Default::default()).await.unwrap();
+    interface
+        .start_callback(|input, output| {
+            output.copy_from_slice(input);
+        })
+        .await;
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    debug!("====program start====");
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    core.SCB.enable_icache();
+    let config = daisy_embassy::default_rcc();
+    let p = hal::init(config);
+    let daisy_p = new_daisy_p!(p);
+
+    // Lower numbers are higher priorities. The DMA interrupts stay at their default (P0),
+    // so they can still preempt the audio task to wake it.
+    interrupt::UART4.set_priority(Priority::P6);
+    let spawner = EXECUTOR_HIGH.start(interrupt::UART4);
+    spawner
+        .spawn(audio_task(daisy_p.wm8731_pin, daisy_p.audio_peripherals))
+        .unwrap();
+
+    let mut led = UserLed::new(daisy_p.led_user_pin);
+    loop {
+        // stands in for blocking work, the audio keeps running through it
+        cortex_m::asm::delay(100_000_000);
+        led.on();
+        Timer::after_millis(500).await;
+        led.off();
+    }
+}
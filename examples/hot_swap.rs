@@ -0,0 +1,76 @@
+//! Cycle between two effects every two seconds without stopping the audio.
+#![no_std]
+#![no_main]
+
+use daisy_embassy::{
+    audio::{Chain, ClipMode, Gain, Lfo, LfoWaveform, Patch, PatchRunner, PatchSlot, Processor},
+    new_daisy_p, DaisyBoard,
+};
+use defmt::{debug, info};
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use embassy_time::Timer;
+use static_cell::StaticCell;
+use {defmt_rtt as _, panic_probe as _};
+
+const SAMPLE_RATE: u32 = 48_000;
+
+static SLOT: PatchSlot = PatchSlot::new();
+
+struct Tremolo {
+    lfo: Lfo,
+}
+
+impl Processor for Tremolo {
+    fn process(&mut self, block: &mut [f32]) {
+        for frame in block.chunks_exact_mut(2) {
+            let gain = 0.5 + 0.5 * self.lfo.next();
+            frame[0] *= gain;
+            frame[1] *= gain;
+        }
+    }
+}
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    debug!("====program start====");
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    core.SCB.enable_icache();
+    let config = daisy_embassy::default_rcc();
+    let p = daisy_embassy::rcc::init(config);
+    let daisy_p = new_daisy_p!(p);
+    let (board, _) = DaisyBoard::new(daisy_p, Default::default()).await.unwrap();
+    let mut interface = board.interface;
+
+    static TREMOLO: StaticCell<Tremolo> = StaticCell::new();
+    let mut lfo = Lfo::new(SAMPLE_RATE, LfoWaveform::Sine);
+    lfo.set_rate_hz(5.0);
+    let tremolo: Patch = TREMOLO.init(Tremolo { lfo });
+
+    // +18dB into a cubic clipper
+    static DISTORTION: StaticCell<Chain<Gain, ClipMode>> = StaticCell::new();
+    let mut drive = Gain::new(SAMPLE_RATE, 10.0);
+    drive.set_gain_db(0, 18.0);
+    drive.set_gain_db(1, 18.0);
+    let distortion: Patch = DISTORTION.init(drive.then(ClipMode::Cubic));
+
+    let mut runner = PatchRunner::new(tremolo, SAMPLE_RATE, 20.0);
+    let interface_fut = async {
+        interface
+            .start_chain((), (), |input, output| runner.process(&SLOT, input, output))
+            .await
+    };
+
+    let control_fut = async {
+        let mut next = Some(distortion);
+        loop {
+            Timer::after_secs(2).await;
+            // the patch swapped out last time is back once its fade out is done
+            if let Some(patch) = next.take().or_else(|| SLOT.take_retired()) {
+                info!("switch patch");
+                SLOT.swap(patch);
+            }
+        }
+    };
+    join(interface_fut, control_fut).await;
+}
@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+//! Passthrough with the board set up by `#[daisy_embassy::main]`.
+use daisy_embassy::{audio::AudioBlockBuffers, DaisyBoard};
+use defmt::debug;
+use embassy_executor::Spawner;
+use embassy_futures::join::join;
+use {defmt_rtt as _, panic_probe as _};
+
+#[daisy_embassy::main]
+async fn main(_spawner: Spawner, board: DaisyBoard<'static>, buffers: AudioBlockBuffers) {
+    debug!("====program start====");
+    let (mut to_interface, mut from_interface) = buffers;
+    let mut interface = board.interface;
+
+    let interface_fut = async { interface.start().await };
+
+    let audio_callback_fut = async {
+        loop {
+            let rx = from_interface.receive().await;
+            let tx = to_interface.send().await;
+            tx.copy_from_slice(rx);
+            to_interface.send_done();
+            from_interface.receive_done();
+        }
+    };
+    join(interface_fut, audio_callback_fut).await;
+}
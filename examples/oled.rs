@@ -0,0 +1,73 @@
+//! Show the position of a knob on `SEED_PIN_15` (ADC1) on the OLED of a Daisy Patch or Field.
+//! Run with `--features display`.
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+use daisy_embassy::{
+    display::{self, OledPins},
+    hal::{
+        self,
+        adc::{Adc, Resolution},
+    },
+    new_daisy_p,
+};
+use defmt::debug;
+use embassy_executor::Spawner;
+use embassy_time::Timer;
+use embedded_graphics::{
+    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+use ssd1306::{prelude::*, Ssd1306};
+use {defmt_rtt as _, panic_probe as _};
+
+#[embassy_executor::main]
+async fn main(_spawner: Spawner) {
+    debug!("====program start====");
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    core.SCB.enable_icache();
+    let config = daisy_embassy::default_rcc();
+    let p = hal::init(config);
+    let daisy_p = new_daisy_p!(p);
+    let pins = daisy_p.daisy_pins;
+
+    let oled_pins = OledPins {
+        CS: pins.SEED_PIN_7,
+        SCK: pins.SEED_PIN_8,
+        DC: pins.SEED_PIN_9,
+        MOSI: pins.SEED_PIN_10,
+        RESET: pins.SEED_PIN_30,
+    };
+    let interface = display::oled(p.SPI1, oled_pins).await;
+    let mut oled = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+        .into_buffered_graphics_mode();
+    oled.init().unwrap();
+
+    let mut adc = Adc::new(p.ADC1);
+    adc.set_resolution(Resolution::BITS16);
+    let mut knob = pins.SEED_PIN_15;
+    let text_style = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    let bar_style = PrimitiveStyle::with_fill(BinaryColor::On);
+    let mut text = heapless::String::<16>::new();
+    loop {
+        let value = adc.read(&mut knob) as u32 * 100 / u16::MAX as u32;
+
+        oled.clear_buffer();
+        text.clear();
+        write!(text, "knob: {}%", value).unwrap();
+        Text::with_baseline(&text, Point::new(0, 8), text_style, Baseline::Top)
+            .draw(&mut oled)
+            .unwrap();
+        Rectangle::new(Point::new(0, 44), Size::new(value * 128 / 100, 12))
+            .into_styled(bar_style)
+            .draw(&mut oled)
+            .unwrap();
+        oled.flush().unwrap();
+
+        Timer::after_millis(30).await;
+    }
+}
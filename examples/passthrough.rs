@@ -1,13 +1,7 @@
 #![no_std]
 #![no_main]
 
-use daisy_embassy::{
-    audio::HALF_DMA_BUFFER_LENGTH,
-    hal::{self, time::Hertz},
-    new_daisy_p,
-    pins::{DaisyPins, USB2Pins, WM8731Pins},
-    DaisyBoard,
-};
+use daisy_embassy::{audio::HALF_DMA_BUFFER_LENGTH, new_daisy_p, DaisyBoard};
 use defmt::debug;
 use embassy_executor::Spawner;
 use embassy_futures::join::join;
@@ -18,39 +12,11 @@ async fn main(_spawner: Spawner) {
     debug!("====program start====");
     let mut core = cortex_m::Peripherals::take().unwrap();
     core.SCB.enable_icache();
-    let mut config = hal::Config::default();
-    {
-        use hal::rcc::*;
-        config.rcc.hsi = Some(HSIPrescaler::DIV1);
-        config.rcc.csi = true;
-        config.rcc.pll1 = Some(Pll {
-            source: PllSource::HSI,
-            prediv: PllPreDiv::DIV4,
-            mul: PllMul::MUL50,
-            divp: Some(PllDiv::DIV2),
-            divq: Some(PllDiv::DIV8),
-            divr: None,
-        });
-        config.rcc.sys = Sysclk::PLL1_P; // 400 Mhz
-        config.rcc.ahb_pre = AHBPrescaler::DIV2; // 200 Mhz
-        config.rcc.apb1_pre = APBPrescaler::DIV2; // 100 Mhz
-        config.rcc.apb2_pre = APBPrescaler::DIV2; // 100 Mhz
-        config.rcc.apb3_pre = APBPrescaler::DIV2; // 100 Mhz
-        config.rcc.apb4_pre = APBPrescaler::DIV2; // 100 Mhz
-        config.rcc.voltage_scale = VoltageScale::Scale1;
-        config.rcc.hse = Some(Hse {
-            freq: Hertz::mhz(16),
-            mode: HseMode::Oscillator,
-        })
-        //default as PLL1_Q?
-        //use hal::pac::rcc::vals::Saisel;
-        //config.rcc.mux.sai1sel = Saisel::PLL1_Q;
-    }
-
-    let p = hal::init(config);
+    let config = daisy_embassy::default_rcc();
+    let p = daisy_embassy::rcc::init(config);
     let daisy_p = new_daisy_p!(p);
     let (board, (mut to_interface, mut from_interface)) =
-        DaisyBoard::new(daisy_p, Default::default()).await;
+        DaisyBoard::new(daisy_p, Default::default()).await.unwrap();
     let mut interface = board.interface;
 
     let interface_fut = async { interface.start().await };
@@ -0,0 +1,100 @@
+//! Procedural macros of `daisy_embassy`. Use them through the re-exports in `daisy_embassy`.
+use proc_macro::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, spanned::Spanned, Error, ItemFn, Path};
+
+// #[daisy_embassy::main(rcc = path::to::fn)]
+struct Args {
+    rcc: Option<Path>,
+}
+
+fn parse_args(attr: TokenStream) -> syn::Result<Args> {
+    let mut args = Args { rcc: None };
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("rcc") {
+            args.rcc = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("unknown argument, expected `rcc = path::to::config_fn`"))
+        }
+    });
+    syn::parse::Parser::parse(parser, attr)?;
+    Ok(args)
+}
+
+/// Entry point that sets up the clocks and the Daisy Seed board before calling the annotated function.
+///
+/// ```ignore
+/// #[daisy_embassy::main]
+/// async fn main(spawner: Spawner, board: DaisyBoard<'static>, buffers: AudioBlockBuffers) {
+///     ...
+/// }
+/// ```
+/// The third parameter is optional. The clock configuration is `daisy_embassy::default_rcc()`
+/// unless given with `#[daisy_embassy::main(rcc = my_rcc)]`, where `my_rcc` is a
+/// `fn() -> hal::Config`, set up with `daisy_embassy::rcc::init`. The audio interface uses `AudioConfig::default()`, and a codec
+/// that doesn't respond panics. Expands to `#[embassy_executor::main]`, so the
+/// binary needs `embassy-executor` as a dependency.
+#[proc_macro_attribute]
+pub fn main(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = match parse_args(attr) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let f = parse_macro_input!(item as ItemFn);
+    match expand(args, f) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(args: Args, mut f: ItemFn) -> syn::Result<proc_macro2::TokenStream> {
+    if f.sig.asyncness.is_none() {
+        return Err(Error::new(f.sig.fn_token.span(), "main must be `async`"));
+    }
+    if !f.sig.generics.params.is_empty() {
+        return Err(Error::new(f.sig.generics.span(), "main can't be generic"));
+    }
+    let with_buffers = match f.sig.inputs.len() {
+        2 => false,
+        3 => true,
+        _ => {
+            return Err(Error::new(
+                f.sig.inputs.span(),
+                "expected `(spawner: Spawner, board: DaisyBoard<'static>)`, optionally followed by `buffers: AudioBlockBuffers`",
+            ))
+        }
+    };
+    let rcc = match args.rcc {
+        Some(path) => quote!(#path()),
+        None => quote!(::daisy_embassy::default_rcc()),
+    };
+    let attrs = core::mem::take(&mut f.attrs);
+    let inner = format_ident!("__daisy_embassy_main");
+    f.sig.ident = inner.clone();
+    // don't trip the unused variable lint when the buffers aren't asked for
+    let (buffers, call) = if with_buffers {
+        (
+            quote!(buffers),
+            quote!(#inner(spawner, board, buffers).await),
+        )
+    } else {
+        (quote!(_), quote!(#inner(spawner, board).await))
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #[::embassy_executor::main]
+        async fn main(spawner: ::embassy_executor::Spawner) {
+            let p = ::daisy_embassy::rcc::init(#rcc);
+            let daisy_p = ::daisy_embassy::new_daisy_p!(p);
+            let (board, #buffers) =
+                ::daisy_embassy::DaisyBoard::new(daisy_p, ::core::default::Default::default())
+                    .await
+                    .unwrap();
+            #call
+        }
+
+        #f
+    })
+}
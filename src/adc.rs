@@ -0,0 +1,101 @@
+//! MCU temperature and supply voltage from the internal ADC channels.
+//!
+//! On the STM32H750 the temperature sensor and VREFINT are only connected to ADC3,
+//! which is free on all Daisy boards (knobs and CV inputs use ADC1):
+//! ```ignore
+//! let mut monitor = McuMonitor::new(p.ADC3);
+//! if monitor.read_mcu_temperature() > 85.0 {
+//!     warn!("running hot");
+//! }
+//! info!("VDDA: {}V", monitor.read_vref());
+//! ```
+use embassy_stm32 as hal;
+use hal::adc::{Adc, AdcChannel, Resolution, SampleTime, Temperature, VrefInt};
+use hal::peripherals::ADC3;
+
+// Factory calibration in system memory, 16-bit readings taken at VDDA = 3.3V.
+// See RM0433 "Temperature sensor characteristics" and the STM32H750 datasheet.
+const TS_CAL1: *const u16 = 0x1FF1_E820 as *const u16; // at 30 degC
+const TS_CAL2: *const u16 = 0x1FF1_E840 as *const u16; // at 110 degC
+const VREFINT_CAL: *const u16 = 0x1FF1_E860 as *const u16;
+const TS_CAL1_TEMP: f32 = 30.0;
+const TS_CAL2_TEMP: f32 = 110.0;
+const CAL_VDDA: f32 = 3.3;
+
+/// Factory calibration values of the temperature sensor and VREFINT.
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct FactoryCalibration {
+    pub ts_cal1: u16,
+    pub ts_cal2: u16,
+    pub vrefint_cal: u16,
+}
+
+impl FactoryCalibration {
+    /// Read the values from system memory.
+    pub fn read() -> Self {
+        // Safety: read only system memory, present on every STM32H750.
+        unsafe {
+            Self {
+                ts_cal1: TS_CAL1.read_volatile(),
+                ts_cal2: TS_CAL2.read_volatile(),
+                vrefint_cal: VREFINT_CAL.read_volatile(),
+            }
+        }
+    }
+    /// Analog supply voltage from a 16-bit VREFINT reading.
+    pub fn vdda(&self, vrefint_raw: u16) -> f32 {
+        CAL_VDDA * self.vrefint_cal as f32 / vrefint_raw.max(1) as f32
+    }
+    /// Temperature in degC from a 16-bit sensor reading taken at `vdda`.
+    pub fn temperature(&self, ts_raw: u16, vdda: f32) -> f32 {
+        // the calibration values were taken at 3.3V, scale the reading to match
+        let ts = ts_raw as f32 * vdda / CAL_VDDA;
+        let slope = (TS_CAL2_TEMP - TS_CAL1_TEMP) / (self.ts_cal2 as f32 - self.ts_cal1 as f32);
+        slope * (ts - self.ts_cal1 as f32) + TS_CAL1_TEMP
+    }
+}
+
+/// ADC3 set up for the internal temperature sensor and VREFINT.
+pub struct McuMonitor<'a> {
+    adc: Adc<'a, ADC3>,
+    temperature: Temperature,
+    vrefint: VrefInt,
+    calibration: FactoryCalibration,
+}
+
+impl<'a> McuMonitor<'a> {
+    pub fn new(adc3: ADC3) -> Self {
+        let mut adc = Adc::new(adc3);
+        adc.set_resolution(Resolution::BITS16);
+        // the sensor needs at least 9us of sampling time
+        adc.set_sample_time(SampleTime::CYCLES810_5);
+        let temperature = adc.enable_temperature();
+        let vrefint = adc.enable_vrefint();
+        Self {
+            adc,
+            temperature,
+            vrefint,
+            calibration: FactoryCalibration::read(),
+        }
+    }
+    /// Die temperature in degC. Compensated for the measured supply voltage.
+    pub fn read_mcu_temperature(&mut self) -> f32 {
+        let vdda = self.read_vref();
+        let raw = self.adc.read(&mut self.temperature);
+        self.calibration.temperature(raw, vdda)
+    }
+    /// Analog supply voltage (VDDA) in volts, derived from VREFINT. 3.3V on the Daisy boards,
+    /// noticeably less points to a sagging supply.
+    pub fn read_vref(&mut self) -> f32 {
+        let raw = self.adc.read(&mut self.vrefint);
+        self.calibration.vdda(raw)
+    }
+    /// 16-bit reading of another ADC3 channel, e.g. `SEED_PIN_15` (PC0) or `SEED_PIN_20` (PC1).
+    /// Convert to volts with the VDDA from [`McuMonitor::read_vref`].
+    pub fn read_raw(&mut self, channel: &mut impl AdcChannel<ADC3>) -> u16 {
+        self.adc.read(channel)
+    }
+    pub fn calibration(&self) -> &FactoryCalibration {
+        &self.calibration
+    }
+}
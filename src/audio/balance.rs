@@ -0,0 +1,155 @@
+//! Stereo balance of the output, one control from full left to full right.
+//!
+//! The interface applies it to every output block after the client's processing (and the input
+//! monitoring), in both [`Interface::start`] and [`Interface::start_callback`]. It multiplies on
+//! top of whatever [`Gain`] the client applies, so the two don't interfere:
+//! ```ignore
+//! set_balance(pot.read() * 2.0 - 1.0); // from any task or interrupt
+//! ```
+//! The balance follows the equal-power (sin/cos) law, so the loudness stays the same across the
+//! range. Both sides are at -3dB in the center, the default, and full left or right is 0dB on
+//! one side and silence on the other.
+//! Changes are ramped over one block, so a moving control doesn't cause zipper noise.
+//! Mono blocks ([`AudioConfig::mono`]) are sent in both slots, so the balance has no effect on them.
+//!
+//! [`Interface::start`]: super::Interface::start
+//! [`Interface::start_callback`]: super::Interface::start_callback
+//! [`Gain`]: super::Gain
+//! [`AudioConfig::mono`]: super::AudioConfig::mono
+#[cfg(any(feature = "hal", test))]
+use super::convert::{round_to_u24, u24_to_i32};
+use core::f32::consts::FRAC_PI_4;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const CHANNELS: usize = 2;
+
+// f32 bits of the balance, 0.0 (center) to start with
+static BALANCE: AtomicU32 = AtomicU32::new(0);
+
+/// Balance of the output, -1.0 (full left) to 1.0 (full right), 0.0 (center) by default.
+/// Takes effect with the next block, from any task or interrupt.
+pub fn set_balance(balance: f32) {
+    let balance = if balance.is_nan() {
+        0.0
+    } else {
+        balance.clamp(-1.0, 1.0)
+    };
+    BALANCE.store(balance.to_bits(), Ordering::Relaxed);
+}
+
+pub fn balance() -> f32 {
+    f32::from_bits(BALANCE.load(Ordering::Relaxed))
+}
+
+/// Left and right gains for `balance` by the equal-power law, `[cos(a), sin(a)]` of
+/// `a = (balance + 1) * pi / 4`: `l * l + r * r` is 1.0 everywhere, each side is -3dB in the center.
+pub fn balance_gains(balance: f32) -> [f32; CHANNELS] {
+    let angle = (balance.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    [libm::cosf(angle), libm::sinf(angle)]
+}
+
+// The gains of the last block the interface applied, to ramp from.
+#[cfg(any(feature = "hal", test))]
+pub(super) struct BalanceRamp {
+    current: [f32; CHANNELS],
+}
+
+#[cfg(any(feature = "hal", test))]
+impl BalanceRamp {
+    pub(super) const fn new() -> Self {
+        Self {
+            // balance_gains(0.0)
+            current: [core::f32::consts::FRAC_1_SQRT_2; CHANNELS],
+        }
+    }
+    // Called by the interface loops on the 24 bit output block.
+    #[cfg(feature = "hal")]
+    pub(super) fn apply(&mut self, block: &mut [u32], slot_count: usize) {
+        self.ramp_to(balance_gains(balance()), block, slot_count);
+    }
+    fn ramp_to(&mut self, target: [f32; CHANNELS], block: &mut [u32], slot_count: usize) {
+        // a mono sample goes out on both sides, there's nothing to balance
+        if slot_count < CHANNELS {
+            return;
+        }
+        let frames = (block.len() / slot_count).max(1) as f32;
+        let step = [
+            (target[0] - self.current[0]) / frames,
+            (target[1] - self.current[1]) / frames,
+        ];
+        let mut gain = self.current;
+        for frame in block.chunks_exact_mut(slot_count) {
+            for ((smp, gain), step) in frame.iter_mut().zip(&mut gain).zip(step) {
+                *gain += step;
+                *smp = round_to_u24(u24_to_i32(*smp) as f32 * *gain);
+            }
+        }
+        self.current = target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn ramps_stereo_over_one_block() {
+        let mut ramp = BalanceRamp {
+            current: [1.0; CHANNELS],
+        };
+        let mut block = [0x10_0000; 8];
+        ramp.ramp_to([0.0, 1.0], &mut block, 2);
+        assert_eq!(block[0], 0x0C_0000);
+        assert_eq!(block[6], 0);
+        assert!(block.iter().skip(1).step_by(2).all(|&smp| smp == 0x10_0000));
+
+        let mut block = [0x10_0000; 8];
+        ramp.ramp_to([0.0, 1.0], &mut block, 2);
+        assert_eq!(
+            block,
+            [0, 0x10_0000, 0, 0x10_0000, 0, 0x10_0000, 0, 0x10_0000]
+        );
+    }
+
+    #[test]
+    fn equal_power() {
+        for balance in [-1.0, -0.75, -0.5, -0.1, 0.0, 0.3, 0.5, 0.9, 1.0] {
+            let [l, r] = balance_gains(balance);
+            assert!((l * l + r * r - 1.0).abs() < 1e-6, "{balance}");
+        }
+        let [l, r] = balance_gains(0.0);
+        assert!((l - FRAC_1_SQRT_2).abs() < 1e-6 && (r - FRAC_1_SQRT_2).abs() < 1e-6);
+        assert!((balance_gains(-1.0)[0] - 1.0).abs() < 1e-6);
+        assert!(balance_gains(-1.0)[1].abs() < 1e-6);
+        assert!(balance_gains(1.0)[0].abs() < 1e-6);
+        assert_eq!(balance_gains(3.0), balance_gains(1.0));
+    }
+
+    #[test]
+    fn starts_at_the_center() {
+        let mut ramp = BalanceRamp::new();
+        let mut block = [0x10_0000; 4];
+        ramp.ramp_to(balance_gains(0.0), &mut block, 2);
+        assert!(block.iter().all(|&smp| smp.abs_diff(0x0B_504F) <= 1));
+    }
+
+    #[test]
+    fn mono_is_left_alone() {
+        let mut ramp = BalanceRamp::new();
+        let mut block = [0x10_0000; 4];
+        ramp.ramp_to([1.0, 0.0], &mut block, 1);
+        assert_eq!(block, [0x10_0000; 4]);
+    }
+
+    #[test]
+    fn tdm_balances_the_first_two_slots() {
+        let mut ramp = BalanceRamp {
+            current: [1.0; CHANNELS],
+        };
+        let mut block = [0x10_0000; 8];
+        ramp.ramp_to([1.0, 0.0], &mut block, 4);
+        ramp.ramp_to([1.0, 0.0], &mut block, 4);
+        assert_eq!(block[4..], [0x10_0000, 0, 0x10_0000, 0x10_0000]);
+    }
+}
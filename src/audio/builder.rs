@@ -0,0 +1,150 @@
+//! Step by step set up of an [`Interface`], as an alternative to filling in an [`AudioConfig`].
+use super::{
+    AudioBlockBuffers, AudioConfig, AudioError, ChannelFix, DmaBuffers, Fs, Interface, OutputRoute,
+    Peripherals, SaiProtocol, SaiRole, SampleFormat, Slots, BLOCK_LENGTH,
+};
+use crate::pins::{CodecPins, Pcm3060Pins, WM8731Pins};
+use embassy_stm32::time::Hertz;
+
+enum CodecWiring {
+    Wm8731(WM8731Pins),
+    Pcm3060(Pcm3060Pins),
+    Pcm3060OnSeedPins(CodecPins),
+}
+
+/// Builds an [`Interface`]. Setters take `&mut self`, so settings can be applied conditionally:
+/// ```ignore
+/// let mut builder = AudioInterfaceBuilder::wm8731(wm8731_pins, audio_peripherals);
+/// builder.sample_rate(Fs::Fs96000).buffer_count(3);
+/// if external_clock {
+///     builder.mclk_out(false);
+/// }
+/// let (interface, (to_interface, from_interface)) = builder.build().await?;
+/// ```
+/// Settings that are not set keep the values of [`AudioConfig::default`].
+pub struct AudioInterfaceBuilder {
+    wiring: CodecWiring,
+    peripherals: Peripherals,
+    config: AudioConfig,
+    block_size: usize,
+}
+
+impl AudioInterfaceBuilder {
+    /// WM8731, as on the Daisy Seed 1.1.
+    pub fn wm8731(pins: WM8731Pins, p: Peripherals) -> Self {
+        Self::with_wiring(CodecWiring::Wm8731(pins), p)
+    }
+    /// PCM3060, as on the Patch SM.
+    pub fn pcm3060(pins: Pcm3060Pins, p: Peripherals) -> Self {
+        Self::with_wiring(CodecWiring::Pcm3060(pins), p)
+    }
+    /// PCM3060 on the Daisy Seed's own codec pins, as on the Seed 2 DFM.
+    pub fn pcm3060_on_seed_pins(pins: CodecPins, p: Peripherals) -> Self {
+        Self::with_wiring(CodecWiring::Pcm3060OnSeedPins(pins), p)
+    }
+    fn with_wiring(wiring: CodecWiring, peripherals: Peripherals) -> Self {
+        Self {
+            wiring,
+            peripherals,
+            config: AudioConfig::default(),
+            block_size: BLOCK_LENGTH,
+        }
+    }
+    /// Sample rate of both directions.
+    pub fn sample_rate(&mut self, fs: Fs) -> &mut Self {
+        self.config.tx_fs = fs;
+        self.config.rx_fs = fs;
+        self
+    }
+    /// Frames per block. The block size is fixed at compile time to [`BLOCK_LENGTH`],
+    /// this only checks that the caller expects the same. [`build`](Self::build) fails otherwise.
+    pub fn block_size(&mut self, frames: usize) -> &mut Self {
+        self.block_size = frames;
+        self
+    }
+    /// SAI frame format, see [`Slots`]. The on-board codecs only support [`Slots::STEREO`].
+    pub fn format(&mut self, slots: Slots) -> &mut Self {
+        self.config.slots = slots;
+        self
+    }
+    /// Single channel blocks for a mono codec, see [`AudioConfig::mono`].
+    pub fn mono(&mut self, mono: bool) -> &mut Self {
+        self.config.mono = mono;
+        self
+    }
+    /// Word length on the SAI of the output and the input, see [`SampleFormat`].
+    pub fn sample_format(&mut self, tx: SampleFormat, rx: SampleFormat) -> &mut Self {
+        self.config.tx_format = tx;
+        self.config.rx_format = rx;
+        self
+    }
+    /// Framing of the SAI and the codec, see [`SaiProtocol`].
+    pub fn protocol(&mut self, protocol: SaiProtocol) -> &mut Self {
+        self.config.protocol = protocol;
+        self
+    }
+    /// Channel swap and polarity inversion, for output and input. See [`ChannelFix`].
+    pub fn channel_mode(&mut self, tx: ChannelFix, rx: ChannelFix) -> &mut Self {
+        self.config.tx_channels = tx;
+        self.config.rx_channels = rx;
+        self
+    }
+    /// Whether the MCU drives MCLK and is the clock master ([`SaiRole::Master`], the default),
+    /// or takes SCK and FS from a codec with its own oscillator ([`SaiRole::Slave`]).
+    pub fn mclk_out(&mut self, enabled: bool) -> &mut Self {
+        self.config.sai_role = if enabled {
+            SaiRole::Master
+        } else {
+            SaiRole::Slave
+        };
+        self
+    }
+    /// WM8731 outputs and headphone gain, see [`OutputRoute`].
+    pub fn output_route(&mut self, route: OutputRoute, headphone_volume_db: i8) -> &mut Self {
+        self.config.output_route = route;
+        self.config.headphone_volume_db = headphone_volume_db;
+        self
+    }
+    /// See [`AudioConfig::i2c_frequency`].
+    pub fn i2c_frequency(&mut self, frequency: Hertz) -> &mut Self {
+        self.config.i2c_frequency = frequency;
+        self
+    }
+    /// See [`AudioConfig::buffer_count`].
+    pub fn buffer_count(&mut self, count: usize) -> &mut Self {
+        self.config.buffer_count = count;
+        self
+    }
+    /// See [`AudioConfig::codec_address`].
+    pub fn codec_address(&mut self, address: u8) -> &mut Self {
+        self.config.codec_address = Some(address);
+        self
+    }
+    /// See [`AudioConfig::dma_buffers`].
+    pub fn dma_buffers(&mut self, buffers: DmaBuffers) -> &mut Self {
+        self.config.dma_buffers = Some(buffers);
+        self
+    }
+    /// The remaining settings, e.g. DMA priority and FIFO threshold.
+    pub fn config(&mut self) -> &mut AudioConfig {
+        &mut self.config
+    }
+    /// Set up the codec and the SAI. Fails if the codec doesn't respond at the configured address
+    /// or the settings can't be set up, see [`AudioError`].
+    pub async fn build<'a>(self) -> Result<(Interface<'a>, AudioBlockBuffers), AudioError> {
+        if self.block_size != BLOCK_LENGTH {
+            return Err(AudioError::UnsupportedConfig(
+                "block size is fixed to BLOCK_LENGTH",
+            ));
+        }
+        match self.wiring {
+            CodecWiring::Wm8731(pins) => Interface::new(pins, self.peripherals, self.config).await,
+            CodecWiring::Pcm3060(pins) => {
+                Interface::new_pcm3060(pins, self.peripherals, self.config).await
+            }
+            CodecWiring::Pcm3060OnSeedPins(pins) => {
+                Interface::new_pcm3060_on_seed_pins(pins, self.peripherals, self.config).await
+            }
+        }
+    }
+}
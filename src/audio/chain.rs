@@ -0,0 +1,93 @@
+//! Fixed processing chains for interleaved stereo `f32` blocks, e.g. an input stage before
+//! the patch and an output stage after it, see [`Interface::start_chain`](super::Interface::start_chain).
+//! ```ignore
+//! let mut input_stage = DcBlocker::new(DcBlocker::DEFAULT_CUTOFF_HZ, 48_000).then(Gain::new(48_000, 10.0));
+//! let mut output_stage = Limiter::new(48_000).then(ClipMode::Cubic);
+//! input_stage.second.set_gain_db(0, 6.0);
+//! ```
+//! Stages are combined at compile time, so a chain costs no more than calling them one by one.
+use super::{soft_clip, ClipMode, DcBlocker, Gain, Limiter};
+
+/// A stage of a [`Chain`], processing an interleaved stereo block in place.
+/// Implemented for the DSP building blocks, `()` (no processing) and closures.
+pub trait Processor {
+    fn process(&mut self, block: &mut [f32]);
+    /// Run `next` after this one.
+    fn then<P: Processor>(self, next: P) -> Chain<Self, P>
+    where
+        Self: Sized,
+    {
+        Chain {
+            first: self,
+            second: next,
+        }
+    }
+}
+
+/// Two stages run one after the other. Longer chains nest: `a.then(b).then(c)`.
+pub struct Chain<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A: Processor, B: Processor> Processor for Chain<A, B> {
+    fn process(&mut self, block: &mut [f32]) {
+        self.first.process(block);
+        self.second.process(block);
+    }
+}
+
+impl Processor for () {
+    fn process(&mut self, _block: &mut [f32]) {}
+}
+
+impl<F: FnMut(&mut [f32])> Processor for F {
+    fn process(&mut self, block: &mut [f32]) {
+        self(block)
+    }
+}
+
+impl Processor for Gain {
+    fn process(&mut self, block: &mut [f32]) {
+        Gain::process(self, block)
+    }
+}
+
+impl Processor for DcBlocker {
+    fn process(&mut self, block: &mut [f32]) {
+        DcBlocker::process(self, block)
+    }
+}
+
+impl Processor for Limiter {
+    fn process(&mut self, block: &mut [f32]) {
+        Limiter::process(self, block)
+    }
+}
+
+impl Processor for ClipMode {
+    fn process(&mut self, block: &mut [f32]) {
+        soft_clip(block, *self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages_run_in_order() {
+        let mut gain = Gain::new(48_000, 0.001);
+        gain.set_gain(0, 0.5);
+        gain.set_gain(1, 0.5);
+        let offset = |block: &mut [f32]| block.iter_mut().for_each(|x| *x += 0.25);
+        let mut chain = gain.then(ClipMode::Hard).then(offset).then(());
+        let mut block = [4.0f32, -0.5, 1.0, 0.2];
+        chain.process(&mut block);
+        assert_eq!(block, [1.25, 0.0, 0.75, 0.35]);
+        let mut chain = Limiter::new(48_000).then(DcBlocker::new(10.0, 48_000));
+        let mut block = [2.0f32, 0.0];
+        chain.process(&mut block);
+        assert_eq!(block, [1.0, 0.0]);
+    }
+}
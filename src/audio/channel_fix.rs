@@ -0,0 +1,109 @@
+//! Fixes for swapped or phase inverted channels, applied to the SAI's 24 bit words.
+use super::convert::{round_to_u24, u24_to_i32};
+
+/// Channel swap and polarity inversion of the first two slots of each frame.
+/// All off (the default) leaves blocks untouched without looking at the samples.
+///
+/// Mono blocks ([`AudioConfig::mono`](super::AudioConfig::mono)) have one slot per frame:
+/// `invert_polarity[0]` inverts it, `swap_channels` and `invert_polarity[1]` have no effect.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChannelFix {
+    /// Exchange left and right.
+    pub swap_channels: bool,
+    /// Invert left (`[0]`) and/or right (`[1]`), after the swap.
+    pub invert_polarity: [bool; 2],
+}
+
+impl ChannelFix {
+    pub const NONE: ChannelFix = ChannelFix {
+        swap_channels: false,
+        invert_polarity: [false; 2],
+    };
+
+    pub fn is_none(&self) -> bool {
+        *self == Self::NONE
+    }
+    /// Apply in place to an interleaved block with `slot_count` channels per frame.
+    pub fn apply(&self, block: &mut [u32], slot_count: usize) {
+        if self.is_none() || slot_count == 0 {
+            return;
+        }
+        for frame in block.chunks_exact_mut(slot_count) {
+            if self.swap_channels && slot_count > 1 {
+                frame.swap(0, 1);
+            }
+            for (smp, invert) in frame.iter_mut().zip(self.invert_polarity) {
+                if invert {
+                    *smp = invert_u24(*smp);
+                }
+            }
+        }
+    }
+}
+
+// negate a 24 bit two's complement sample, -2^23 saturates to 2^23 - 1
+fn invert_u24(sample: u32) -> u32 {
+    round_to_u24(-u24_to_i32(sample) as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{f32_to_u24, u24_to_f32};
+
+    #[test]
+    fn swap_and_invert() {
+        let mut block = [f32_to_u24(0.5), f32_to_u24(-0.25), 0x80_0000, 1];
+        ChannelFix::NONE.apply(&mut block, 2);
+        let fix = ChannelFix {
+            swap_channels: true,
+            invert_polarity: [true, false],
+        };
+        fix.apply(&mut block, 2);
+        assert!((u24_to_f32(block[0]) - 0.25).abs() < 1e-6);
+        assert!((u24_to_f32(block[1]) - 0.5).abs() < 1e-6);
+        assert_eq!(block[2], 0xFF_FFFF); // -1
+        assert_eq!(block[3], 0x80_0000);
+    }
+
+    #[test]
+    fn inverting_full_scale_saturates() {
+        let fix = ChannelFix {
+            swap_channels: false,
+            invert_polarity: [true, false],
+        };
+        let mut block = [0x80_0000u32, 0];
+        fix.apply(&mut block, 2);
+        assert_eq!(block[0], 0x7F_FFFF);
+    }
+
+    #[test]
+    fn mono_inverts_the_only_slot() {
+        let fix = ChannelFix {
+            swap_channels: true,
+            invert_polarity: [true, false],
+        };
+        let mut block = [1, 2, 0xFF_FFFF];
+        fix.apply(&mut block, 1);
+        assert_eq!(block, [0xFF_FFFF, 0xFF_FFFE, 1]);
+
+        let fix = ChannelFix {
+            swap_channels: true,
+            invert_polarity: [false, true],
+        };
+        fix.apply(&mut block, 1);
+        assert_eq!(block, [0xFF_FFFF, 0xFF_FFFE, 1]);
+    }
+
+    #[test]
+    fn tdm_leaves_slots_past_the_second() {
+        let fix = ChannelFix {
+            swap_channels: true,
+            invert_polarity: [false, true],
+        };
+        let mut block = [1, 2, 3, 4, 5, 6, 7, 8];
+        fix.apply(&mut block, 4);
+        assert_eq!(block, [2, 0xFF_FFFF, 3, 4, 6, 0xFF_FFFB, 7, 8]);
+    }
+}
@@ -0,0 +1,52 @@
+//! Output clipping, to keep overs from wrapping or hard clipping in the DAC conversion.
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClipMode {
+    /// Leave the samples alone. [`f32_to_u24`](super::f32_to_u24) still clamps to -1.0..=1.0.
+    None,
+    /// Clamp to -1.0..=1.0. Transparent below full scale, harsh above.
+    Hard,
+    /// `tanh`, smooth everywhere but already compresses at moderate levels (0.5 becomes 0.46).
+    Tanh,
+    /// Cubic `1.5x - 0.5x^3` up to full scale, clamped above. Smooth knee, cheap.
+    Cubic,
+}
+
+/// Clip `block` in place, e.g. right before [`from_f32_block`](super::from_f32_block).
+pub fn soft_clip(block: &mut [f32], mode: ClipMode) {
+    match mode {
+        ClipMode::None => {}
+        ClipMode::Hard => block.iter_mut().for_each(|smp| *smp = smp.clamp(-1.0, 1.0)),
+        ClipMode::Tanh => block.iter_mut().for_each(|smp| *smp = libm::tanhf(*smp)),
+        ClipMode::Cubic => block.iter_mut().for_each(|smp| *smp = cubic(*smp)),
+    }
+}
+
+fn cubic(x: f32) -> f32 {
+    let x = x.clamp(-1.0, 1.0);
+    1.5 * x - 0.5 * x * x * x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: [f32; 5] = [-2.0, -1.0, 0.0, 0.5, 2.0];
+
+    fn clipped(mode: ClipMode) -> [f32; 5] {
+        let mut block = INPUT;
+        soft_clip(&mut block, mode);
+        block
+    }
+
+    #[test]
+    fn modes() {
+        assert_eq!(clipped(ClipMode::None), INPUT);
+        assert_eq!(clipped(ClipMode::Hard), [-1.0, -1.0, 0.0, 0.5, 1.0]);
+        assert_eq!(clipped(ClipMode::Cubic), [-1.0, -1.0, 0.0, 0.6875, 1.0]);
+        let tanh = clipped(ClipMode::Tanh);
+        assert!((tanh[3] - 0.4621).abs() < 1e-3);
+        assert!(tanh[4] < 1.0);
+    }
+}
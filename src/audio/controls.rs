@@ -0,0 +1,85 @@
+//! Control values (knobs, CV) sampled once per audio block.
+//!
+//! An ADC task scans the controls right after the interface received a block and publishes them;
+//! the audio callback reads the complete set of the previous scan, without locks:
+//! ```ignore
+//! static CONTROLS: BlockControls<2> = BlockControls::new();
+//!
+//! #[embassy_executor::task]
+//! async fn scan(mut knobs: Knobs<'static>) {
+//!     CONTROLS.run(|i| knobs.read(if i == 0 { Knob::One } else { Knob::Two })).await
+//! }
+//!
+//! interface.start_callback(|input, output| {
+//!     let [cutoff, resonance] = CONTROLS.get();
+//!     // ...
+//! }).await;
+//! ```
+//!
+//! Timing: the scan for block `n` starts when the interface advances [`SAMPLE_CLOCK`] on receiving
+//! block `n`, as soon as the executor gets to the scan task. The values become visible in one go
+//! when the scan is finished, which is before the callback of block `n + 1` as long as the scan takes
+//! less than a block (0.67ms at 48kHz; a 16-bit conversion with `CYCLES32_5` takes about 2us).
+//! So every block sees one consistent set, at most one block old.
+use super::SAMPLE_CLOCK;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+/// `N` control values, double buffered between one writer and any number of readers.
+pub struct BlockControls<const N: usize> {
+    // f32 bits. The writer fills the slot readers don't use, then flips `current`.
+    slots: [[AtomicU32; N]; 2],
+    current: AtomicUsize,
+    scans: AtomicU32,
+}
+
+impl<const N: usize> BlockControls<N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU32 = AtomicU32::new(0);
+    #[allow(clippy::declare_interior_mutable_const)]
+    const SLOT: [AtomicU32; N] = [Self::ZERO; N];
+
+    /// All values 0.0 until the first scan.
+    pub const fn new() -> Self {
+        Self {
+            slots: [Self::SLOT; 2],
+            current: AtomicUsize::new(0),
+            scans: AtomicU32::new(0),
+        }
+    }
+    /// Publish a complete set of values.
+    ///
+    /// Only call from one task. A reader that is suspended for a whole block could otherwise see
+    /// the next set being written; readers running at audio priority are never suspended that long.
+    pub fn publish(&self, values: &[f32; N]) {
+        let next = 1 - self.current.load(Ordering::Relaxed);
+        for (slot, value) in self.slots[next].iter().zip(values) {
+            slot.store(value.to_bits(), Ordering::Relaxed);
+        }
+        self.current.store(next, Ordering::Release);
+        self.scans.fetch_add(1, Ordering::Relaxed);
+    }
+    /// The latest complete set of values.
+    pub fn get(&self) -> [f32; N] {
+        let slot = &self.slots[self.current.load(Ordering::Acquire)];
+        core::array::from_fn(|i| f32::from_bits(slot[i].load(Ordering::Relaxed)))
+    }
+    /// Number of sets published so far, wrapping. Tells a reader whether the values are new.
+    pub fn scans(&self) -> u32 {
+        self.scans.load(Ordering::Relaxed)
+    }
+    /// Read all controls with `read(index)` once per audio block and publish them, forever.
+    pub async fn run(&self, mut read: impl FnMut(usize) -> f32) -> ! {
+        loop {
+            // wakes up on the next block, whatever the block length
+            SAMPLE_CLOCK.await_samples(1).await;
+            let values = core::array::from_fn(&mut read);
+            self.publish(&values);
+        }
+    }
+}
+
+impl<const N: usize> Default for BlockControls<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,160 @@
+//! Conversion between the SAI's 24 bit samples and `f32`.
+//!
+//! The SAI is configured with `DataSize::Data24`, so each `u32` word carries
+//! a two's complement sample in its lower 24 bits.
+//!
+//! Besides `f32` there is a Q31 fixed-point path, for patches where integer arithmetic is cheaper
+//! than the FPU (e.g. many voices of multiply-accumulate). Q31 samples are `i32` in
+//! `-1.0..1.0`, the 24-bit words convert to it without loss:
+//! ```ignore
+//! let mut buf = [0i32; HALF_DMA_BUFFER_LENGTH];
+//! to_q31_block(input, &mut buf);
+//! for s in buf.iter_mut() {
+//!     *s = q31_mul(*s, gain_q31);
+//! }
+//! from_q31_block(&buf, output);
+//! ```
+//! Use [`q31_mul`] for products and the `saturating_*` methods of `i32` for sums;
+//! [`from_q31`] rounds to the nearest 24-bit value.
+
+const SCALE: f32 = 8_388_608.0; // 2^23
+pub(super) const MAX: f32 = 8_388_607.0; // 2^23 - 1
+
+/// Convert a single 24 bit sample to `f32` in the range `-1.0..1.0`.
+pub fn u24_to_f32(sample: u32) -> f32 {
+    u24_to_i32(sample) as f32 / SCALE
+}
+
+/// Convert a single `f32` sample to 24 bits. Values outside `-1.0..=1.0` are clamped.
+pub fn f32_to_u24(sample: f32) -> u32 {
+    let sample = (sample.clamp(-1.0, 1.0) * MAX) as i32;
+    (sample as u32) & 0x00FF_FFFF
+}
+
+// The SAI's 24 bit word as a sign extended integer.
+pub(super) fn u24_to_i32(sample: u32) -> i32 {
+    ((sample << 8) as i32) >> 8
+}
+
+// A sample in 24 bit steps (not -1.0..1.0) back to the SAI's word,
+// rounded to nearest and saturated at full scale.
+pub(super) fn round_to_u24(sample: f32) -> u32 {
+    let sample = libm::roundf(sample).clamp(-SCALE, MAX) as i32;
+    (sample as u32) & 0x00FF_FFFF
+}
+
+/// Convert an interleaved block received from the SAI to `f32`.
+pub fn to_f32_block(src: &[u32], dst: &mut [f32]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = u24_to_f32(*s);
+    }
+}
+
+/// Convert an interleaved `f32` block to be sent to the SAI.
+pub fn from_f32_block(src: &[f32], dst: &mut [u32]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = f32_to_u24(*s);
+    }
+}
+
+const Q31_SCALE: f32 = 2_147_483_648.0; // 2^31
+
+/// Convert a single 24 bit sample to Q31. Exact.
+pub fn to_q31(sample: u32) -> i32 {
+    (sample << 8) as i32
+}
+
+/// Convert a single Q31 sample to 24 bits, rounded to nearest.
+pub fn from_q31(sample: i32) -> u32 {
+    // saturate instead of wrapping when rounding up from the top
+    let sample = sample.saturating_add(1 << 7) >> 8;
+    (sample as u32) & 0x00FF_FFFF
+}
+
+/// Convert an `f32` sample to Q31. Values outside `-1.0..1.0` saturate.
+pub fn f32_to_q31(sample: f32) -> i32 {
+    // `as` saturates, and maps NaN to 0
+    (sample * Q31_SCALE) as i32
+}
+
+/// Convert a Q31 sample to `f32` in the range `-1.0..1.0`.
+pub fn q31_to_f32(sample: i32) -> f32 {
+    sample as f32 / Q31_SCALE
+}
+
+/// Product of two Q31 values, rounded to nearest. Only `-1.0 * -1.0` saturates.
+pub fn q31_mul(a: i32, b: i32) -> i32 {
+    let product = (a as i64 * b as i64 + (1 << 30)) >> 31;
+    product.min(i32::MAX as i64) as i32
+}
+
+/// Convert an interleaved block received from the SAI to Q31.
+pub fn to_q31_block(src: &[u32], dst: &mut [i32]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = to_q31(*s);
+    }
+}
+
+/// Convert an interleaved Q31 block to be sent to the SAI.
+pub fn from_q31_block(src: &[i32], dst: &mut [u32]) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = from_q31(*s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn q31_round_trip_is_exact() {
+        for sample in 0..=0x00FF_FFFF {
+            assert_eq!(from_q31(to_q31(sample)), sample);
+        }
+        assert_eq!(to_q31(0x7F_FFFF), 0x7FFF_FF00);
+        assert_eq!(to_q31(0x80_0000), i32::MIN);
+        assert_eq!(to_q31(0xFF_FFFF), -0x100);
+    }
+
+    #[test]
+    fn from_q31_rounds_and_saturates() {
+        assert_eq!(from_q31(0x17F), 1);
+        assert_eq!(from_q31(0x180), 2);
+        assert_eq!(from_q31(-0x181), 0xFF_FFFE);
+        assert_eq!(from_q31(-0x180), 0xFF_FFFF);
+        assert_eq!(from_q31(0x7FFF_FF80), 0x7F_FFFF);
+        assert_eq!(from_q31(i32::MAX), 0x7F_FFFF);
+        assert_eq!(from_q31(i32::MIN), 0x80_0000);
+    }
+
+    #[test]
+    fn f32_to_q31_saturates() {
+        assert_eq!(f32_to_q31(0.0), 0);
+        assert_eq!(f32_to_q31(0.5), 1 << 30);
+        assert_eq!(f32_to_q31(-1.0), i32::MIN);
+        assert_eq!(f32_to_q31(1.0), i32::MAX);
+        assert_eq!(f32_to_q31(3.0), i32::MAX);
+        assert_eq!(f32_to_q31(-3.0), i32::MIN);
+        assert_eq!(f32_to_q31(f32::NAN), 0);
+        assert_eq!(q31_to_f32(f32_to_q31(-0.375)), -0.375);
+    }
+
+    #[test]
+    fn q31_mul_rounds_and_saturates() {
+        assert_eq!(q31_mul(1 << 30, 1 << 30), 1 << 29);
+        assert_eq!(q31_mul(1, 1 << 30), 1);
+        assert_eq!(q31_mul(-1, 1 << 30), 0);
+        assert_eq!(q31_mul(12_345, i32::MIN), -12_345);
+        assert_eq!(q31_mul(i32::MAX, i32::MIN), -i32::MAX);
+        assert_eq!(q31_mul(i32::MAX, i32::MAX), i32::MAX - 1);
+        assert_eq!(q31_mul(i32::MIN, i32::MIN), i32::MAX);
+    }
+
+    #[test]
+    fn f32_to_u24_clamps() {
+        assert_eq!(f32_to_u24(1.0), 0x7F_FFFF);
+        assert_eq!(f32_to_u24(2.0), 0x7F_FFFF);
+        assert_eq!(f32_to_u24(-2.0), 0x80_0001);
+        assert_eq!(u24_to_f32(0x80_0000), -1.0);
+    }
+}
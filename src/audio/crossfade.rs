@@ -0,0 +1,132 @@
+//! Crossfade between two interleaved stereo blocks, e.g. the outputs of two patches.
+
+const CHANNELS: usize = 2;
+
+/// Shape of the crossfade.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FadeCurve {
+    /// Gains add up to 1. Right for correlated signals (the same source through two effects),
+    /// dips by 3dB in the middle for uncorrelated ones.
+    Linear,
+    /// Squared gains add up to 1 (sine/cosine). Keeps the loudness of uncorrelated signals
+    /// (two different patches) constant.
+    EqualPower,
+}
+
+/// Mixes block `a` and block `b`, moving the mix position smoothly from 0.0 (only `a`) to 1.0 (only `b`).
+///
+/// ```ignore
+/// let mut fade = Crossfade::new(48_000, 20.0, FadeCurve::EqualPower);
+/// fade.start_to(1.0); // switch to patch b
+/// fade.process(&patch_a, &patch_b, &mut out);
+/// ```
+pub struct Crossfade {
+    position: f32,
+    target: f32,
+    step: f32,
+    remaining: u32,
+    fade_samples: u32,
+    curve: FadeCurve,
+}
+
+impl Crossfade {
+    /// At position 0.0, moving over `fade_ms` when started.
+    pub fn new(sample_rate: u32, fade_ms: f32, curve: FadeCurve) -> Self {
+        let fade_samples = (sample_rate as f32 * fade_ms / 1000.0) as u32;
+        Self {
+            position: 0.0,
+            target: 0.0,
+            step: 0.0,
+            remaining: 0,
+            fade_samples: fade_samples.max(1),
+            curve,
+        }
+    }
+    /// Start moving to `position` (0.0 to 1.0). A fade in progress continues from where it is.
+    pub fn start_to(&mut self, position: f32) {
+        self.target = position.clamp(0.0, 1.0);
+        self.step = (self.target - self.position) / self.fade_samples as f32;
+        self.remaining = self.fade_samples;
+    }
+    /// Jump to `position` without a fade.
+    pub fn set(&mut self, position: f32) {
+        self.position = position.clamp(0.0, 1.0);
+        self.target = self.position;
+        self.remaining = 0;
+    }
+    /// The current mix position.
+    pub fn position(&self) -> f32 {
+        self.position
+    }
+    /// Whether a fade is in progress. Once it's done, only one of the patches needs to run
+    /// if the position is 0.0 or 1.0.
+    pub fn is_fading(&self) -> bool {
+        self.remaining > 0
+    }
+    /// Mix interleaved stereo blocks `a` and `b` into `out`. The position advances once per frame.
+    pub fn process(&mut self, a: &[f32], b: &[f32], out: &mut [f32]) {
+        let frames = out
+            .chunks_exact_mut(CHANNELS)
+            .zip(a.chunks_exact(CHANNELS).zip(b.chunks_exact(CHANNELS)));
+        for (out, (a, b)) in frames {
+            if self.remaining > 0 {
+                self.remaining -= 1;
+                self.position = if self.remaining == 0 {
+                    self.target
+                } else {
+                    self.position + self.step
+                };
+            }
+            let (gain_a, gain_b) = self.gains();
+            for ch in 0..CHANNELS {
+                out[ch] = a[ch] * gain_a + b[ch] * gain_b;
+            }
+        }
+    }
+    fn gains(&self) -> (f32, f32) {
+        match self.curve {
+            FadeCurve::Linear => (1.0 - self.position, self.position),
+            FadeCurve::EqualPower => {
+                let angle = self.position * core::f32::consts::FRAC_PI_2;
+                (libm::cosf(angle), libm::sinf(angle))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_fade() {
+        let (a, b) = ([1.0f32; 8], [-1.0f32; 8]);
+        let mut out = [0.0; 8];
+        let mut fade = Crossfade::new(1000, 4.0, FadeCurve::Linear);
+        fade.process(&a, &b, &mut out);
+        assert_eq!(out, a);
+        fade.start_to(1.0);
+        assert!(fade.is_fading());
+        fade.process(&a, &b, &mut out);
+        assert_eq!(out, [0.5, 0.5, 0.0, 0.0, -0.5, -0.5, -1.0, -1.0]);
+        assert!(!fade.is_fading());
+        assert_eq!(fade.position(), 1.0);
+        fade.set(0.0);
+        assert_eq!(fade.position(), 0.0);
+    }
+
+    #[test]
+    fn equal_power_fade() {
+        let (a, silence) = ([1.0f32; 4], [0.0f32; 4]);
+        let (mut out_a, mut out_b) = ([0.0; 4], [0.0; 4]);
+        let mut fade = Crossfade::new(1000, 2.0, FadeCurve::EqualPower);
+        fade.start_to(1.0);
+        fade.process(&a, &silence, &mut out_a);
+        let mut fade = Crossfade::new(1000, 2.0, FadeCurve::EqualPower);
+        fade.start_to(1.0);
+        fade.process(&silence, &a, &mut out_b);
+        assert!((out_a[0] * out_a[0] + out_b[0] * out_b[0] - 1.0).abs() < 1e-6);
+        assert!((out_a[0] - core::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+    }
+}
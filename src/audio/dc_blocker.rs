@@ -0,0 +1,115 @@
+//! One-pole DC blocking high-pass, for codec inputs with an offset.
+//!
+//! `y[n] = x[n] - x[n-1] + R * y[n-1]` has a zero at DC and a pole at `R` just inside the unit
+//! circle. With the pole at `R = exp(-2π * fc / fs)` (the impulse invariant mapping of an analog
+//! one-pole at `fc`), the response is 3dB down at about `fc` for cutoffs far below `fs`:
+//! 10Hz at 48kHz gives `R = 0.99869`.
+use super::convert::{round_to_u24, u24_to_i32};
+
+const CHANNELS: usize = 2;
+
+/// DC blocker for the first two channels of interleaved blocks,
+/// see [`AudioConfig::input_dc_block`](super::AudioConfig::input_dc_block).
+pub struct DcBlocker {
+    cutoff_hz: f32,
+    coefficient: f32,
+    x1: [f32; CHANNELS],
+    y1: [f32; CHANNELS],
+}
+
+impl DcBlocker {
+    /// A cutoff that removes offsets quickly without touching the audio band.
+    pub const DEFAULT_CUTOFF_HZ: f32 = 10.0;
+
+    pub fn new(cutoff_hz: f32, sample_rate: u32) -> Self {
+        let mut blocker = Self {
+            cutoff_hz,
+            coefficient: 0.0,
+            x1: [0.0; CHANNELS],
+            y1: [0.0; CHANNELS],
+        };
+        blocker.set_sample_rate(sample_rate);
+        blocker
+    }
+    /// Recalculate the coefficient for a new rate, keeping the cutoff.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.coefficient =
+            libm::expf(-2.0 * core::f32::consts::PI * self.cutoff_hz / sample_rate as f32);
+    }
+    pub fn cutoff_hz(&self) -> f32 {
+        self.cutoff_hz
+    }
+    /// The pole `R`.
+    pub fn coefficient(&self) -> f32 {
+        self.coefficient
+    }
+    /// Forget the previous samples.
+    pub fn reset(&mut self) {
+        self.x1 = [0.0; CHANNELS];
+        self.y1 = [0.0; CHANNELS];
+    }
+    /// Filter an interleaved stereo `f32` block in place.
+    pub fn process(&mut self, block: &mut [f32]) {
+        for frame in block.chunks_exact_mut(CHANNELS) {
+            for (ch, smp) in frame.iter_mut().enumerate() {
+                *smp = self.filter(ch, *smp);
+            }
+        }
+    }
+    /// Filter the first two slots of a block of the SAI's 24 bit words in place,
+    /// with `slot_count` channels per frame. The output saturates at full scale.
+    pub fn apply(&mut self, block: &mut [u32], slot_count: usize) {
+        for frame in block.chunks_exact_mut(slot_count) {
+            for (ch, smp) in frame.iter_mut().take(CHANNELS).enumerate() {
+                let x = u24_to_i32(*smp) as f32;
+                *smp = round_to_u24(self.filter(ch, x));
+            }
+        }
+    }
+    fn filter(&mut self, ch: usize, x: f32) -> f32 {
+        let y = x - self.x1[ch] + self.coefficient * self.y1[ch];
+        self.x1[ch] = x;
+        self.y1[ch] = y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_dc() {
+        let mut blocker = DcBlocker::new(10.0, 48_000);
+        let r = blocker.coefficient();
+        assert!((r - 0.998692).abs() < 1e-5);
+        let mut block = [1.0f32, 0.5, 1.0, 0.5];
+        blocker.process(&mut block);
+        assert_eq!(block[..2], [1.0, 0.5]);
+        assert!((block[2] - r).abs() < 1e-7);
+        assert!((block[3] - 0.5 * r).abs() < 1e-7);
+        let mut block = [0.5f32; 2 * 48_000];
+        blocker.reset();
+        blocker.process(&mut block);
+        assert!(block[block.len() - 1].abs() < 1e-3);
+    }
+
+    #[test]
+    fn u24_blocks() {
+        let mut blocker = DcBlocker::new(10.0, 48_000);
+        let r = blocker.coefficient();
+        let x = (-1000i32 as u32) & 0xFF_FFFF;
+        // three slots, the third is left alone
+        let mut block = [x, x, 7, x, x, 7];
+        blocker.apply(&mut block, 3);
+        assert_eq!(block[0], x);
+        assert_eq!(block[2], 7);
+        assert_eq!(block[5], 7);
+        assert_eq!(block[3], (-(1000.0 * r).round() as i32 as u32) & 0xFF_FFFF);
+        // a full swing step saturates
+        let mut blocker = DcBlocker::new(10.0, 48_000);
+        let mut block = [0x80_0000, 0, 0x7F_FFFF, 0];
+        blocker.apply(&mut block, 2);
+        assert_eq!(block[2], 0x7F_FFFF);
+    }
+}
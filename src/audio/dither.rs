@@ -0,0 +1,135 @@
+//! Dithered conversion of `f32` blocks to the SAI's 24 bit words.
+use super::convert::{f32_to_u24, round_to_u24, MAX};
+use super::NoiseRng;
+use rand_core::RngCore;
+
+/// Dither added before an `f32` sample is quantized, see [`AudioConfig::output_dither`](super::AudioConfig::output_dither).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Dither {
+    /// Plain truncation, as [`from_f32_block`](super::from_f32_block).
+    Off,
+    /// Triangular (TPDF) dither of +-1 LSB at `bits` of resolution, 8 to 24.
+    ///
+    /// Makes the quantization error independent of the signal (white noise instead of distortion)
+    /// at the cost of a noise floor 4.8dB above the undithered one. Use 24 for the on-board codecs,
+    /// or the word length of the stage after them, e.g. 16 for a 16-bit external DAC.
+    Tpdf { bits: u8 },
+}
+
+/// State of the dither, owned by whoever converts the output blocks.
+/// ```ignore
+/// let mut ditherer = Ditherer::new(interface.output_dither(), board.rng.next_u32());
+/// // in the audio callback
+/// from_f32_block_dithered(&output, tx, &mut ditherer);
+/// ```
+pub struct Ditherer {
+    dither: Dither,
+    rng: NoiseRng,
+    // one LSB at the dither's resolution, in 24-bit steps
+    step: f32,
+}
+
+impl Ditherer {
+    /// `seed` for the noise generator, e.g. from the hardware RNG.
+    pub fn new(dither: Dither, seed: u32) -> Self {
+        let step = match dither {
+            Dither::Off => 1.0,
+            Dither::Tpdf { bits } => {
+                assert!((8..=24).contains(&bits), "dither resolution out of range");
+                (1u32 << (24 - bits)) as f32
+            }
+        };
+        Self {
+            dither,
+            rng: NoiseRng::new(seed),
+            step,
+        }
+    }
+    pub fn dither(&self) -> Dither {
+        self.dither
+    }
+    /// Quantize one sample to 24 bits. Values outside `-1.0..=1.0` are clamped.
+    pub fn quantize(&mut self, sample: f32) -> u32 {
+        match self.dither {
+            Dither::Off => f32_to_u24(sample),
+            Dither::Tpdf { .. } => {
+                let scaled = sample.clamp(-1.0, 1.0) * MAX;
+                // sum of two uniform values in -0.5..0.5 LSB
+                let tpdf = self.uniform() + self.uniform();
+                let steps = libm::floorf(scaled / self.step + tpdf + 0.5);
+                // stay on the grid at full scale
+                let max_steps = libm::floorf(MAX / self.step);
+                round_to_u24(steps.clamp(-max_steps - 1.0, max_steps) * self.step)
+            }
+        }
+    }
+    // -0.5..0.5
+    fn uniform(&mut self) -> f32 {
+        (self.rng.next_u32() >> 8) as f32 / (1 << 24) as f32 - 0.5
+    }
+}
+
+/// Convert an interleaved `f32` block to be sent to the SAI, with dither.
+pub fn from_f32_block_dithered(src: &[f32], dst: &mut [u32], ditherer: &mut Ditherer) {
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d = ditherer.quantize(*s);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::convert::u24_to_i32;
+
+    const RUNS: i32 = 100_000;
+
+    #[test]
+    fn off_rounds() {
+        let mut ditherer = Ditherer::new(Dither::Off, 1);
+        assert_eq!(ditherer.quantize(0.5), f32_to_u24(0.5));
+    }
+
+    #[test]
+    fn tpdf_on_silence() {
+        let mut ditherer = Ditherer::new(Dither::Tpdf { bits: 16 }, 1);
+        let (mut sum, mut squares) = (0.0f64, 0.0f64);
+        for _ in 0..RUNS {
+            let value = u24_to_i32(ditherer.quantize(0.0));
+            // on the 16-bit grid, within +-1 LSB
+            assert_eq!(value % 256, 0);
+            assert!(value.abs() <= 256);
+            let lsb = (value / 256) as f64;
+            sum += lsb;
+            squares += lsb * lsb;
+        }
+        let mean = sum / RUNS as f64;
+        // 1/6 LSB^2 of TPDF noise plus 1/12 of rounding
+        let variance = squares / RUNS as f64;
+        assert!(mean.abs() < 0.01, "{mean}");
+        assert!((variance - 0.25).abs() < 0.02, "{variance}");
+    }
+
+    #[test]
+    fn tpdf_keeps_sub_lsb_dc() {
+        let mut ditherer = Ditherer::new(Dither::Tpdf { bits: 16 }, 1);
+        let x = 0.3 * 256.0 / MAX;
+        let sum: f64 = (0..RUNS)
+            .map(|_| (u24_to_i32(ditherer.quantize(x)) / 256) as f64)
+            .sum();
+        assert!((sum / RUNS as f64 - 0.3).abs() < 0.01);
+    }
+
+    #[test]
+    fn tpdf_full_scale() {
+        let mut ditherer = Ditherer::new(Dither::Tpdf { bits: 16 }, 1);
+        assert_eq!(u24_to_i32(ditherer.quantize(1.0)) % 256, 0);
+        let value = u24_to_i32(ditherer.quantize(-1.0));
+        assert!(value % 256 == 0 && value >= -8_388_608);
+        let mut ditherer = Ditherer::new(Dither::Tpdf { bits: 24 }, 3);
+        for _ in 0..1000 {
+            let value = u24_to_i32(ditherer.quantize(1.0));
+            assert!((8_388_606..=8_388_607).contains(&value));
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Per channel software gain for interleaved stereo blocks.
+
+const CHANNELS: usize = 2;
+
+/// Smoothed per channel linear gain.
+///
+/// When the target gain changes, the applied gain ramps linearly to it over
+/// the configured ramp time, which avoids clicks and zipper noise.
+pub struct Gain {
+    current: [f32; CHANNELS],
+    target: [f32; CHANNELS],
+    step: [f32; CHANNELS],
+    remaining: [u32; CHANNELS],
+    ramp_samples: u32,
+}
+
+impl Gain {
+    /// Unity gain on all channels, ramping over `ramp_ms` when changed.
+    pub fn new(sample_rate: u32, ramp_ms: f32) -> Self {
+        let ramp_samples = (sample_rate as f32 * ramp_ms / 1000.0) as u32;
+        Self {
+            current: [1.0; CHANNELS],
+            target: [1.0; CHANNELS],
+            step: [0.0; CHANNELS],
+            remaining: [0; CHANNELS],
+            ramp_samples: ramp_samples.max(1),
+        }
+    }
+    /// Set the target gain of `ch` in decibels.
+    pub fn set_gain_db(&mut self, ch: usize, db: f32) {
+        self.set_gain(ch, db_to_linear(db));
+    }
+    /// Set the target linear gain of `ch`.
+    pub fn set_gain(&mut self, ch: usize, gain: f32) {
+        self.target[ch] = gain;
+        self.step[ch] = (gain - self.current[ch]) / self.ramp_samples as f32;
+        self.remaining[ch] = self.ramp_samples;
+    }
+    /// The target linear gain of `ch`.
+    pub fn gain(&self, ch: usize) -> f32 {
+        self.target[ch]
+    }
+    /// Apply the gain in place to an interleaved stereo block.
+    pub fn process(&mut self, block: &mut [f32]) {
+        for frame in block.chunks_exact_mut(CHANNELS) {
+            for (ch, smp) in frame.iter_mut().enumerate() {
+                if self.remaining[ch] > 0 {
+                    self.remaining[ch] -= 1;
+                    self.current[ch] = if self.remaining[ch] == 0 {
+                        self.target[ch]
+                    } else {
+                        self.current[ch] + self.step[ch]
+                    };
+                }
+                *smp *= self.current[ch];
+            }
+        }
+    }
+}
+
+/// Amplitude gain of a level in dB, `10^(db / 20)`: 0dB is 1.0, -6dB about 0.5, +20dB 10.0.
+/// For sample values and voltages, not powers.
+pub fn db_to_linear(db: f32) -> f32 {
+    libm::powf(10.0, db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn db_to_linear_reference_points() {
+        assert_eq!(db_to_linear(0.0), 1.0);
+        assert!((db_to_linear(-6.0) - 0.501).abs() < 1e-3);
+        assert!((db_to_linear(20.0) - 10.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ramps_to_target() {
+        let mut gain = Gain::new(48_000, 1.0);
+        gain.set_gain_db(0, -6.0);
+        let mut block = [1.0f32; 200];
+        gain.process(&mut block);
+        assert!((block[0] - 1.0).abs() < 0.02);
+        assert!((block[198] - 0.501).abs() < 0.01, "{}", block[198]);
+        // the right channel is untouched
+        assert_eq!(block[199], 1.0);
+        assert_eq!(gain.gain(1), 1.0);
+    }
+}
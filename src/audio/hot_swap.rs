@@ -0,0 +1,169 @@
+//! Switching the running patch without stopping the audio, e.g. for presets or effect slots.
+//!
+//! Patches are [`Processor`]s in `static`s, handed around as `&'static mut`, so nothing is
+//! allocated or copied. A control task queues the next one in a [`PatchSlot`], and the
+//! [`PatchRunner`] in the audio callback picks it up at the next block boundary and crossfades
+//! from the old one, which both keep running until the fade is done. The old patch is then
+//! handed back through [`PatchSlot::take_retired`], to switch back to it later:
+//! ```ignore
+//! static SLOT: PatchSlot = PatchSlot::new();
+//!
+//! // audio task
+//! let mut runner = PatchRunner::new(clean, 48_000, 20.0);
+//! interface.start_chain((), (), |input, output| runner.process(&SLOT, input, output)).await;
+//!
+//! // control task
+//! SLOT.swap(distortion);
+//! ```
+//! See `examples/hot_swap.rs`.
+use super::{Crossfade, FadeCurve, Processor, HALF_DMA_BUFFER_LENGTH};
+use core::cell::Cell;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+
+/// A patch processing the input in place into the output.
+pub type Patch = &'static mut (dyn Processor + Send);
+
+/// Hand over between the control task and the [`PatchRunner`]. Put it in a `static`.
+pub struct PatchSlot {
+    pending: Mutex<CriticalSectionRawMutex, Cell<Option<Patch>>>,
+    retired: Mutex<CriticalSectionRawMutex, Cell<Option<Patch>>>,
+}
+
+impl PatchSlot {
+    pub const fn new() -> Self {
+        Self {
+            pending: Mutex::new(Cell::new(None)),
+            retired: Mutex::new(Cell::new(None)),
+        }
+    }
+    /// Queue `patch` to take over at the next block. Returns the patch queued before,
+    /// if the runner hasn't picked it up yet.
+    pub fn swap(&self, patch: Patch) -> Option<Patch> {
+        self.pending.lock(|pending| pending.replace(Some(patch)))
+    }
+    /// The patch replaced by the last swap, once its fade out is done.
+    /// Only the latest one is kept, take it before swapping again to switch back to it later.
+    pub fn take_retired(&self) -> Option<Patch> {
+        self.retired.lock(|retired| retired.take())
+    }
+    /// Whether a swapped in patch is still waiting for the next block.
+    pub fn is_pending(&self) -> bool {
+        self.pending.lock(|pending| {
+            let patch = pending.take();
+            let is_pending = patch.is_some();
+            pending.set(patch);
+            is_pending
+        })
+    }
+    fn take_pending(&self) -> Option<Patch> {
+        self.pending.lock(|pending| pending.take())
+    }
+    fn retire(&self, patch: Patch) {
+        self.retired.lock(|retired| retired.set(Some(patch)));
+    }
+}
+
+impl Default for PatchSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the current patch in the audio callback and crossfades to new ones from a [`PatchSlot`].
+pub struct PatchRunner {
+    current: Patch,
+    // fading out, still running until the fade is done
+    previous: Option<Patch>,
+    fade: Crossfade,
+    fade_out: [f32; HALF_DMA_BUFFER_LENGTH],
+    fade_in: [f32; HALF_DMA_BUFFER_LENGTH],
+}
+
+impl PatchRunner {
+    /// Start with `patch`, crossfading over `fade_ms` on every swap.
+    pub fn new(patch: Patch, sample_rate: u32, fade_ms: f32) -> Self {
+        Self {
+            current: patch,
+            previous: None,
+            fade: Crossfade::new(sample_rate, fade_ms, FadeCurve::EqualPower),
+            fade_out: [0.0; HALF_DMA_BUFFER_LENGTH],
+            fade_in: [0.0; HALF_DMA_BUFFER_LENGTH],
+        }
+    }
+    /// Process an interleaved stereo block of up to `HALF_DMA_BUFFER_LENGTH` samples, picking up
+    /// a patch swapped into `slot` first. A swap during a fade cuts the oldest patch off.
+    pub fn process(&mut self, slot: &PatchSlot, input: &[f32], output: &mut [f32]) {
+        if let Some(next) = slot.take_pending() {
+            let previous = core::mem::replace(&mut self.current, next);
+            if let Some(cut) = self.previous.replace(previous) {
+                slot.retire(cut);
+            }
+            self.fade.set(0.0);
+            self.fade.start_to(1.0);
+        }
+        let len = output.len();
+        match self.previous.take() {
+            Some(previous) => {
+                let (fade_out, fade_in) = (&mut self.fade_out[..len], &mut self.fade_in[..len]);
+                fade_out.copy_from_slice(input);
+                previous.process(fade_out);
+                fade_in.copy_from_slice(input);
+                self.current.process(fade_in);
+                self.fade.process(fade_out, fade_in, output);
+                if self.fade.is_fading() {
+                    self.previous = Some(previous);
+                } else {
+                    slot.retire(previous);
+                }
+            }
+            None => {
+                output.copy_from_slice(input);
+                self.current.process(output);
+            }
+        }
+    }
+    /// Whether a crossfade between two patches is running.
+    pub fn is_fading(&self) -> bool {
+        self.previous.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leak(patch: impl Processor + Send + 'static) -> Patch {
+        std::boxed::Box::leak(std::boxed::Box::new(patch))
+    }
+
+    #[test]
+    fn swaps_with_crossfade_and_retires() {
+        let slot = PatchSlot::new();
+        let double = leak(|block: &mut [f32]| block.iter_mut().for_each(|x| *x *= 2.0));
+        let invert = leak(|block: &mut [f32]| block.iter_mut().for_each(|x| *x = -*x));
+        // 4 frame fades
+        let mut runner = PatchRunner::new(double, 1000, 4.0);
+        let input = [1.0f32; 4];
+        let mut out = [0.0; 4];
+        runner.process(&slot, &input, &mut out);
+        assert_eq!(out, [2.0; 4]);
+        assert!(slot.swap(invert).is_none());
+        assert!(slot.is_pending());
+        runner.process(&slot, &input, &mut out);
+        assert!(!slot.is_pending());
+        assert!(runner.is_fading());
+        assert!(slot.take_retired().is_none());
+        runner.process(&slot, &input, &mut out);
+        assert!(!runner.is_fading());
+        assert!(out[2..].iter().all(|x| (x + 1.0).abs() < 1e-5));
+        // and back
+        let double = slot.take_retired().unwrap();
+        slot.swap(double);
+        for _ in 0..3 {
+            runner.process(&slot, &input, &mut out);
+        }
+        assert_eq!(out, [2.0; 4]);
+        assert!(slot.take_retired().is_some());
+    }
+}
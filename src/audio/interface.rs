@@ -0,0 +1,2008 @@
+//! The codec and SAI interface, the part of [`audio`](super) that needs the hardware.
+use super::*;
+use crate::pins::{CodecPins, Pcm3060Pins, WM8731Pins};
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use defmt::{info, warn};
+use embassy_stm32 as hal;
+use embassy_sync::{
+    blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex},
+    signal::Signal,
+    zerocopy_channel::{Channel, Receiver, Sender},
+};
+use embassy_time::{Duration, Instant, Timer};
+use grounded::uninit::GroundedArrayCell;
+use hal::sai::BitOrder;
+use hal::sai::ComplementFormat;
+use hal::sai::FifoThreshold;
+use hal::sai::FrameSyncOffset;
+use hal::{
+    dma::{word, Priority},
+    peripherals,
+    sai::{
+        self, ClockStrobe, Config, DataSize, FrameSyncDefinition, FrameSyncPolarity,
+        MasterClockDivider, Mode, Sai, SlotSize, StereoMono, TxRx,
+    },
+    time::Hertz,
+};
+use static_cell::StaticCell;
+
+// - global constants ---------------------------------------------------------
+
+const I2C_FS: Hertz = Hertz(100_000);
+// fast mode, the limit of both the WM8731 and the PCM3060
+const I2C_FS_MAX: Hertz = Hertz(400_000);
+// seed of start_chain's dither noise
+const DITHER_SEED: u32 = 0x2545_F491;
+
+// - static data --------------------------------------------------------------
+
+//DMA buffer must be in special region. Refer https://embassy.dev/book/#_stm32_bdma_only_working_out_of_some_ram_regions
+#[link_section = ".sram1_bss"]
+static mut TX_BUFFER: GroundedArrayCell<u32, DMA_BUFFER_LENGTH> = GroundedArrayCell::uninit();
+#[link_section = ".sram1_bss"]
+static mut RX_BUFFER: GroundedArrayCell<u32, DMA_BUFFER_LENGTH> = GroundedArrayCell::uninit();
+
+// Codec set up by the Interface, for emergency_mute(). 0 means no codec yet, otherwise `Codec as u8 + 1`.
+static ACTIVE_CODEC: AtomicU8 = AtomicU8::new(0);
+static ACTIVE_CODEC_ADDRESS: AtomicU8 = AtomicU8::new(0);
+static ACTIVE_SAMPLE_RATE: AtomicU32 = AtomicU32::new(0);
+static ACTIVE_SAI_SLAVE: AtomicBool = AtomicBool::new(false);
+// SCL of the codec's I2C2 is PB10 (PCM3060 pins) instead of PH4 (Seed codec pins)
+static ACTIVE_SCL_PB10: AtomicBool = AtomicBool::new(false);
+static ACTIVE_I2C_FREQUENCY: AtomicU32 = AtomicU32::new(I2C_FS.0);
+// TX DMA buffer in use, the crate's own or the one from AudioConfig::dma_buffers
+static ACTIVE_TX_BUFFER: AtomicPtr<u32> = AtomicPtr::new(core::ptr::null_mut());
+static ACTIVE_TX_BUFFER_LENGTH: AtomicUsize = AtomicUsize::new(0);
+// sample rate change requested while the interface is running, see request_reconfigure()
+static RECONFIGURE: Signal<CriticalSectionRawMutex, Fs> = Signal::new();
+// SAI restarts after an under/overrun, see sai_resync_count()
+static SAI_RESYNCS: AtomicU32 = AtomicU32::new(0);
+
+// - types --------------------------------------------------------------------
+
+pub type AudioBlockBuffers = (
+    Sender<'static, NoopRawMutex, InterleavedBlock>,
+    Receiver<'static, NoopRawMutex, InterleavedBlock>,
+);
+
+pub struct Interface<'a> {
+    sai_tx_conf: sai::Config,
+    sai_rx_conf: sai::Config,
+    // read and written directly by the loopback test
+    pub(super) sai_tx: Sai<'a, peripherals::SAI1, u32>,
+    pub(super) sai_rx: Sai<'a, peripherals::SAI1, u32>,
+    i2c: hal::i2c::I2c<'a, hal::mode::Blocking>,
+    codec: Codec,
+    codec_address: u8,
+    started: bool,
+    slot_count: usize,
+    sai_role: SaiRole,
+    tx_channels: ChannelFix,
+    rx_channels: ChannelFix,
+    channel_trim: ChannelTrim,
+    tx_format: SampleFormat,
+    rx_format: SampleFormat,
+    dc_blocker: Option<DcBlocker>,
+    balance: balance::BalanceRamp,
+    output_dither: Dither,
+    block_timestamps: bool,
+    output_route: OutputRoute,
+    headphone_volume_db: i8,
+    pub to_client: Sender<'static, NoopRawMutex, InterleavedBlock>,
+    pub from_client: Receiver<'static, NoopRawMutex, InterleavedBlock>,
+}
+
+pub struct Peripherals {
+    pub sai1: hal::peripherals::SAI1,
+    pub i2c2: hal::peripherals::I2C2,
+    pub dma1_ch1: hal::peripherals::DMA1_CH1,
+    pub dma1_ch2: hal::peripherals::DMA1_CH2,
+}
+
+// what's left of Peripherals once the I2C bus is set up
+struct SaiPeripherals {
+    sai1: hal::peripherals::SAI1,
+    dma1_ch1: hal::peripherals::DMA1_CH1,
+    dma1_ch2: hal::peripherals::DMA1_CH2,
+}
+
+impl Peripherals {
+    /// Hand the peripherals back unconfigured, for a SAI or codec setup this crate doesn't cover,
+    /// while [`new_daisy_p!`](crate::new_daisy_p) still splits up the rest of the board:
+    /// ```ignore
+    /// let daisy_p = new_daisy_p!(p);
+    /// let (sai1, dma1_ch1, dma1_ch2, i2c2) = daisy_p.audio_peripherals.into_raw();
+    /// let WM8731Pins { SCL, SDA, MCLK_A, SCK_A, FS_A, SD_A, SD_B } = daisy_p.wm8731_pin;
+    /// ```
+    /// SAI1, the DMA1 streams 1 and 2 the interface would use for tx and rx, and I2C2, the codec's
+    /// control bus. The codec pins are already public fields of [`WM8731Pins`].
+    pub fn into_raw(
+        self,
+    ) -> (
+        hal::peripherals::SAI1,
+        hal::peripherals::DMA1_CH1,
+        hal::peripherals::DMA1_CH2,
+        hal::peripherals::I2C2,
+    ) {
+        (self.sai1, self.dma1_ch1, self.dma1_ch2, self.i2c2)
+    }
+    fn split(self) -> (hal::peripherals::I2C2, SaiPeripherals) {
+        let Peripherals {
+            sai1,
+            i2c2,
+            dma1_ch1,
+            dma1_ch2,
+        } = self;
+        (
+            i2c2,
+            SaiPeripherals {
+                sai1,
+                dma1_ch1,
+                dma1_ch2,
+            },
+        )
+    }
+}
+
+/// Codec chips the interface knows how to configure over I2C.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+#[repr(u8)]
+pub enum Codec {
+    /// Daisy Seed 1.1
+    Wm8731,
+    /// Daisy Patch SM, Daisy Seed 1.2
+    Pcm3060,
+}
+
+impl Codec {
+    /// 7-bit I2C address of the codec on the Electro-Smith boards.
+    pub const fn default_address(self) -> u8 {
+        match self {
+            Codec::Wm8731 => 0x1a,  // or 0x1b if CSB is high
+            Codec::Pcm3060 => 0x46, // or 0x47 if ADR is high
+        }
+    }
+    /// Both addresses the codec can be strapped to.
+    pub const fn addresses(self) -> [u8; 2] {
+        let address = self.default_address();
+        [address, address + 1]
+    }
+    fn other(self) -> Codec {
+        match self {
+            Codec::Wm8731 => Codec::Pcm3060,
+            Codec::Pcm3060 => Codec::Wm8731,
+        }
+    }
+    // Whether the codec acknowledges `address`. Neither codec has an ID register, but their
+    // addresses don't overlap. The PCM3060's system register is read back; the WM8731 is write
+    // only and gets a reset instead, which is harmless on a codec that isn't set up yet.
+    fn probe(self, i2c: &mut hal::i2c::I2c<'_, hal::mode::Blocking>, address: u8) -> bool {
+        match self {
+            Codec::Wm8731 => try_write_wm8731_reg(i2c, address, wm8731::WM8731::reset()).is_ok(),
+            Codec::Pcm3060 => i2c
+                .blocking_write_read(address, &[PCM3060_SYS_CTRL], &mut [0])
+                .is_ok(),
+        }
+    }
+}
+
+/// The codec couldn't be set up over I2C.
+#[derive(Debug, defmt::Format)]
+pub enum CodecError {
+    /// Nothing acknowledged `address`. Check the codec address in [`AudioConfig`] and the I2C wiring.
+    NoAck {
+        codec: Codec,
+        address: u8,
+    },
+    I2c(hal::i2c::Error),
+    /// `expected` didn't answer, but the other codec did, at `address`: the constructor or board
+    /// feature doesn't match the board. See [`CodecError::hint`].
+    WrongCodec {
+        expected: Codec,
+        found: Codec,
+        address: u8,
+    },
+}
+
+impl CodecError {
+    /// What to do about the error.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            CodecError::NoAck { .. } => "check the codec address in AudioConfig and the I2C wiring",
+            CodecError::I2c(_) => "check the I2C wiring and pull-ups",
+            CodecError::WrongCodec {
+                found: Codec::Pcm3060,
+                ..
+            } => "found a PCM3060 (Daisy Seed 2 DFM or Patch SM): enable the seed_2_dfm or patch_sm feature, or use Interface::new_pcm3060_on_seed_pins",
+            CodecError::WrongCodec {
+                found: Codec::Wm8731,
+                ..
+            } => "found a WM8731 (Daisy Seed 1.1): disable the board features, or use Interface::new",
+        }
+    }
+    // After the expected codec didn't answer, look for the other one.
+    fn check_other_codec(self, i2c: &mut hal::i2c::I2c<'_, hal::mode::Blocking>) -> Self {
+        let CodecError::NoAck { codec, .. } = self else {
+            return self;
+        };
+        let other = codec.other();
+        for address in other.addresses() {
+            if other.probe(i2c, address) {
+                let e = CodecError::WrongCodec {
+                    expected: codec,
+                    found: other,
+                    address,
+                };
+                warn!("{}: {}", e, e.hint());
+                return e;
+            }
+        }
+        self
+    }
+    fn from_i2c(codec: Codec, address: u8, e: hal::i2c::Error) -> Self {
+        match e {
+            hal::i2c::Error::Nack => CodecError::NoAck { codec, address },
+            e => CodecError::I2c(e),
+        }
+    }
+}
+
+/// Setting up or reconfiguring the audio interface failed.
+#[derive(Debug, defmt::Format)]
+pub enum AudioError {
+    /// The codec didn't respond or isn't the expected one, see [`CodecError`].
+    Codec(CodecError),
+    /// The [`AudioConfig`] can't be set up on this hardware, e.g. TDM slots the SAI doesn't
+    /// support or a sample rate the SAI kernel clock can't be divided down to.
+    UnsupportedConfig(&'static str),
+    /// The [`DmaBuffers`] can't be used by DMA1.
+    DmaSetup(&'static str),
+    /// The SAI didn't respond in time, e.g. a block that doesn't stop because the clocks
+    /// it's synchronous to are missing.
+    SaiFault,
+}
+
+impl AudioError {
+    /// What to do about the error.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            AudioError::Codec(e) => e.hint(),
+            AudioError::UnsupportedConfig(_) => {
+                "check AudioConfig against the board's codec and crate::rcc clock profile"
+            }
+            AudioError::DmaSetup(_) => "see DmaBuffers for the required placement and length",
+            AudioError::SaiFault => "with SaiRole::Slave, check that the codec drives SCK and FS",
+        }
+    }
+}
+
+impl From<CodecError> for AudioError {
+    fn from(e: CodecError) -> Self {
+        AudioError::Codec(e)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Fs {
+    Fs32000,
+    Fs44100,
+    Fs48000,
+    Fs64000,
+    Fs88200,
+    Fs96000,
+    Fs128000,
+    Fs176000,
+    Fs192000,
+}
+const CLOCK_RATIO: u32 = 256; //Not yet support oversampling.
+impl Fs {
+    pub fn into_hz(&self) -> u32 {
+        match self {
+            Fs::Fs32000 => 32000,
+            Fs::Fs44100 => 44100,
+            Fs::Fs48000 => 48000,
+            Fs::Fs64000 => 64000,
+            Fs::Fs88200 => 88200,
+            Fs::Fs96000 => 96000,
+            Fs::Fs128000 => 128000,
+            Fs::Fs176000 => 176000,
+            Fs::Fs192000 => 192000,
+        }
+    }
+    pub(super) fn into_clock_divider(self) -> Result<MasterClockDivider, AudioError> {
+        self.mclk_div().map(mclk_div_from_u8)
+    }
+    fn mclk_div(&self) -> Result<u8, AudioError> {
+        let fs = self.into_hz();
+        let kernel_clock = hal::rcc::frequency::<hal::peripherals::SAI1>().0;
+        let mclk_div = kernel_clock / (fs * CLOCK_RATIO);
+        if !(1..=63).contains(&mclk_div) {
+            warn!(
+                "SAI kernel clock {}Hz can't be divided down to {}Hz",
+                kernel_clock,
+                fs * CLOCK_RATIO
+            );
+            return Err(AudioError::UnsupportedConfig(
+                "sample rate out of the SAI clock divider's range",
+            ));
+        }
+        let mclk_div = mclk_div as u8;
+        if kernel_clock % (fs * CLOCK_RATIO) != 0 {
+            warn!(
+                "SAI kernel clock {}Hz is not a multiple of {}Hz, actual sample rate will be {}Hz. Use a matching clock profile in crate::rcc.",
+                kernel_clock,
+                fs * CLOCK_RATIO,
+                kernel_clock / (mclk_div as u32 * CLOCK_RATIO)
+            );
+        }
+        Ok(mclk_div)
+    }
+}
+
+/// `rx_fs` is the rate the SAI clocks are generated at (SAI_A is the master).
+/// The SAI kernel clock has to be a multiple of `256 * fs`, see [`crate::rcc`].
+///
+/// All board features (Daisy Seed, `patch_sm`, `petal`, `seed_2_dfm`, `versio`) default to [`SaiRole::Master`]:
+/// their codecs take MCLK from the MCU and have no clock source of their own.
+///
+/// [`AudioInterfaceBuilder`] sets up an [`Interface`] from the same settings with chainable setters.
+pub struct AudioConfig {
+    pub tx_fs: Fs,
+    pub rx_fs: Fs,
+    /// 7-bit I2C address of the codec. `None` uses [`Codec::default_address`],
+    /// set it for compatible boards that strap the codec differently.
+    pub codec_address: Option<u8>,
+    /// Clock of the codec's I2C bus (I2C2), 100kHz by default, which the on-board codecs have been
+    /// run at. Both the WM8731 and the PCM3060 are specified up to 400kHz (fast mode), e.g. for
+    /// displays on the same bus; faster clocks fail with [`AudioError::UnsupportedConfig`].
+    pub i2c_frequency: Hertz,
+    /// Blocks queued in each direction between the interface and the client task, 2 to [`MAX_BUFFER_COUNT`].
+    /// More blocks give the client slack to absorb jitter (e.g. from USB) at the cost of
+    /// `BLOCK_LENGTH` samples of latency each.
+    pub buffer_count: usize,
+    /// SAI slots per frame, see [`Slots`]. The on-board codecs only support [`Slots::STEREO`].
+    pub slots: Slots,
+    /// Single channel blocks, for mono codecs on custom boards. Off by default;
+    /// all board features (Daisy Seed, `patch_sm`, `petal`, `seed_2_dfm`, `versio`) have stereo codecs.
+    ///
+    /// The SAI keeps the stereo frame (stereo [`Slots`] only) but runs in mono mode: received blocks
+    /// hold slot 0 only, and each output sample is sent in both slots. Blocks then carry
+    /// `HALF_DMA_BUFFER_LENGTH` frames of one channel ([`Interface::slot_count`] is 1), so the
+    /// latency in frames doubles. The `f32` helpers that assume stereo blocks (e.g. [`Gain`],
+    /// [`Meter`], [`Processor`]s) don't apply. Of `tx_channels` and `rx_channels` only
+    /// `invert_polarity[0]` applies, and the [balance](set_balance) has no effect.
+    pub mono: bool,
+    /// Clock role of the SAI. With [`SaiRole::Slave`] the codec is set up as the clock master and
+    /// generates SCK and FS at `rx_fs`. MCLK is not driven then, so the codec needs its own
+    /// oscillator (at 256 * fs), which only custom boards have.
+    pub sai_role: SaiRole,
+    /// DMA1 stream priority of both SAI streams, `VeryHigh` by default.
+    ///
+    /// The priority only arbitrates between streams of the same controller: DMA1 serves other
+    /// streams (e.g. SPI or ADC DMA) at the same or lower priority after the SAI requests, ties go to
+    /// the lower stream number. DMA2, MDMA (QSPI) and the USB OTG internal DMA are separate bus masters,
+    /// so contention with them is resolved in the bus matrix and isn't affected by this.
+    /// Keep the audio streams on `VeryHigh` unless another DMA1 stream is more latency critical.
+    pub dma_priority: Priority,
+    /// SAI FIFO level at which DMA requests are raised, [`FifoThreshold::Empty`] by default.
+    /// A higher threshold (e.g. `Half`) makes the SAI tolerate longer DMA stalls before it
+    /// under/overruns, at the cost of more frequent, shorter DMA bursts.
+    pub fifo_threshold: FifoThreshold,
+    /// SAI DMA buffers to use instead of the crate's own ones in D2 SRAM (`.sram1_bss`), see [`DmaBuffers`].
+    pub dma_buffers: Option<DmaBuffers>,
+    /// Channel swap and polarity inversion of the output, for miswired jacks. Off by default.
+    pub tx_channels: ChannelFix,
+    /// The same for the input.
+    pub rx_channels: ChannelFix,
+    /// Per unit level trims of the first two inputs and outputs, see [`ChannelTrim`].
+    /// [`ChannelTrim::UNITY`] by default. Set when the interface is created,
+    /// [`set_channel_trim`] changes them afterwards.
+    pub channel_trim: ChannelTrim,
+    /// Word length on the SAI of the output, [`SampleFormat::Bits24`] by default.
+    /// Can differ from `rx_format`, see [`SampleFormat`] for what that takes.
+    pub tx_format: SampleFormat,
+    /// The same for the input.
+    pub rx_format: SampleFormat,
+    /// Cutoff in Hz of a DC blocking high-pass on the input, see [`DcBlocker`]. Off (`None`) by default.
+    /// 5 to 20Hz removes codec offsets without audible effect, e.g. [`DcBlocker::DEFAULT_CUTOFF_HZ`].
+    pub input_dc_block: Option<f32>,
+    /// Framing of the SAI and the codec, [`SaiProtocol::LeftJustified`] by default.
+    /// Both on-board codecs are set up to match.
+    pub protocol: SaiProtocol,
+    /// FS polarity instead of the protocol's, for external codecs that deviate from it.
+    /// Only changes the SAI, not the on-board codec.
+    pub frame_sync_polarity: Option<FrameSyncPolarity>,
+    /// Data delay instead of the protocol's: `BeforeFirstBit` delays the data by one bit clock
+    /// after the FS edge, `OnFirstBit` doesn't. Only changes the SAI, not the on-board codec.
+    pub frame_sync_offset: Option<FrameSyncOffset>,
+    /// Record when each block arrives from the SAI, for jitter diagnostics, see [`crate::perf::block_timing`].
+    /// Off by default. Costs a few cycles per block and enables the DWT cycle counter.
+    pub block_timestamps: bool,
+    /// Dither of the `f32` to 24-bit conversion in [`Interface::start_chain`], [`Dither::Off`] by default.
+    /// With `u32` blocks the client converts, so build a [`Ditherer`] from
+    /// [`Interface::output_dither`] and convert with [`from_f32_block_dithered`] there.
+    /// `Tpdf` resolutions outside 8 to 24 bits fail with [`AudioError::UnsupportedConfig`].
+    pub output_dither: Dither,
+    /// WM8731 outputs in use, [`OutputRoute::Both`] by default, as the codec comes out of reset.
+    pub output_route: OutputRoute,
+    /// WM8731 headphone amp gain in dB, -73 to 6. 0 by default.
+    pub headphone_volume_db: i8,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            tx_fs: Fs::Fs48000,
+            rx_fs: Fs::Fs48000,
+            codec_address: None,
+            i2c_frequency: I2C_FS,
+            buffer_count: 2,
+            slots: Slots::STEREO,
+            mono: false,
+            sai_role: SaiRole::Master,
+            dma_priority: Priority::VeryHigh,
+            fifo_threshold: FifoThreshold::Empty,
+            dma_buffers: None,
+            tx_channels: ChannelFix::NONE,
+            rx_channels: ChannelFix::NONE,
+            channel_trim: ChannelTrim::UNITY,
+            tx_format: SampleFormat::Bits24,
+            rx_format: SampleFormat::Bits24,
+            input_dc_block: None,
+            protocol: SaiProtocol::LeftJustified,
+            frame_sync_polarity: None,
+            frame_sync_offset: None,
+            block_timestamps: false,
+            output_dither: Dither::Off,
+            output_route: OutputRoute::Both,
+            headphone_volume_db: 0,
+        }
+    }
+}
+
+impl AudioConfig {
+    fn checked_i2c_frequency(&self) -> Result<Hertz, AudioError> {
+        if self.i2c_frequency.0 == 0 || self.i2c_frequency.0 > I2C_FS_MAX.0 {
+            return Err(AudioError::UnsupportedConfig(
+                "codec I2C clock has to be up to 400kHz",
+            ));
+        }
+        Ok(self.i2c_frequency)
+    }
+    fn check_output_dither(&self) -> Result<(), AudioError> {
+        match self.output_dither {
+            Dither::Tpdf { bits } if !(8..=24).contains(&bits) => Err(
+                AudioError::UnsupportedConfig("dither resolution has to be 8 to 24 bits"),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<'a> Interface<'a> {
+    /// Fails if the codec doesn't respond at the configured address or `audio_config` can't be
+    /// set up, see [`AudioError`].
+    pub async fn new(
+        wm8731: WM8731Pins,
+        p: Peripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        info!("set up i2c");
+        let (i2c2, p) = p.split();
+        let i2c_config = hal::i2c::Config::default();
+        let mut i2c = embassy_stm32::i2c::I2c::new_blocking(
+            i2c2,
+            wm8731.SCL,
+            wm8731.SDA,
+            audio_config.checked_i2c_frequency()?,
+            i2c_config,
+        );
+        let address = audio_config
+            .codec_address
+            .unwrap_or(Codec::Wm8731.default_address());
+        info!("set up WM8731 at {:#x}", address);
+        setup_wm8731(
+            &mut i2c,
+            address,
+            &audio_config.rx_fs,
+            audio_config.sai_role,
+            audio_config.protocol,
+            audio_config.output_route,
+            audio_config.headphone_volume_db,
+        )
+        .await
+        .map_err(|e| CodecError::from_i2c(Codec::Wm8731, address, e).check_other_codec(&mut i2c))?;
+
+        Self::new_with_codec(
+            i2c,
+            Codec::Wm8731,
+            address,
+            CodecScl::Ph4,
+            SaiPins {
+                mclk_a: wm8731.MCLK_A,
+                sck_a: wm8731.SCK_A,
+                fs_a: wm8731.FS_A,
+                sd_a: wm8731.SD_A,
+                sd_b: wm8731.SD_B,
+            },
+            p,
+            audio_config,
+        )
+    }
+    /// Fails if the codec doesn't respond at the configured address or `audio_config` can't be
+    /// set up, see [`AudioError`].
+    pub async fn new_pcm3060(
+        pcm3060: Pcm3060Pins,
+        p: Peripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        info!("set up i2c");
+        let (i2c2, p) = p.split();
+        let i2c_config = hal::i2c::Config::default();
+        let i2c = embassy_stm32::i2c::I2c::new_blocking(
+            i2c2,
+            pcm3060.SCL,
+            pcm3060.SDA,
+            audio_config.checked_i2c_frequency()?,
+            i2c_config,
+        );
+        Self::with_pcm3060(
+            i2c,
+            CodecScl::Pb10,
+            SaiPins {
+                mclk_a: pcm3060.MCLK_A,
+                sck_a: pcm3060.SCK_A,
+                fs_a: pcm3060.FS_A,
+                sd_a: pcm3060.SD_A,
+                sd_b: pcm3060.SD_B,
+            },
+            p,
+            audio_config,
+        )
+        .await
+    }
+    /// PCM3060 on the Daisy Seed's own codec pins, as on the Seed 2 DFM.
+    /// Fails if the codec doesn't respond at the configured address or `audio_config` can't be
+    /// set up, see [`AudioError`].
+    pub async fn new_pcm3060_on_seed_pins(
+        codec_pins: CodecPins,
+        p: Peripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        info!("set up i2c");
+        let (i2c2, p) = p.split();
+        let i2c_config = hal::i2c::Config::default();
+        let i2c = embassy_stm32::i2c::I2c::new_blocking(
+            i2c2,
+            codec_pins.SCL,
+            codec_pins.SDA,
+            audio_config.checked_i2c_frequency()?,
+            i2c_config,
+        );
+        Self::with_pcm3060(
+            i2c,
+            CodecScl::Ph4,
+            SaiPins {
+                mclk_a: codec_pins.MCLK_A,
+                sck_a: codec_pins.SCK_A,
+                fs_a: codec_pins.FS_A,
+                sd_a: codec_pins.SD_A,
+                sd_b: codec_pins.SD_B,
+            },
+            p,
+            audio_config,
+        )
+        .await
+    }
+    async fn with_pcm3060(
+        mut i2c: hal::i2c::I2c<'a, hal::mode::Blocking>,
+        scl: CodecScl,
+        pins: SaiPins,
+        p: SaiPeripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        let address = audio_config
+            .codec_address
+            .unwrap_or(Codec::Pcm3060.default_address());
+        info!("set up PCM3060 at {:#x}", address);
+        setup_pcm3060(
+            &mut i2c,
+            address,
+            audio_config.sai_role,
+            audio_config.protocol,
+        )
+        .await
+        .map_err(|e| {
+            CodecError::from_i2c(Codec::Pcm3060, address, e).check_other_codec(&mut i2c)
+        })?;
+
+        Self::new_with_codec(i2c, Codec::Pcm3060, address, scl, pins, p, audio_config)
+    }
+    fn new_with_codec(
+        i2c: hal::i2c::I2c<'a, hal::mode::Blocking>,
+        codec: Codec,
+        codec_address: u8,
+        scl: CodecScl,
+        pins: SaiPins,
+        p: SaiPeripherals,
+        mut audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        audio_config.check_output_dither()?;
+        trim::store(&audio_config.channel_trim);
+        ACTIVE_CODEC_ADDRESS.store(codec_address, Ordering::Relaxed);
+        ACTIVE_SAMPLE_RATE.store(audio_config.rx_fs.into_hz(), Ordering::Relaxed);
+        ACTIVE_SAI_SLAVE.store(audio_config.sai_role == SaiRole::Slave, Ordering::Relaxed);
+        ACTIVE_SCL_PB10.store(scl == CodecScl::Pb10, Ordering::Relaxed);
+        ACTIVE_CODEC.store(codec as u8 + 1, Ordering::Relaxed);
+        let (tx_buffer, rx_buffer) = match audio_config.dma_buffers.take() {
+            Some(buffers) => buffers.check()?,
+            None => unsafe { (tx_dma_buffer(), rx_dma_buffer()) },
+        };
+        ACTIVE_TX_BUFFER.store(tx_buffer.as_mut_ptr(), Ordering::Relaxed);
+        ACTIVE_TX_BUFFER_LENGTH.store(tx_buffer.len(), Ordering::Relaxed);
+        ACTIVE_I2C_FREQUENCY.store(audio_config.i2c_frequency.0, Ordering::Relaxed);
+        let (sub_block_receiver, sub_block_transmitter) = hal::sai::split_subblocks(p.sai1);
+
+        info!("set up sai_tx");
+        let sai_tx_conf = {
+            let mut config = sai_tx_base_config();
+            apply_slots(&mut config, audio_config.slots, audio_config.sai_role)?;
+            apply_protocol(&mut config, &audio_config)?;
+            apply_formats(&mut config, &audio_config)?;
+            apply_mono(&mut config, &audio_config)?;
+            config.data_size = audio_config.tx_format.data_size();
+            config.fifo_threshold = audio_config.fifo_threshold;
+            config.master_clock_divider = audio_config.tx_fs.into_clock_divider()?;
+            config
+        };
+        let sai_tx = hal::sai::Sai::new_synchronous(
+            sub_block_transmitter,
+            pins.sd_b,
+            p.dma1_ch1,
+            tx_buffer,
+            sai_tx_conf,
+        );
+
+        info!("set up sai_rx");
+        let sai_rx_conf = {
+            //copy tx configuration
+            let mut config = sai_tx_conf;
+            //fix rx only configuration
+            config.tx_rx = TxRx::Receiver;
+            config.data_size = audio_config.rx_format.data_size();
+            config.clock_strobe = ClockStrobe::Rising;
+            config.sync_output = true;
+            match audio_config.sai_role {
+                SaiRole::Master => {
+                    config.mode = Mode::Master;
+                    config.master_clock_divider = audio_config.rx_fs.into_clock_divider()?;
+                }
+                SaiRole::Slave => config.mode = Mode::Slave,
+            }
+            config
+        };
+        let sai_rx = match audio_config.sai_role {
+            SaiRole::Master => hal::sai::Sai::new_asynchronous_with_mclk(
+                sub_block_receiver,
+                pins.sck_a,
+                pins.sd_a,
+                pins.fs_a,
+                pins.mclk_a,
+                p.dma1_ch2,
+                rx_buffer,
+                sai_rx_conf,
+            ),
+            // SCK and FS are inputs from the codec, MCLK_A stays unused
+            SaiRole::Slave => hal::sai::Sai::new_asynchronous(
+                sub_block_receiver,
+                pins.sck_a,
+                pins.sd_a,
+                pins.fs_a,
+                p.dma1_ch2,
+                rx_buffer,
+                sai_rx_conf,
+            ),
+        };
+
+        if audio_config.block_timestamps {
+            crate::perf::enable_cycle_count();
+        }
+
+        // DMA1_CH1 is the tx stream, DMA1_CH2 the rx stream
+        set_sai_dma_priority(1, audio_config.dma_priority);
+        set_sai_dma_priority(2, audio_config.dma_priority);
+
+        let buffer_count = audio_config.buffer_count.clamp(2, MAX_BUFFER_COUNT);
+        static TO_INTERFACE_BUF: StaticCell<[InterleavedBlock; MAX_BUFFER_COUNT]> =
+            StaticCell::new();
+        let to_interface_buf = &mut TO_INTERFACE_BUF
+            .init([[0; HALF_DMA_BUFFER_LENGTH]; MAX_BUFFER_COUNT])[..buffer_count];
+        static TO_INTERFACE: StaticCell<Channel<'_, NoopRawMutex, InterleavedBlock>> =
+            StaticCell::new();
+        let (client_to_if_tx, client_to_if_rx) =
+            TO_INTERFACE.init(Channel::new(to_interface_buf)).split();
+        static FROM_INTERFACE_BUF: StaticCell<[InterleavedBlock; MAX_BUFFER_COUNT]> =
+            StaticCell::new();
+        let from_interface_buf = &mut FROM_INTERFACE_BUF
+            .init([[0; HALF_DMA_BUFFER_LENGTH]; MAX_BUFFER_COUNT])[..buffer_count];
+        static FROM_INTERFACE: StaticCell<Channel<'_, NoopRawMutex, InterleavedBlock>> =
+            StaticCell::new();
+        let (if_to_client_tx, if_to_client_rx) = FROM_INTERFACE
+            .init(Channel::new(from_interface_buf))
+            .split();
+
+        Ok((
+            Self {
+                sai_rx_conf,
+                sai_tx_conf,
+                sai_rx,
+                sai_tx,
+                i2c,
+                codec,
+                codec_address,
+                started: false,
+                slot_count: if audio_config.mono {
+                    1
+                } else {
+                    audio_config.slots.count as usize
+                },
+                sai_role: audio_config.sai_role,
+                tx_channels: audio_config.tx_channels,
+                rx_channels: audio_config.rx_channels,
+                channel_trim: audio_config.channel_trim,
+                tx_format: audio_config.tx_format,
+                rx_format: audio_config.rx_format,
+                dc_blocker: audio_config
+                    .input_dc_block
+                    .map(|cutoff| DcBlocker::new(cutoff, audio_config.rx_fs.into_hz())),
+                balance: balance::BalanceRamp::new(),
+                output_dither: audio_config.output_dither,
+                block_timestamps: audio_config.block_timestamps,
+                output_route: audio_config.output_route,
+                headphone_volume_db: audio_config.headphone_volume_db,
+                to_client: if_to_client_tx,
+                from_client: client_to_if_rx,
+            },
+            (client_to_if_tx, if_to_client_rx),
+        ))
+    }
+    pub async fn start(&mut self) -> ! {
+        info!("let's set up audio callback");
+        self.start_sai_or_warn().await;
+
+        // the input for monitoring, the client's copy is gone by the time its output comes back
+        let mut monitor_input = [0; HALF_DMA_BUFFER_LENGTH];
+        info!("enter audio callback loop");
+        loop {
+            self.apply_reconfigure_request().await;
+            // Obtain a free buffer from the channel
+            let buf = self.to_client.send().await;
+            // and fill it with data
+            let read_ok = self.sai_rx.read(buf).await.is_ok();
+            if !read_ok {
+                buf.fill(0);
+            }
+            self.rx_format.widen(buf);
+            self.rx_channels.apply(buf, self.slot_count);
+            trim::refresh(&mut self.channel_trim);
+            self.channel_trim.apply_input(buf, self.slot_count);
+            if let Some(dc_blocker) = &mut self.dc_blocker {
+                dc_blocker.apply(buf, self.slot_count);
+            }
+            if self.block_timestamps {
+                crate::perf::record_block();
+            }
+            SAMPLE_CLOCK.advance(self.frames_per_block() as u32);
+            monitor_input.copy_from_slice(buf);
+            //Notify the channel that the buffer is now ready to be received
+            self.to_client.send_done();
+            // await till client audio callback task has finished processing
+            let buf = self.from_client.receive().await;
+            monitor::apply_monitor(&monitor_input, buf);
+            self.balance.apply(buf, self.slot_count);
+            self.channel_trim.apply_output(buf, self.slot_count);
+            self.tx_channels.apply(buf, self.slot_count);
+            self.tx_format.narrow(buf);
+            let write_ok = self.sai_tx.write(buf).await.is_ok();
+            self.from_client.receive_done();
+            if !read_ok || !write_ok || sai_underrun() {
+                self.resync();
+            }
+        }
+    }
+    /// Run `callback` directly in the SAI loop instead of handing blocks to another task.
+    ///
+    /// Each received block is passed as `input` and `output` is written to the SAI right after,
+    /// which skips the channel round trip and a context switch per block.
+    /// The channels returned by [`Interface::new`] are not used in this mode.
+    ///
+    /// Real-time constraints: `callback` can't `.await` and has to return well within one block
+    /// (`BLOCK_LENGTH` samples, 0.67ms at 48kHz), otherwise the SAI under/overruns.
+    /// Run this on an `InterruptExecutor` if lower priority tasks must not delay it.
+    pub async fn start_callback(
+        &mut self,
+        mut callback: impl FnMut(&InterleavedBlock, &mut InterleavedBlock),
+    ) -> ! {
+        info!("let's set up audio callback");
+        self.start_sai_or_warn().await;
+
+        let mut input = [0; HALF_DMA_BUFFER_LENGTH];
+        let mut output = [0; HALF_DMA_BUFFER_LENGTH];
+        info!("enter audio callback loop");
+        loop {
+            self.apply_reconfigure_request().await;
+            let read_ok = self.sai_rx.read(&mut input).await.is_ok();
+            if !read_ok {
+                input.fill(0);
+            }
+            self.rx_format.widen(&mut input);
+            self.rx_channels.apply(&mut input, self.slot_count);
+            trim::refresh(&mut self.channel_trim);
+            self.channel_trim.apply_input(&mut input, self.slot_count);
+            if let Some(dc_blocker) = &mut self.dc_blocker {
+                dc_blocker.apply(&mut input, self.slot_count);
+            }
+            if self.block_timestamps {
+                crate::perf::record_block();
+            }
+            SAMPLE_CLOCK.advance(self.frames_per_block() as u32);
+            callback(&input, &mut output);
+            monitor::apply_monitor(&input, &mut output);
+            self.balance.apply(&mut output, self.slot_count);
+            self.channel_trim.apply_output(&mut output, self.slot_count);
+            self.tx_channels.apply(&mut output, self.slot_count);
+            self.tx_format.narrow(&mut output);
+            let write_ok = self.sai_tx.write(&output).await.is_ok();
+            if !read_ok || !write_ok || sai_underrun() {
+                self.resync();
+            }
+        }
+    }
+    /// [`Interface::start_callback`] with `f32` blocks and fixed stages around `callback`:
+    /// `pre` processes the input before `callback` sees it, `post` the output after it.
+    /// ```ignore
+    /// let pre = DcBlocker::new(DcBlocker::DEFAULT_CUTOFF_HZ, 48_000);
+    /// let post = Limiter::new(48_000).then(ClipMode::Hard);
+    /// interface.start_chain(pre, post, |input, output| output.copy_from_slice(input)).await;
+    /// ```
+    /// Pass `()` for no stage. The stages assume stereo blocks, see [`Processor`].
+    /// The output is quantized with [`AudioConfig::output_dither`].
+    pub async fn start_chain(
+        &mut self,
+        mut pre: impl Processor,
+        mut post: impl Processor,
+        mut callback: impl FnMut(&[f32], &mut [f32]),
+    ) -> ! {
+        let mut input = [0.0; HALF_DMA_BUFFER_LENGTH];
+        let mut output = [0.0; HALF_DMA_BUFFER_LENGTH];
+        // the noise only has to be uncorrelated with the signal, any seed will do
+        let mut ditherer = Ditherer::new(self.output_dither, DITHER_SEED);
+        self.start_callback(|rx, tx| {
+            to_f32_block(rx, &mut input);
+            pre.process(&mut input);
+            callback(&input, &mut output);
+            post.process(&mut output);
+            from_f32_block_dithered(&output, tx, &mut ditherer);
+        })
+        .await
+    }
+    // enable the codec's output and start SAI, only once.
+    // The SAI is started even if the codec doesn't answer, so the block loops keep running.
+    pub(super) async fn start_sai(&mut self) -> Result<(), CodecError> {
+        if self.started {
+            return Ok(());
+        }
+        self.started = true;
+        let enabled = match self.codec {
+            Codec::Wm8731 => {
+                info!("enable WM8731 output");
+                try_write_wm8731_reg(
+                    &mut self.i2c,
+                    self.codec_address,
+                    wm8731::WM8731::power_down(final_power_settings),
+                )
+            }
+            Codec::Pcm3060 => {
+                info!("enable PCM3060 output");
+                try_write_pcm3060_reg(
+                    &mut self.i2c,
+                    self.codec_address,
+                    PCM3060_SYS_CTRL,
+                    PCM3060_SYS_ACTIVE,
+                )
+            }
+        };
+        Timer::after_micros(10).await;
+
+        info!("start SAI");
+        self.sai_tx.start();
+        self.sai_rx.start();
+        enabled.map_err(|e| CodecError::from_i2c(self.codec, self.codec_address, e))
+    }
+    // start_sai() for the block loops, which can't return an error
+    async fn start_sai_or_warn(&mut self) {
+        if let Err(e) = self.start_sai().await {
+            warn!("codec output not enabled: {} ({})", e, e.hint());
+        }
+    }
+    /// Change the sample rate without releasing the SAI, the codec or the DMA buffers.
+    ///
+    /// The codec is muted, SAI block A is stopped at a frame boundary, gets the new MCLK divider
+    /// and is restarted, then the codec is set to the new rate and unmuted. The DMA streams simply
+    /// pause meanwhile, so the output drops out for about a millisecond (mostly I2C traffic) and
+    /// blocks keep their position. Once [`Interface::start`] runs, use [`request_reconfigure`].
+    ///
+    /// Only the sample rate can be changed this way. Formats and slots change the DMA transfer size
+    /// and need a new interface. With [`SaiRole::Slave`] the codec's own clock sets the rate,
+    /// so only the codec is reconfigured.
+    ///
+    /// Fails without changing anything if the SAI kernel clock can't be divided down to `fs`.
+    pub async fn reconfigure(&mut self, fs: Fs) -> Result<(), AudioError> {
+        let codec = self.codec;
+        let address = self.codec_address;
+        info!("reconfigure to {}Hz", fs.into_hz());
+        let mckdiv = match self.sai_role {
+            SaiRole::Master => Some(fs.mclk_div()?),
+            SaiRole::Slave => None,
+        };
+        if self.started {
+            self.set_codec_muted(true)
+                .map_err(|e| CodecError::from_i2c(codec, address, e))?;
+            Timer::after_micros(10).await;
+        }
+
+        if let Some(mckdiv) = mckdiv {
+            // block A generates the clocks, block B (synchronous) pauses with it
+            let block_a = hal::pac::SAI1.ch(0);
+            block_a.cr1().modify(|w| w.set_saien(false));
+            wait_sai_disabled(0)?;
+            block_a.cr1().modify(|w| w.set_mckdiv(mckdiv));
+            if self.started {
+                block_a.cr1().modify(|w| w.set_saien(true));
+            }
+            self.sai_rx_conf.master_clock_divider = mclk_div_from_u8(mckdiv);
+            self.sai_tx_conf.master_clock_divider = mclk_div_from_u8(mckdiv);
+        }
+
+        if codec == Codec::Wm8731 {
+            // the sampling control register may only be changed while the codec is inactive
+            let i2c = &mut self.i2c;
+            try_write_wm8731_reg(i2c, address, wm8731::WM8731::active().inactive())
+                .and_then(|_| try_write_wm8731_reg(i2c, address, wm8731_sampling(&fs)))
+                .and_then(|_| try_write_wm8731_reg(i2c, address, wm8731::WM8731::active().active()))
+                .map_err(|e| CodecError::from_i2c(codec, address, e))?;
+            Timer::after_micros(10).await;
+        }
+        // PCM3060 detects the rate from its clocks in slave mode
+
+        ACTIVE_SAMPLE_RATE.store(fs.into_hz(), Ordering::Relaxed);
+        if let Some(dc_blocker) = &mut self.dc_blocker {
+            dc_blocker.set_sample_rate(fs.into_hz());
+        }
+        if self.started {
+            self.set_codec_muted(false)
+                .map_err(|e| CodecError::from_i2c(codec, address, e))?;
+        }
+        Ok(())
+    }
+    // Restart both SAI blocks after an under/overrun.
+    //
+    // A transmitter that ran dry sends whatever is left in its shift register and can come back
+    // a slot off, with swapped or shifted channels, and after a DMA overrun the ring buffer
+    // can't catch up. Disabling the blocks and flushing the FIFOs realigns them on the next frame.
+    // The DMA streams keep running, the blocks just stop requesting while disabled.
+    fn resync(&mut self) {
+        let count = SAI_RESYNCS.fetch_add(1, Ordering::Relaxed) + 1;
+        warn!("SAI under/overrun, restarting ({} so far)", count);
+        let sai = hal::pac::SAI1;
+        // block B first, it runs on block A's clocks
+        for ch in [1, 0] {
+            sai.ch(ch).cr1().modify(|w| w.set_saien(false));
+        }
+        for ch in [1, 0] {
+            if wait_sai_disabled(ch).is_err() {
+                warn!("SAI block {} didn't stop, are its clocks running?", ch);
+            }
+            sai.ch(ch).cr2().modify(|w| w.set_fflush(true));
+            sai.ch(ch).clrfr().write(|w| {
+                w.set_covrudr(true);
+                w.set_cwckcfg(true);
+                w.set_cafsdet(true);
+                w.set_clfsdet(true);
+            });
+        }
+        // block B has to be enabled before block A to start on the same frame
+        for ch in [1, 0] {
+            sai.ch(ch).cr1().modify(|w| w.set_saien(true));
+        }
+    }
+    async fn apply_reconfigure_request(&mut self) {
+        if let Some(fs) = RECONFIGURE.try_take() {
+            if let Err(e) = self.reconfigure(fs).await {
+                warn!("reconfigure failed: {}", e);
+            }
+        }
+    }
+    fn set_codec_muted(&mut self, muted: bool) -> Result<(), hal::i2c::Error> {
+        match self.codec {
+            Codec::Wm8731 => try_write_wm8731_reg(
+                &mut self.i2c,
+                self.codec_address,
+                wm8731::WM8731::digital_audio_path(|w| {
+                    if muted {
+                        w.dac_mut().enable();
+                    } else {
+                        w.dac_mut().disable();
+                    }
+                    w.deemphasis().frequency_48();
+                }),
+            ),
+            Codec::Pcm3060 => try_write_pcm3060_reg(
+                &mut self.i2c,
+                self.codec_address,
+                PCM3060_SYS_CTRL,
+                if muted {
+                    PCM3060_SYS_POWER_SAVE
+                } else {
+                    PCM3060_SYS_ACTIVE
+                },
+            ),
+        }
+    }
+    pub fn rx_config(&self) -> &sai::Config {
+        &self.sai_rx_conf
+    }
+    pub fn tx_config(&self) -> &sai::Config {
+        &self.sai_tx_conf
+    }
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+    /// I2C address the codec was found at.
+    pub fn codec_address(&self) -> u8 {
+        self.codec_address
+    }
+    pub fn sai_role(&self) -> SaiRole {
+        self.sai_role
+    }
+    /// The configured [`AudioConfig::output_dither`].
+    pub fn output_dither(&self) -> Dither {
+        self.output_dither
+    }
+    /// Switch the WM8731 outputs, see [`OutputRoute`]. Other codecs only have line outputs
+    /// and ignore this.
+    pub fn set_output_route(&mut self, route: OutputRoute) -> Result<(), CodecError> {
+        self.output_route = route;
+        self.write_headphone_volume()
+    }
+    /// WM8731 headphone amp gain in dB, -73 to 6 in 1dB steps. Stays muted while the route is
+    /// [`OutputRoute::Line`]. Other codecs ignore this.
+    pub fn set_headphone_volume(&mut self, db: i8) -> Result<(), CodecError> {
+        self.headphone_volume_db = db;
+        self.write_headphone_volume()
+    }
+    pub fn output_route(&self) -> OutputRoute {
+        self.output_route
+    }
+    fn write_headphone_volume(&mut self) -> Result<(), CodecError> {
+        if self.codec != Codec::Wm8731 {
+            return Ok(());
+        }
+        let value = wm8731_headphone_out(self.output_route, self.headphone_volume_db);
+        try_write_wm8731_raw(
+            &mut self.i2c,
+            self.codec_address,
+            WM8731_LEFT_HEADPHONE_OUT,
+            value,
+        )
+        .map_err(|e| CodecError::from_i2c(self.codec, self.codec_address, e))
+    }
+    /// Channels interleaved in each block, 2 unless TDM [`Slots`] or [`AudioConfig::mono`] are configured.
+    pub fn slot_count(&self) -> usize {
+        self.slot_count
+    }
+    /// Frames (samples per channel) in each block. `BLOCK_LENGTH` for stereo.
+    pub fn frames_per_block(&self) -> usize {
+        HALF_DMA_BUFFER_LENGTH / self.slot_count
+    }
+    /// The sample rate the SAI actually runs at, from the SAI kernel clock and the MCLK divider
+    /// currently set, e.g. 44100.04Hz instead of 44100Hz with [`crate::rcc::rcc_44100`].
+    /// Use it for tuning or rate feedback instead of the nominal [`Fs`].
+    ///
+    /// With [`SaiRole::Slave`] the codec's oscillator sets the rate, which the MCU can't see,
+    /// so this is the nominal rate then.
+    pub fn actual_sample_rate(&self) -> f32 {
+        match self.sai_role {
+            SaiRole::Master => {
+                let kernel_clock = hal::rcc::frequency::<hal::peripherals::SAI1>().0;
+                // MCKDIV 0 divides by 1 as well
+                let mckdiv = hal::pac::SAI1.ch(0).cr1().read().mckdiv().max(1);
+                kernel_clock as f32 / (mckdiv as u32 * CLOCK_RATIO) as f32
+            }
+            SaiRole::Slave => ACTIVE_SAMPLE_RATE.load(Ordering::Relaxed) as f32,
+        }
+    }
+}
+
+// Which pin the codec's I2C2 SCL is on, so emergency_mute() can find the codec again.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CodecScl {
+    // Seed codec pins (WM8731Pins / CodecPins)
+    Ph4,
+    // Pcm3060Pins
+    Pb10,
+}
+
+/// SAI1 pins, as used by the on-board codec.
+pub struct SaiPins {
+    pub mclk_a: hal::peripherals::PE2,
+    pub sck_a: hal::peripherals::PE5,
+    pub fs_a: hal::peripherals::PE4,
+    /// block A data, received
+    pub sd_a: hal::peripherals::PE6,
+    /// block B data, transmitted
+    pub sd_b: hal::peripherals::PE3,
+}
+
+// transmitter, stereo 24-bit left justified, synchronous to the receiver.
+pub(super) fn sai_tx_base_config() -> Config {
+    let mut config = Config::default();
+    config.mode = Mode::Slave;
+    config.tx_rx = TxRx::Transmitter;
+    config.stereo_mono = StereoMono::Stereo;
+    config.data_size = DataSize::Data24;
+    config.clock_strobe = ClockStrobe::Falling;
+    config.frame_sync_polarity = FrameSyncPolarity::ActiveHigh;
+    config.fifo_threshold = FifoThreshold::Empty;
+    config.sync_output = false;
+    config.bit_order = BitOrder::MsbFirst;
+    config.complement_format = ComplementFormat::OnesComplement;
+    config.frame_sync_offset = FrameSyncOffset::OnFirstBit;
+    config
+}
+
+// The HAL configures the SAI DMA streams with its default priority when the Sai is created.
+// The stream is only enabled by Sai::start(), so PL can still be changed here.
+fn set_sai_dma_priority(stream: usize, priority: Priority) {
+    use hal::pac::dma::vals::Pl;
+    let pl = match priority {
+        Priority::Low => Pl::LOW,
+        Priority::Medium => Pl::MEDIUM,
+        Priority::High => Pl::HIGH,
+        Priority::VeryHigh => Pl::VERYHIGH,
+    };
+    hal::pac::DMA1.st(stream).cr().modify(|w| w.set_pl(pl));
+}
+
+// Safety: hands out the static DMA buffers, only one SAI user may exist at a time.
+unsafe fn tx_dma_buffer() -> &'static mut [u32] {
+    TX_BUFFER.initialize_all_copied(0);
+    let (ptr, len) = TX_BUFFER.get_ptr_len();
+    core::slice::from_raw_parts_mut(ptr, len)
+}
+unsafe fn rx_dma_buffer() -> &'static mut [u32] {
+    RX_BUFFER.initialize_all_copied(0);
+    let (ptr, len) = RX_BUFFER.get_ptr_len();
+    core::slice::from_raw_parts_mut(ptr, len)
+}
+
+/// User provided SAI DMA buffers, for placement in a different memory region.
+///
+/// Each buffer holds two halves the DMA alternates between, so it has to be a multiple of
+/// `HALF_DMA_BUFFER_LENGTH` words, at least [`DMA_BUFFER_LENGTH`]. Longer buffers give the
+/// interface more slack against DMA stalls at the cost of latency; the block size stays `BLOCK_LENGTH`.
+///
+/// What DMA1 (which serves SAI1) can reach on the STM32H750, see [`crate::memory`]:
+///
+/// | region              | address       | DMA1 | note                                 |
+/// |---------------------|---------------|------|--------------------------------------|
+/// | ITCM                | `0x0000_0000` | no   | CPU only                             |
+/// | DTCM                | `0x2000_0000` | no   | CPU only, default `.bss` and stack   |
+/// | AXI SRAM (D1)       | `0x2400_0000` | yes  | D-cached if the cache is enabled     |
+/// | SRAM1-3 (D2)        | `0x3000_0000` | yes  | the crate's default, not cached      |
+/// | SRAM4 (D3)          | `0x3800_0000` | yes  | through the D2-D3 bridge, slower     |
+/// | SDRAM (FMC)         | `0xC000_0000` | yes  | needs FMC setup first, D-cached      |
+/// | internal/QSPI flash |               | no   | read only                            |
+///
+/// With the D-cache enabled, buffers in cached regions need cache maintenance
+/// or an MPU region marking them non-cacheable. The examples only enable the I-cache.
+pub struct DmaBuffers {
+    pub tx: &'static mut [u32],
+    pub rx: &'static mut [u32],
+}
+
+impl DmaBuffers {
+    // fails with the reason, a misplaced buffer would otherwise fail silently as a DMA transfer error
+    fn check(self) -> Result<(&'static mut [u32], &'static mut [u32]), AudioError> {
+        for buffer in [&*self.tx, &*self.rx] {
+            let length_ok = buffer.len() >= DMA_BUFFER_LENGTH
+                && buffer.len().is_multiple_of(HALF_DMA_BUFFER_LENGTH);
+            if !length_ok {
+                return Err(AudioError::DmaSetup(
+                    "DMA buffer length is not a multiple of two blocks",
+                ));
+            }
+            if !crate::memory::is_dma_reachable(buffer.as_ptr() as usize, buffer.len() * 4) {
+                return Err(AudioError::DmaSetup(
+                    "DMA buffer is not in DMA1 reachable memory",
+                ));
+            }
+        }
+        self.tx.fill(0);
+        self.rx.fill(0);
+        Ok((self.tx, self.rx))
+    }
+}
+
+/// Slots (channels) per SAI frame.
+///
+/// With more than 2 slots the SAI runs in TDM mode: FS is high for the first half of the frame
+/// and marks the start of slot 0, and blocks carry `count` interleaved channels.
+/// The block size in words stays the same, so a block holds `HALF_DMA_BUFFER_LENGTH / count` frames.
+/// Processing helpers like [`Gain`] and [`Meter`] assume stereo blocks.
+///
+/// `count` must be 2, 4 or 8, and a frame (`count` times the slot width) can't exceed 255 bits
+/// in the HAL, so 8 slots need 16-bit slots. With [`SaiRole::Master`] the SAI outputs MCLK,
+/// which needs a frame length that is a power of two: slots of 24-bit [`SlotSize::DataSize`]
+/// (96 and 192 bit frames) only work with [`SaiRole::Slave`]. TDM has only been checked against
+/// the SAI's register settings, not against a TDM codec; the on-board WM8731 and PCM3060 are
+/// stereo only.
+#[derive(Clone, Copy)]
+pub struct Slots {
+    pub count: u8,
+    pub size: SlotSize,
+}
+
+impl Slots {
+    /// Two slots as wide as the data. What the on-board codecs use.
+    pub const STEREO: Slots = Slots {
+        count: 2,
+        size: SlotSize::DataSize,
+    };
+}
+
+fn apply_slots(config: &mut Config, slots: Slots, role: SaiRole) -> Result<(), AudioError> {
+    config.slot_size = slots.size;
+    if slots.count == 2 {
+        // keep the default 64-bit frame, FS tells left from right
+        return Ok(());
+    }
+    let slot_bits = match slots.size {
+        SlotSize::Channel16 => 16,
+        SlotSize::Channel32 => 32,
+        SlotSize::DataSize => data_size_bits(config.data_size),
+    };
+    let frame_length = slot_bits * slots.count as u32;
+    // MCLK is 256 * fs, which only divides down to SCK for frames of a power of two bits
+    let mclk_mismatch = role == SaiRole::Master && !frame_length.is_power_of_two();
+    if !matches!(slots.count, 4 | 8) || frame_length > u8::MAX as u32 || mclk_mismatch {
+        warn!(
+            "unsupported TDM slots: {} slots, {} bit frame",
+            slots.count, frame_length
+        );
+        return Err(AudioError::UnsupportedConfig("unsupported TDM slots"));
+    }
+    config.slot_count = word::U4(slots.count);
+    config.slot_enable = ((1u32 << slots.count) - 1) as u16;
+    config.frame_length = frame_length as u8;
+    config.frame_sync_active_level_length = word::U7(frame_length as u8 / 2);
+    config.frame_sync_definition = FrameSyncDefinition::StartOfFrame;
+    Ok(())
+}
+
+/// Framing of the left and right channel within the SAI frame.
+///
+/// What the stock codecs need: the WM8731 (Seed 1.1) and the PCM3060 (Seed 1.2, Patch SM, Seed 2 DFM)
+/// support all three with 24-bit data and are set up over I2C for the chosen one. Left justified
+/// is what libDaisy uses and the default. Codecs without a control port are usually strapped
+/// to I2S or left justified by a pin, check their datasheet.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum SaiProtocol {
+    /// Left channel while FS is low, data one bit clock after the FS edge.
+    I2s,
+    /// Left channel while FS is high, data starting at the FS edge.
+    LeftJustified,
+    /// Left channel while FS is high, data ending at the next FS edge (32-bit slots).
+    /// Stereo [`Slots`] only.
+    RightJustified,
+}
+
+fn apply_protocol(config: &mut Config, audio_config: &AudioConfig) -> Result<(), AudioError> {
+    let (polarity, offset) = match audio_config.protocol {
+        SaiProtocol::I2s => (
+            FrameSyncPolarity::ActiveLow,
+            FrameSyncOffset::BeforeFirstBit,
+        ),
+        SaiProtocol::LeftJustified | SaiProtocol::RightJustified => {
+            (FrameSyncPolarity::ActiveHigh, FrameSyncOffset::OnFirstBit)
+        }
+    };
+    if audio_config.protocol == SaiProtocol::RightJustified {
+        if audio_config.slots.count != 2 {
+            return Err(AudioError::UnsupportedConfig(
+                "right justified framing needs stereo slots",
+            ));
+        }
+        // the data sits at the end of each 32-bit half frame
+        config.slot_size = SlotSize::Channel32;
+        config.first_bit_offset = word::U5(32 - data_size_bits(config.data_size) as u8);
+    }
+    config.frame_sync_polarity = audio_config.frame_sync_polarity.unwrap_or(polarity);
+    config.frame_sync_offset = audio_config.frame_sync_offset.unwrap_or(offset);
+    Ok(())
+}
+
+fn data_size_bits(data_size: DataSize) -> u32 {
+    match data_size {
+        DataSize::Data8 => 8,
+        DataSize::Data10 => 10,
+        DataSize::Data16 => 16,
+        DataSize::Data20 => 20,
+        DataSize::Data24 => 24,
+        DataSize::Data32 => 32,
+    }
+}
+
+/// Word length of one direction of the SAI, see [`AudioConfig::tx_format`].
+///
+/// Blocks exchanged with the client always hold 24-bit words, the interface converts at the
+/// DMA buffers: 16-bit input is shifted up, 16-bit output keeps the upper 16 bits (truncated).
+/// The on-board codecs keep running at 24 bits. A 16-bit direction sends or samples the first
+/// 16 bits of each 32-bit slot, which with MSB aligned framing (I2S, left justified) are the codec's
+/// upper 16 bits; the rest of the slot reads as 0. The SAI blocks share SCK and FS, so with a
+/// 16-bit direction both use 32-bit slots and only stereo, MSB aligned framing is supported.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum SampleFormat {
+    Bits16,
+    Bits24,
+}
+
+impl SampleFormat {
+    fn data_size(self) -> DataSize {
+        match self {
+            SampleFormat::Bits16 => DataSize::Data16,
+            SampleFormat::Bits24 => DataSize::Data24,
+        }
+    }
+    // received words to the client's 24-bit words
+    fn widen(self, block: &mut [u32]) {
+        if self == SampleFormat::Bits16 {
+            block.iter_mut().for_each(|smp| *smp = (*smp & 0xFFFF) << 8);
+        }
+    }
+    // the client's 24-bit words to words to send
+    fn narrow(self, block: &mut [u32]) {
+        if self == SampleFormat::Bits16 {
+            block.iter_mut().for_each(|smp| *smp = (*smp >> 8) & 0xFFFF);
+        }
+    }
+}
+
+// the SAI's mono mode only exists for frames of two slots
+fn apply_mono(config: &mut Config, audio_config: &AudioConfig) -> Result<(), AudioError> {
+    if !audio_config.mono {
+        return Ok(());
+    }
+    if audio_config.slots.count != 2 {
+        return Err(AudioError::UnsupportedConfig("mono needs stereo slots"));
+    }
+    config.stereo_mono = StereoMono::Mono;
+    Ok(())
+}
+
+// 16-bit directions need 32-bit slots, so that both blocks frame alike and the codec's 64 * fs SCK stays.
+fn apply_formats(config: &mut Config, audio_config: &AudioConfig) -> Result<(), AudioError> {
+    if audio_config.tx_format == SampleFormat::Bits24
+        && audio_config.rx_format == SampleFormat::Bits24
+    {
+        return Ok(());
+    }
+    if audio_config.slots.count != 2 || audio_config.protocol == SaiProtocol::RightJustified {
+        return Err(AudioError::UnsupportedConfig(
+            "16-bit formats need stereo slots and I2S or left justified framing",
+        ));
+    }
+    config.slot_size = SlotSize::Channel32;
+    Ok(())
+}
+
+//====================raw SAI without codec=======================================
+
+/// Clock role of SAI1 block A. Block B always runs synchronous to block A.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum SaiRole {
+    /// The MCU drives MCLK, SCK and FS.
+    Master,
+    /// SCK and FS come from the external device. MCLK is not driven.
+    Slave,
+}
+
+/// Which outputs of the WM8731 carry the signal, see [`AudioConfig::output_route`].
+///
+/// Boards wire either the line outputs (the Daisy Seed 1.1) or the headphone amp (some
+/// third-party WM8731 boards) to their jacks. The line outputs have no switch of their own, they
+/// carry the DAC whenever the outputs are powered, so only the headphone amp is switched:
+/// `Headphone` and `Both` are the same on the codec side.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum OutputRoute {
+    /// Line outputs only, the headphone amp is muted.
+    Line,
+    /// The headphone amp, at [`AudioConfig::headphone_volume_db`].
+    Headphone,
+    Both,
+}
+
+/// SAI framing for [`raw_sai`].
+///
+/// By default each frame has two 32-bit slots (left, right) with `data_size` bits of data, MSB first.
+/// Samples are right aligned in the `u32` DMA words.
+pub struct RawSaiConfig {
+    /// Sample rate. Only used for the MCLK divider in [`SaiRole::Master`].
+    pub fs: Fs,
+    pub role: SaiRole,
+    /// `Data16`, `Data24` or `Data32` are the usual ones.
+    pub data_size: DataSize,
+    /// `OnFirstBit`: FS changes with the first data bit (left justified).
+    /// `BeforeFirstBit`: FS changes one bit clock before the data (I2S).
+    pub frame_sync_offset: FrameSyncOffset,
+    /// `ActiveHigh`: the left slot is sent while FS is high (left justified).
+    /// I2S sends the left slot while FS is low (`ActiveLow`).
+    pub frame_sync_polarity: FrameSyncPolarity,
+    pub slots: Slots,
+}
+
+impl Default for RawSaiConfig {
+    /// Same framing as the on-board codecs: master, 48kHz, 24-bit left justified.
+    fn default() -> Self {
+        Self {
+            fs: Fs::Fs48000,
+            role: SaiRole::Master,
+            data_size: DataSize::Data24,
+            frame_sync_offset: FrameSyncOffset::OnFirstBit,
+            frame_sync_polarity: FrameSyncPolarity::ActiveHigh,
+            slots: Slots {
+                count: 2,
+                size: SlotSize::Channel32,
+            },
+        }
+    }
+}
+
+/// Set up SAI1 for an external ADC/DAC without touching any codec over I2C.
+///
+/// Returns `(sai_tx, sai_rx)` on block B and block A, using the same DMA buffers as [`Interface`].
+/// Nothing is started; call `start()` on both, then `write()`/`read()` interleaved blocks.
+/// Configure the external converter (if it has a control port) yourself.
+/// Fails on slots or a sample rate the SAI can't be set up for.
+pub fn raw_sai<'a>(
+    pins: SaiPins,
+    sai1: peripherals::SAI1,
+    dma1_ch1: peripherals::DMA1_CH1,
+    dma1_ch2: peripherals::DMA1_CH2,
+    config: RawSaiConfig,
+) -> Result<
+    (
+        Sai<'a, peripherals::SAI1, u32>,
+        Sai<'a, peripherals::SAI1, u32>,
+    ),
+    AudioError,
+> {
+    let (sub_block_receiver, sub_block_transmitter) = hal::sai::split_subblocks(sai1);
+
+    let mut tx_config = sai_tx_base_config();
+    tx_config.data_size = config.data_size;
+    tx_config.frame_sync_offset = config.frame_sync_offset;
+    tx_config.frame_sync_polarity = config.frame_sync_polarity;
+    apply_slots(&mut tx_config, config.slots, config.role)?;
+
+    let mut rx_config = tx_config;
+    rx_config.tx_rx = TxRx::Receiver;
+    rx_config.clock_strobe = ClockStrobe::Rising;
+    rx_config.sync_output = true;
+
+    let sai_tx = Sai::new_synchronous(
+        sub_block_transmitter,
+        pins.sd_b,
+        dma1_ch1,
+        unsafe { tx_dma_buffer() },
+        tx_config,
+    );
+    let sai_rx = match config.role {
+        SaiRole::Master => {
+            rx_config.mode = Mode::Master;
+            rx_config.master_clock_divider = config.fs.into_clock_divider()?;
+            Sai::new_asynchronous_with_mclk(
+                sub_block_receiver,
+                pins.sck_a,
+                pins.sd_a,
+                pins.fs_a,
+                pins.mclk_a,
+                dma1_ch2,
+                unsafe { rx_dma_buffer() },
+                rx_config,
+            )
+        }
+        SaiRole::Slave => {
+            rx_config.mode = Mode::Slave;
+            Sai::new_asynchronous(
+                sub_block_receiver,
+                pins.sck_a,
+                pins.sd_a,
+                pins.fs_a,
+                dma1_ch2,
+                unsafe { rx_dma_buffer() },
+                rx_config,
+            )
+        }
+    };
+    Ok((sai_tx, sai_rx))
+}
+
+//====================wm8731 register set up functions============================
+async fn setup_wm8731<'a>(
+    i2c: &mut hal::i2c::I2c<'a, hal::mode::Blocking>,
+    address: u8,
+    fs: &Fs,
+    sai_role: SaiRole,
+    protocol: SaiProtocol,
+    output_route: OutputRoute,
+    headphone_volume_db: i8,
+) -> Result<(), hal::i2c::Error> {
+    use wm8731::WM8731;
+    info!("setup wm8731 from I2C");
+
+    Timer::after_micros(10).await;
+
+    // reset. Also tells whether the codec is there at all.
+    try_write_wm8731_reg(i2c, address, WM8731::reset())?;
+    Timer::after_micros(10).await;
+
+    // wakeup
+    try_write_wm8731_reg(
+        i2c,
+        address,
+        WM8731::power_down(|w| {
+            final_power_settings(w);
+            //output off before start()
+            w.output().power_off();
+        }),
+    )?;
+    Timer::after_micros(10).await;
+
+    // disable input mute, set to 0dB gain
+    try_write_wm8731_reg(
+        i2c,
+        address,
+        WM8731::left_line_in(|w| {
+            w.both().enable();
+            w.mute().disable();
+            w.volume().nearest_dB(0);
+        }),
+    )?;
+    Timer::after_micros(10).await;
+
+    // sidetone off; DAC selected; bypass off; line input selected; mic muted; mic boost off
+    try_write_wm8731_reg(
+        i2c,
+        address,
+        WM8731::analog_audio_path(|w| {
+            w.sidetone().disable();
+            w.dac_select().select();
+            w.bypass().disable();
+            w.input_select().line_input();
+            w.mute_mic().enable();
+            w.mic_boost().disable();
+        }),
+    )?;
+    Timer::after_micros(10).await;
+
+    // disable DAC mute, deemphasis for 48k
+    try_write_wm8731_reg(
+        i2c,
+        address,
+        WM8731::digital_audio_path(|w| {
+            w.dac_mut().disable();
+            w.deemphasis().frequency_48();
+        }),
+    )?;
+    Timer::after_micros(10).await;
+
+    // nothing inverted, 24-bits, framing as the SAI. The codec is the clock master when the SAI is slave.
+    try_write_wm8731_reg(
+        i2c,
+        address,
+        WM8731::digital_audio_interface_format(|w| {
+            w.bit_clock_invert().no_invert();
+            match sai_role {
+                SaiRole::Master => w.master_slave().slave(),
+                SaiRole::Slave => w.master_slave().master(),
+            };
+            w.left_right_dac_clock_swap().right_channel_dac_data_right();
+            w.left_right_phase().data_when_daclrc_low();
+            w.bit_length().bits_24();
+            match protocol {
+                SaiProtocol::I2s => w.format().i2s(),
+                SaiProtocol::LeftJustified => w.format().left_justified(),
+                SaiProtocol::RightJustified => w.format().right_justified(),
+            };
+        }),
+    )?;
+    Timer::after_micros(10).await;
+
+    try_write_wm8731_reg(i2c, address, wm8731_sampling(fs))?;
+    Timer::after_micros(10).await;
+
+    // headphone amp, both channels at once
+    try_write_wm8731_raw(
+        i2c,
+        address,
+        WM8731_LEFT_HEADPHONE_OUT,
+        wm8731_headphone_out(output_route, headphone_volume_db),
+    )?;
+    Timer::after_micros(10).await;
+
+    // set active
+    try_write_wm8731_reg(i2c, address, WM8731::active().active())?;
+    Timer::after_micros(10).await;
+
+    //Note: WM8731's output not yet enabled.
+    Ok(())
+}
+fn try_write_wm8731_reg(
+    i2c: &mut hal::i2c::I2c<'_, hal::mode::Blocking>,
+    address: u8,
+    r: wm8731::Register,
+) -> Result<(), hal::i2c::Error> {
+    try_write_wm8731_raw(i2c, address, r.address, r.value)
+}
+// for registers the wm8731 crate doesn't cover
+fn try_write_wm8731_raw(
+    i2c: &mut hal::i2c::I2c<'_, hal::mode::Blocking>,
+    address: u8,
+    register: u8,
+    value: u16,
+) -> Result<(), hal::i2c::Error> {
+    // WM8731 has 16 bits registers.
+    // The first 7 bits are for the addresses, and the rest 9 bits are for the "value"s.
+    // Let's pack the register into 16 bits.
+    let byte1: u8 = ((register << 1) & 0b1111_1110) | (((value >> 8) & 0b0000_0001) as u8);
+    let byte2: u8 = (value & 0b1111_1111) as u8;
+    i2c.blocking_write(address, &[byte1, byte2])
+}
+const WM8731_LEFT_HEADPHONE_OUT: u8 = 0x02;
+const WM8731_HP_BOTH: u16 = 1 << 8; // LRHPBOTH, load the right channel too
+const WM8731_HP_0DB: i16 = 0b111_1001;
+// Codes below -73dB mute the headphone amp.
+fn wm8731_headphone_out(route: OutputRoute, volume_db: i8) -> u16 {
+    let code = match route {
+        OutputRoute::Line => 0,
+        OutputRoute::Headphone | OutputRoute::Both => {
+            WM8731_HP_0DB + volume_db.clamp(-73, 6) as i16
+        }
+    };
+    WM8731_HP_BOTH | code as u16
+}
+// MCLK is always 256fs.
+fn wm8731_sampling(fs: &Fs) -> wm8731::Register {
+    if fs.into_hz() <= 48000 {
+        // no clock division, normal mode, 256fs
+        wm8731::WM8731::sampling(|w| {
+            w.core_clock_divider_select().normal();
+            w.base_oversampling_rate().normal_256();
+            w.sample_rate().adc_48();
+            w.usb_normal().normal();
+        })
+    } else {
+        // MCLK(24.576MHz or 22.5792MHz) exceeds the core clock limit.
+        // Divide it by 2 (CLKIDIV2) and select 128fs (SR = 0b0111), normal mode.
+        wm8731::Register {
+            address: 0x08,
+            value: 0b0101_1100,
+        }
+    }
+}
+fn final_power_settings(w: &mut wm8731::power_down::PowerDown) {
+    w.power_off().power_on();
+    w.clock_output().power_off();
+    w.oscillator().power_off();
+    w.output().power_on();
+    w.dac().power_on();
+    w.adc().power_on();
+    w.mic().power_off();
+    w.line_input().power_on();
+}
+
+//====================pcm3060 register set up functions===========================
+const PCM3060_SYS_CTRL: u8 = 0x40; // MRST, SRST, ADPSV, DAPSV, SE
+const PCM3060_DAC_CTRL1: u8 = 0x43; // CSEL2, MS2, FMT2
+const PCM3060_ADC_CTRL1: u8 = 0x48; // CSEL1, MS1, FMT1
+
+// MRST and SRST are active low. Keep both high and clear the power save bits.
+const PCM3060_SYS_ACTIVE: u8 = 0b1100_0000;
+// ADC and DAC powered down(ADPSV, DAPSV) while the interface is being set up.
+const PCM3060_SYS_POWER_SAVE: u8 = 0b1111_0000;
+// FMT bits, slave mode
+const PCM3060_FMT_24BIT_I2S: u8 = 0b0000_0000;
+const PCM3060_FMT_24BIT_LEFT_JUSTIFIED: u8 = 0b0000_0001;
+const PCM3060_FMT_24BIT_RIGHT_JUSTIFIED: u8 = 0b0000_0010;
+// MS bits: master mode with SCK = 256fs (requires SCKI = 256fs)
+const PCM3060_MS_MASTER_256FS: u8 = 0b0100_0000;
+
+async fn setup_pcm3060<'a>(
+    i2c: &mut hal::i2c::I2c<'a, hal::mode::Blocking>,
+    address: u8,
+    sai_role: SaiRole,
+    protocol: SaiProtocol,
+) -> Result<(), hal::i2c::Error> {
+    info!("setup pcm3060 from I2C");
+    let fmt = match protocol {
+        SaiProtocol::I2s => PCM3060_FMT_24BIT_I2S,
+        SaiProtocol::LeftJustified => PCM3060_FMT_24BIT_LEFT_JUSTIFIED,
+        SaiProtocol::RightJustified => PCM3060_FMT_24BIT_RIGHT_JUSTIFIED,
+    };
+    let format = match sai_role {
+        SaiRole::Master => fmt,
+        SaiRole::Slave => PCM3060_MS_MASTER_256FS | fmt,
+    };
+
+    Timer::after_micros(10).await;
+
+    // mode control register reset. Also tells whether the codec is there at all.
+    try_write_pcm3060_reg(
+        i2c,
+        address,
+        PCM3060_SYS_CTRL,
+        PCM3060_SYS_POWER_SAVE & !(1 << 7),
+    )?;
+    Timer::after_millis(1).await;
+
+    // release reset, stay in power save until start()
+    try_write_pcm3060_reg(i2c, address, PCM3060_SYS_CTRL, PCM3060_SYS_POWER_SAVE)?;
+    Timer::after_micros(10).await;
+
+    // DAC: 24-bit, slave unless the SAI is
+    try_write_pcm3060_reg(i2c, address, PCM3060_DAC_CTRL1, format)?;
+    Timer::after_micros(10).await;
+
+    // ADC: 24-bit, slave unless the SAI is
+    try_write_pcm3060_reg(i2c, address, PCM3060_ADC_CTRL1, format)?;
+    Timer::after_micros(10).await;
+
+    //Note: PCM3060's ADC and DAC are still in power save mode.
+    Ok(())
+}
+fn try_write_pcm3060_reg(
+    i2c: &mut hal::i2c::I2c<'_, hal::mode::Blocking>,
+    address: u8,
+    reg: u8,
+    value: u8,
+) -> Result<(), hal::i2c::Error> {
+    i2c.blocking_write(address, &[reg, value])
+}
+
+/// Ask the running interface ([`Interface::start`] or [`Interface::start_callback`]) to change
+/// the sample rate, e.g. when a USB host selects a different rate. Applied before the next block,
+/// see [`Interface::reconfigure`]. A newer request replaces one that wasn't applied yet.
+pub fn request_reconfigure(fs: Fs) {
+    RECONFIGURE.signal(fs);
+}
+
+/// Where the audio interface is in bringing up audio, see [`audio_state`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum AudioState {
+    /// No [`Interface`] yet, or the codec didn't respond.
+    Idle,
+    /// The codec is set up over I2C, the SAI hasn't delivered a block yet.
+    CodecReady,
+    /// The first block has been received from the SAI, audio is flowing.
+    Running,
+}
+
+pub fn audio_state() -> AudioState {
+    if SAMPLE_CLOCK.samples_elapsed() > 0 {
+        AudioState::Running
+    } else if ACTIVE_CODEC.load(Ordering::Relaxed) != 0 {
+        AudioState::CodecReady
+    } else {
+        AudioState::Idle
+    }
+}
+
+/// Wait until audio is live: the codec init sequence has completed and the first DMA transfer
+/// from the SAI has arrived ([`AudioState::Running`]). Returns right away after that.
+///
+/// The interface itself is busy in [`Interface::start`], so this is a free function for other
+/// tasks, e.g. a UI showing "ready":
+/// ```ignore
+/// join(interface.start(), async {
+///     await_ready().await;
+///     led.on();
+/// })
+/// ```
+pub async fn await_ready() {
+    SAMPLE_CLOCK.await_until(1).await;
+}
+
+/// How often the running interface restarted the SAI after an underrun (the client was late with a block)
+/// or overrun (the interface task was late), since power up. Each one is a short dropout.
+pub fn sai_resync_count() -> u32 {
+    SAI_RESYNCS.load(Ordering::Relaxed)
+}
+
+// under/overrun flag of block A (receiver) or block B (transmitter)
+fn sai_underrun() -> bool {
+    let sai = hal::pac::SAI1;
+    sai.ch(0).sr().read().ovrudr() || sai.ch(1).sr().read().ovrudr()
+}
+
+// SAIEN reads back as set until the current frame is finished. Without clocks it never clears,
+// a frame takes 31us at 32kHz.
+fn wait_sai_disabled(ch: usize) -> Result<(), AudioError> {
+    let start = Instant::now();
+    while hal::pac::SAI1.ch(ch).cr1().read().saien() {
+        if start.elapsed() > Duration::from_millis(1) {
+            return Err(AudioError::SaiFault);
+        }
+    }
+    Ok(())
+}
+
+// codec and its I2C address set up by the Interface, if any.
+pub(crate) fn active_codec() -> Option<(Codec, u8)> {
+    let codec = match ACTIVE_CODEC.load(Ordering::Relaxed) {
+        1 => Codec::Wm8731,
+        2 => Codec::Pcm3060,
+        _ => return None,
+    };
+    Some((codec, ACTIVE_CODEC_ADDRESS.load(Ordering::Relaxed)))
+}
+// sample rate the SAI clocks were set up for, 0 before the Interface is created.
+pub(crate) fn active_sample_rate() -> u32 {
+    ACTIVE_SAMPLE_RATE.load(Ordering::Relaxed)
+}
+pub(crate) fn active_sai_role() -> SaiRole {
+    if ACTIVE_SAI_SLAVE.load(Ordering::Relaxed) {
+        SaiRole::Slave
+    } else {
+        SaiRole::Master
+    }
+}
+
+//====================emergency mute===============================================
+
+/// Silence the audio output from a panic (or hard fault) handler.
+///
+/// Zeroes the TX DMA buffer in use (the crate's own or [`AudioConfig::dma_buffers`]), so the still
+/// running DMA keeps sending silence, then mutes the codec over I2C at the configured clock. I2C errors are ignored, as there is nothing left to do about them.
+/// Does nothing if no [`Interface`] has been created yet.
+///
+/// `panic_probe` doesn't provide a hook, so install your own panic handler instead:
+/// ```ignore
+/// #[panic_handler]
+/// fn panic(info: &core::panic::PanicInfo) -> ! {
+///     unsafe { daisy_embassy::audio::emergency_mute() };
+///     defmt::error!("{}", defmt::Display2Format(info));
+///     cortex_m::asm::udf()
+/// }
+/// ```
+///
+/// # Safety
+/// This steals the SAI's DMA buffer, I2C2 and its pins from whoever owns them.
+/// Only call it when the program is not going to continue, e.g. from a panic handler.
+pub unsafe fn emergency_mute() {
+    let Some((codec, address)) = active_codec() else {
+        return;
+    };
+
+    let tx_buffer = ACTIVE_TX_BUFFER.load(Ordering::Relaxed);
+    for i in 0..ACTIVE_TX_BUFFER_LENGTH.load(Ordering::Relaxed) {
+        tx_buffer.add(i).write_volatile(0);
+    }
+
+    let i2c_frequency = Hertz(ACTIVE_I2C_FREQUENCY.load(Ordering::Relaxed));
+    let i2c2 = peripherals::I2C2::steal();
+    let sda = peripherals::PB11::steal();
+    let i2c_config = hal::i2c::Config::default();
+    let mut i2c = if ACTIVE_SCL_PB10.load(Ordering::Relaxed) {
+        let scl = peripherals::PB10::steal();
+        hal::i2c::I2c::new_blocking(i2c2, scl, sda, i2c_frequency, i2c_config)
+    } else {
+        let scl = peripherals::PH4::steal();
+        hal::i2c::I2c::new_blocking(i2c2, scl, sda, i2c_frequency, i2c_config)
+    };
+    match codec {
+        Codec::Wm8731 => {
+            let _ = try_write_wm8731_reg(
+                &mut i2c,
+                address,
+                wm8731::WM8731::digital_audio_path(|w| {
+                    w.dac_mut().enable();
+                    w.deemphasis().frequency_48();
+                }),
+            );
+            let _ = try_write_wm8731_reg(
+                &mut i2c,
+                address,
+                wm8731::WM8731::power_down(|w| {
+                    final_power_settings(w);
+                    w.output().power_off();
+                }),
+            );
+        }
+        Codec::Pcm3060 => {
+            let _ =
+                try_write_pcm3060_reg(&mut i2c, address, PCM3060_SYS_CTRL, PCM3060_SYS_POWER_SAVE);
+        }
+    }
+}
+
+//================================================
+
+const fn mclk_div_from_u8(v: u8) -> MasterClockDivider {
+    match v {
+        1 => MasterClockDivider::Div1,
+        2 => MasterClockDivider::Div2,
+        3 => MasterClockDivider::Div3,
+        4 => MasterClockDivider::Div4,
+        5 => MasterClockDivider::Div5,
+        6 => MasterClockDivider::Div6,
+        7 => MasterClockDivider::Div7,
+        8 => MasterClockDivider::Div8,
+        9 => MasterClockDivider::Div9,
+        10 => MasterClockDivider::Div10,
+        11 => MasterClockDivider::Div11,
+        12 => MasterClockDivider::Div12,
+        13 => MasterClockDivider::Div13,
+        14 => MasterClockDivider::Div14,
+        15 => MasterClockDivider::Div15,
+        16 => MasterClockDivider::Div16,
+        17 => MasterClockDivider::Div17,
+        18 => MasterClockDivider::Div18,
+        19 => MasterClockDivider::Div19,
+        20 => MasterClockDivider::Div20,
+        21 => MasterClockDivider::Div21,
+        22 => MasterClockDivider::Div22,
+        23 => MasterClockDivider::Div23,
+        24 => MasterClockDivider::Div24,
+        25 => MasterClockDivider::Div25,
+        26 => MasterClockDivider::Div26,
+        27 => MasterClockDivider::Div27,
+        28 => MasterClockDivider::Div28,
+        29 => MasterClockDivider::Div29,
+        30 => MasterClockDivider::Div30,
+        31 => MasterClockDivider::Div31,
+        32 => MasterClockDivider::Div32,
+        33 => MasterClockDivider::Div33,
+        34 => MasterClockDivider::Div34,
+        35 => MasterClockDivider::Div35,
+        36 => MasterClockDivider::Div36,
+        37 => MasterClockDivider::Div37,
+        38 => MasterClockDivider::Div38,
+        39 => MasterClockDivider::Div39,
+        40 => MasterClockDivider::Div40,
+        41 => MasterClockDivider::Div41,
+        42 => MasterClockDivider::Div42,
+        43 => MasterClockDivider::Div43,
+        44 => MasterClockDivider::Div44,
+        45 => MasterClockDivider::Div45,
+        46 => MasterClockDivider::Div46,
+        47 => MasterClockDivider::Div47,
+        48 => MasterClockDivider::Div48,
+        49 => MasterClockDivider::Div49,
+        50 => MasterClockDivider::Div50,
+        51 => MasterClockDivider::Div51,
+        52 => MasterClockDivider::Div52,
+        53 => MasterClockDivider::Div53,
+        54 => MasterClockDivider::Div54,
+        55 => MasterClockDivider::Div55,
+        56 => MasterClockDivider::Div56,
+        57 => MasterClockDivider::Div57,
+        58 => MasterClockDivider::Div58,
+        59 => MasterClockDivider::Div59,
+        60 => MasterClockDivider::Div60,
+        61 => MasterClockDivider::Div61,
+        62 => MasterClockDivider::Div62,
+        63 => MasterClockDivider::Div63,
+        _ => panic!(),
+    }
+}
@@ -0,0 +1,73 @@
+//! Latency and memory footprint of an audio configuration, usable in `const` context:
+//! ```ignore
+//! const LATENCY: f32 = audio::latency_ms(48_000, audio::BLOCK_LENGTH, 3);
+//! // fails to compile if the DMA buffers don't fit in D2 SRAM
+//! const _: () = audio::assert_dma_fits(256, 8);
+//! ```
+
+/// Size of the DMA capable D2 SRAM the SAI buffers are placed in (`.sram1_bss`, see `memory.x`).
+pub const DMA_SRAM_BYTES: usize = 288 * 1024;
+
+// one u32 word per sample
+const WORD_BYTES: usize = 4;
+// both directions are double buffered: the DMA works on one half, the client on the other
+const DMA_HALVES: usize = 2;
+
+/// Worst case input to output latency in frames: one block to fill the rx DMA half,
+/// `buffer_count` blocks queued between interface and client, and one block in the tx DMA half.
+pub const fn latency_frames(block_length: usize, buffer_count: usize) -> usize {
+    block_length * (buffer_count + 2)
+}
+
+/// [`latency_frames`] in microseconds at `sample_rate`.
+pub const fn latency_us(sample_rate: u32, block_length: usize, buffer_count: usize) -> u32 {
+    (latency_frames(block_length, buffer_count) as u64 * 1_000_000 / sample_rate as u64) as u32
+}
+
+/// [`latency_frames`] in milliseconds at `sample_rate`.
+pub const fn latency_ms(sample_rate: u32, block_length: usize, buffer_count: usize) -> f32 {
+    latency_frames(block_length, buffer_count) as f32 * 1000.0 / sample_rate as f32
+}
+
+/// Bytes of the rx and tx DMA buffers, which have to be in DMA capable SRAM.
+pub const fn dma_buffer_bytes(block_length: usize, channels: usize) -> usize {
+    2 * DMA_HALVES * block_length * channels * WORD_BYTES
+}
+
+/// Bytes of all audio buffers: the DMA buffers plus the blocks queued in both channels
+/// between the interface and the client, which live in ordinary RAM.
+pub const fn buffer_bytes(block_length: usize, channels: usize, buffer_count: usize) -> usize {
+    dma_buffer_bytes(block_length, channels)
+        + 2 * buffer_count * block_length * channels * WORD_BYTES
+}
+
+/// Panics, which is a compile error in `const` context, if the DMA buffers of the
+/// configuration don't fit in [`DMA_SRAM_BYTES`].
+pub const fn assert_dma_fits(block_length: usize, channels: usize) {
+    assert!(
+        dma_buffer_bytes(block_length, channels) <= DMA_SRAM_BYTES,
+        "audio DMA buffers don't fit in D2 SRAM"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LATENCY: f32 = latency_ms(48_000, 32, 2);
+    const _: () = assert_dma_fits(32, 2);
+
+    #[test]
+    fn default_configuration() {
+        assert!((LATENCY - 2.6666).abs() < 1e-3);
+        assert_eq!(latency_us(48_000, 32, 2), 2666);
+        assert_eq!(dma_buffer_bytes(32, 2), 1024);
+        assert_eq!(buffer_bytes(32, 2, 2), 2048);
+    }
+
+    #[test]
+    #[should_panic]
+    fn oversized_dma_buffers() {
+        assert_dma_fits(1 << 20, 2);
+    }
+}
@@ -0,0 +1,151 @@
+//! Low frequency oscillator for modulation, free running or synced to a tempo.
+//!
+//! Locked to an external clock, the rate follows the detected tempo and every beat restarts the cycle:
+//! ```ignore
+//! let mut lfo = Lfo::new(48_000, LfoWaveform::Triangle);
+//! // clock task, with a ClockInput at 4 pulses per beat
+//! let tick = clock.await_tick().await;
+//! if let Some(bpm) = clock.bpm() {
+//!     lfo.set_rate_synced(1.0, bpm);
+//! }
+//! if tick % 4 == 0 {
+//!     // on the beat
+//!     lfo.reset_phase();
+//! }
+//! // audio callback
+//! let cutoff = 1_000.0 + 500.0 * lfo.next();
+//! ```
+use super::{noise::to_bipolar, oscillator::sine, NoiseRng};
+use rand_core::RngCore;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LfoWaveform {
+    Sine,
+    Triangle,
+    /// Rising saw.
+    Saw,
+    Square,
+    /// A new random value at the start of every cycle, held until the next one.
+    SampleAndHold,
+}
+
+/// Allocation free LFO. Unlike [`Oscillator`](super::Oscillator) it isn't band limited,
+/// the hard edges of saw and square are wanted for modulation.
+pub struct Lfo {
+    sample_rate: f32,
+    waveform: LfoWaveform,
+    // 0.0..1.0
+    phase: f32,
+    // phase increment per sample
+    increment: f32,
+    rng: NoiseRng,
+    held: f32,
+}
+
+impl Lfo {
+    /// A stopped (0Hz) LFO at `sample_rate`, i.e. the rate [`Lfo::next`] is called at.
+    pub fn new(sample_rate: u32, waveform: LfoWaveform) -> Self {
+        let mut rng = NoiseRng::new(0);
+        let held = to_bipolar(rng.next_u32());
+        Self {
+            sample_rate: sample_rate as f32,
+            waveform,
+            phase: 0.0,
+            increment: 0.0,
+            rng,
+            held,
+        }
+    }
+    pub fn set_waveform(&mut self, waveform: LfoWaveform) {
+        self.waveform = waveform;
+    }
+    pub fn set_rate_hz(&mut self, hz: f32) {
+        self.increment = (hz / self.sample_rate).clamp(0.0, 0.5);
+    }
+    pub fn rate_hz(&self) -> f32 {
+        self.increment * self.sample_rate
+    }
+    /// One cycle every `division` beats at `bpm`: 1.0 for quarter notes, 0.25 for sixteenths,
+    /// 4.0 for a bar of 4/4. Call again when the tempo changes, e.g. with [`crate::sync::TempoTracker::bpm`].
+    pub fn set_rate_synced(&mut self, division: f32, bpm: f32) {
+        if division > 0.0 {
+            self.set_rate_hz(bpm / (60.0 * division));
+        }
+    }
+    /// Restart the cycle, e.g. on a clock pulse. Sample and hold picks a new value.
+    pub fn reset_phase(&mut self) {
+        self.phase = 0.0;
+        self.held = to_bipolar(self.rng.next_u32());
+    }
+    /// The next value in -1.0..=1.0.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> f32 {
+        let t = self.phase;
+        let out = match self.waveform {
+            LfoWaveform::Sine => sine(t),
+            LfoWaveform::Triangle => {
+                if t < 0.5 {
+                    4.0 * t - 1.0
+                } else {
+                    3.0 - 4.0 * t
+                }
+            }
+            LfoWaveform::Saw => 2.0 * t - 1.0,
+            LfoWaveform::Square => {
+                if t < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoWaveform::SampleAndHold => self.held,
+        };
+        self.phase = t + self.increment;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.held = to_bipolar(self.rng.next_u32());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take<const N: usize>(lfo: &mut Lfo) -> [f32; N] {
+        core::array::from_fn(|_| lfo.next())
+    }
+
+    #[test]
+    fn synced_rate() {
+        let mut lfo = Lfo::new(48_000, LfoWaveform::Saw);
+        lfo.set_rate_synced(1.0, 120.0);
+        assert!((lfo.rate_hz() - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn waveforms() {
+        let mut lfo = Lfo::new(48_000, LfoWaveform::Saw);
+        lfo.set_rate_hz(12_000.0);
+        assert_eq!(take(&mut lfo), [-1.0, -0.5, 0.0, 0.5, -1.0]);
+        lfo.set_waveform(LfoWaveform::Square);
+        lfo.reset_phase();
+        assert_eq!(take(&mut lfo), [1.0, 1.0, -1.0, -1.0]);
+        lfo.set_waveform(LfoWaveform::Sine);
+        lfo.reset_phase();
+        assert!(lfo.next().abs() < 1e-6);
+        assert!((lfo.next() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn sample_and_hold_holds_for_a_cycle() {
+        let mut lfo = Lfo::new(48_000, LfoWaveform::SampleAndHold);
+        lfo.set_rate_hz(12_000.0);
+        let held: [f32; 8] = take(&mut lfo);
+        assert_eq!(held[0], held[3]);
+        assert_ne!(held[3], held[4]);
+        assert!(held.iter().all(|x| (-1.0..=1.0).contains(x)));
+    }
+}
@@ -0,0 +1,121 @@
+//! Peak limiter for interleaved stereo blocks, to catch transient overs before the DAC.
+
+use super::{db_to_linear, linear_to_db};
+
+const CHANNELS: usize = 2;
+
+/// Stereo linked peak limiter with instant attack and exponential release.
+///
+/// The gain drops at once to whatever keeps the louder channel at the threshold, so the output
+/// never exceeds it, and recovers with the release time constant. Without look-ahead the attack
+/// bends the waveform of the first peak; put a [`soft_clip`](super::soft_clip) after it
+/// when that matters less than latency.
+/// ```ignore
+/// let mut limiter = Limiter::new(48_000);
+/// limiter.set_threshold_db(-1.0);
+/// // in the audio callback
+/// limiter.process(&mut output);
+/// meter_led.set(limiter.gain_reduction_db() > 3.0);
+/// ```
+pub struct Limiter {
+    sample_rate: f32,
+    threshold: f32,
+    // release coefficient per sample
+    release: f32,
+    gain: f32,
+    // lowest gain in the last block
+    min_gain: f32,
+}
+
+impl Limiter {
+    /// Threshold at full scale (0dB), 50ms release.
+    pub fn new(sample_rate: u32) -> Self {
+        let mut limiter = Self {
+            sample_rate: sample_rate as f32,
+            threshold: 1.0,
+            release: 0.0,
+            gain: 1.0,
+            min_gain: 1.0,
+        };
+        limiter.set_release_ms(50.0);
+        limiter
+    }
+    pub fn set_threshold_db(&mut self, db: f32) {
+        self.threshold = db_to_linear(db);
+    }
+    pub fn threshold_db(&self) -> f32 {
+        linear_to_db(self.threshold)
+    }
+    /// Time constant of the recovery: after the peak the gain reduction falls to 37% in `release_ms`.
+    pub fn set_release_ms(&mut self, release_ms: f32) {
+        let release_samples = (self.sample_rate * release_ms / 1000.0).max(1.0);
+        self.release = libm::expf(-1.0 / release_samples);
+    }
+    /// Limit an interleaved stereo block in place. Call this once per block.
+    pub fn process(&mut self, block: &mut [f32]) {
+        let mut min_gain = 1.0f32;
+        for frame in block.chunks_exact_mut(CHANNELS) {
+            let peak = frame.iter().fold(0.0f32, |peak, smp| peak.max(smp.abs()));
+            let target = if peak > self.threshold {
+                self.threshold / peak
+            } else {
+                1.0
+            };
+            self.gain = if target < self.gain {
+                target
+            } else {
+                target + (self.gain - target) * self.release
+            };
+            min_gain = min_gain.min(self.gain);
+            for smp in frame.iter_mut() {
+                *smp *= self.gain;
+            }
+        }
+        self.min_gain = min_gain;
+    }
+    /// Largest gain reduction in the last block, in dB (positive, 0.0 when not limiting).
+    pub fn gain_reduction_db(&self) -> f32 {
+        -linear_to_db(self.min_gain)
+    }
+    /// Forget the current gain reduction.
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+        self.min_gain = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_and_recovers() {
+        let mut limiter = Limiter::new(48_000);
+        limiter.set_threshold_db(-6.0);
+        let threshold = db_to_linear(-6.0);
+        let mut block = [0.1f32, -0.1, 2.0, 0.5, 0.1, 0.1];
+        limiter.process(&mut block);
+        assert_eq!(block[..2], [0.1, -0.1]);
+        assert!((block[2] - threshold).abs() < 1e-6);
+        // stereo linked
+        assert!((block[3] - 0.5 * threshold / 2.0).abs() < 1e-6);
+        assert!(block[4] < 0.1 * 0.26);
+        let reduction = 20.0 * libm::log10f(2.0 / threshold);
+        assert!((limiter.gain_reduction_db() - reduction).abs() < 1e-3);
+        let mut quiet = [0.1f32; 2 * 48_000];
+        limiter.process(&mut quiet);
+        assert!((quiet[quiet.len() - 1] - 0.1).abs() < 1e-4);
+        limiter.process(&mut [0.1; 64]);
+        assert!(limiter.gain_reduction_db() < 1e-3);
+    }
+
+    #[test]
+    fn never_over_threshold() {
+        let mut limiter = Limiter::new(48_000);
+        limiter.set_threshold_db(-6.0);
+        let threshold = db_to_linear(-6.0);
+        let mut block: [f32; 4096] = core::array::from_fn(|i| (i * 37 % 101) as f32 / 25.0 - 2.0);
+        limiter.process(&mut block);
+        assert!(block.iter().all(|x| x.abs() <= threshold + 1e-6));
+    }
+}
@@ -0,0 +1,123 @@
+//! Loopback self-test for bringing up the audio path.
+//!
+//! Connect the left output to the left input (or right to right) with a cable,
+//! then run [`loopback_test`] before [`Interface::start`].
+//! A pseudo random burst is sent on the left channel and searched for in the received signal.
+use super::{f32_to_u24, u24_to_f32, Interface, BLOCK_LENGTH, HALF_DMA_BUFFER_LENGTH};
+use defmt::{info, warn};
+
+// blocks of silence before the burst, to let the codec settle
+const SETTLE_BLOCKS: usize = 8;
+// blocks received after the burst has been sent
+const CAPTURE_BLOCKS: usize = 16;
+const CAPTURE_LENGTH: usize = CAPTURE_BLOCKS * BLOCK_LENGTH;
+const BURST_AMPLITUDE: f32 = 0.5;
+// normalized correlation above which the burst counts as detected
+const DETECTION_THRESHOLD: f32 = 0.5;
+
+#[derive(Debug, defmt::Format)]
+pub struct LoopbackResult {
+    /// Delay in samples between the burst's position in the TX stream and in the RX stream,
+    /// including the DMA buffers and the codec's converters. `None` if the burst wasn't detected.
+    pub delay: Option<usize>,
+    /// Normalized correlation at `delay`, from 0.0 to 1.0.
+    pub correlation: f32,
+    /// Peak level of the received signal.
+    pub rx_peak: f32,
+    /// The burst sent on the left channel came back on the right channel.
+    pub channels_swapped: bool,
+}
+
+/// Send a known burst and measure it on the input.
+///
+/// This starts the SAI, so [`Interface::start`] can be called afterwards to continue as usual.
+pub async fn loopback_test(interface: &mut Interface<'_>) -> LoopbackResult {
+    info!("loopback test: start");
+    if let Err(e) = interface.start_sai().await {
+        warn!("loopback test: codec output not enabled: {}", e);
+    }
+
+    let burst = make_burst();
+    let mut tx = [0u32; HALF_DMA_BUFFER_LENGTH];
+    let mut rx = [0u32; HALF_DMA_BUFFER_LENGTH];
+    let mut left = [0.0f32; CAPTURE_LENGTH];
+    let mut right = [0.0f32; CAPTURE_LENGTH];
+
+    for _ in 0..SETTLE_BLOCKS {
+        interface.sai_rx.read(&mut rx).await.unwrap();
+        interface.sai_tx.write(&tx).await.unwrap();
+    }
+    for block in 0..CAPTURE_BLOCKS {
+        interface.sai_rx.read(&mut rx).await.unwrap();
+        let offset = block * BLOCK_LENGTH;
+        for (i, frame) in rx.chunks_exact(2).enumerate() {
+            left[offset + i] = u24_to_f32(frame[0]);
+            right[offset + i] = u24_to_f32(frame[1]);
+        }
+
+        // the burst goes out in the first capture block, silence after that
+        for (i, frame) in tx.chunks_exact_mut(2).enumerate() {
+            frame[0] = if block == 0 { f32_to_u24(burst[i]) } else { 0 };
+            frame[1] = 0;
+        }
+        interface.sai_tx.write(&tx).await.unwrap();
+    }
+
+    let (left_delay, left_correlation) = find_burst(&burst, &left);
+    let (right_delay, right_correlation) = find_burst(&burst, &right);
+    let channels_swapped = right_correlation > left_correlation;
+    let (delay, correlation) = if channels_swapped {
+        (right_delay, right_correlation)
+    } else {
+        (left_delay, left_correlation)
+    };
+    let rx_peak = left
+        .iter()
+        .chain(right.iter())
+        .fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+    let result = LoopbackResult {
+        delay: (correlation > DETECTION_THRESHOLD).then_some(delay),
+        correlation,
+        rx_peak,
+        channels_swapped,
+    };
+    info!("loopback test: {}", result);
+    result
+}
+
+// 16 bit LFSR noise, +-BURST_AMPLITUDE
+fn make_burst() -> [f32; BLOCK_LENGTH] {
+    let mut lfsr: u16 = 0xACE1;
+    let mut burst = [0.0; BLOCK_LENGTH];
+    for smp in burst.iter_mut() {
+        let bit = (lfsr ^ (lfsr >> 2) ^ (lfsr >> 3) ^ (lfsr >> 5)) & 1;
+        lfsr = (lfsr >> 1) | (bit << 15);
+        *smp = if lfsr & 1 == 1 {
+            BURST_AMPLITUDE
+        } else {
+            -BURST_AMPLITUDE
+        };
+    }
+    burst
+}
+
+// returns the lag with the highest normalized correlation, and the correlation.
+// The polarity of the received signal is ignored.
+fn find_burst(burst: &[f32], signal: &[f32]) -> (usize, f32) {
+    let burst_energy: f32 = burst.iter().map(|s| s * s).sum();
+    let mut best = (0, 0.0);
+    for lag in 0..=(signal.len() - burst.len()) {
+        let window = &signal[lag..lag + burst.len()];
+        let energy: f32 = window.iter().map(|s| s * s).sum();
+        if energy == 0.0 {
+            continue;
+        }
+        let dot: f32 = window.iter().zip(burst).map(|(a, b)| a * b).sum();
+        let correlation = dot.abs() / libm::sqrtf(energy * burst_energy);
+        if correlation > best.1 {
+            best = (lag, correlation);
+        }
+    }
+    best
+}
@@ -0,0 +1,102 @@
+//! Peak and RMS level metering for interleaved stereo blocks.
+
+const CHANNELS: usize = 2;
+
+/// Per block peak and RMS levels, plus a decaying peak hold for UI meters.
+pub struct Meter {
+    peak: [f32; CHANNELS],
+    rms: [f32; CHANNELS],
+    peak_hold: [f32; CHANNELS],
+    // peak hold decay per sample
+    decay: f32,
+}
+
+impl Meter {
+    /// `release_ms` is the time the peak hold takes to fall by 60dB.
+    pub fn new(sample_rate: u32, release_ms: f32) -> Self {
+        let release_samples = (sample_rate as f32 * release_ms / 1000.0).max(1.0);
+        Self {
+            peak: [0.0; CHANNELS],
+            rms: [0.0; CHANNELS],
+            peak_hold: [0.0; CHANNELS],
+            // 0.001 == -60dB
+            decay: libm::powf(0.001, 1.0 / release_samples),
+        }
+    }
+    /// Measure an interleaved stereo block. Call this once per block.
+    pub fn process(&mut self, block: &[f32]) {
+        let frames = block.len() / CHANNELS;
+        if frames == 0 {
+            return;
+        }
+        let mut peak = [0.0f32; CHANNELS];
+        let mut sum_sq = [0.0f32; CHANNELS];
+        for frame in block.chunks_exact(CHANNELS) {
+            for (ch, smp) in frame.iter().enumerate() {
+                peak[ch] = peak[ch].max(smp.abs());
+                sum_sq[ch] += smp * smp;
+            }
+        }
+        let decay = libm::powf(self.decay, frames as f32);
+        for ch in 0..CHANNELS {
+            self.peak[ch] = peak[ch];
+            self.rms[ch] = libm::sqrtf(sum_sq[ch] / frames as f32);
+            self.peak_hold[ch] = peak[ch].max(self.peak_hold[ch] * decay);
+        }
+    }
+    /// Peak level of the last block, linear.
+    pub fn peak(&self, ch: usize) -> f32 {
+        self.peak[ch]
+    }
+    /// RMS level of the last block, linear.
+    pub fn rms(&self, ch: usize) -> f32 {
+        self.rms[ch]
+    }
+    /// Peak level with exponential release, linear.
+    pub fn peak_hold(&self, ch: usize) -> f32 {
+        self.peak_hold[ch]
+    }
+    pub fn reset(&mut self) {
+        self.peak = [0.0; CHANNELS];
+        self.rms = [0.0; CHANNELS];
+        self.peak_hold = [0.0; CHANNELS];
+    }
+}
+
+/// Linear level to decibels. Silence maps to `f32::NEG_INFINITY`.
+pub fn linear_to_db(level: f32) -> f32 {
+    20.0 * libm::log10f(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_rms_and_hold_decay() {
+        let mut meter = Meter::new(48_000, 100.0);
+        let mut block = [0.0f32; 64];
+        for frame in block.chunks_exact_mut(2) {
+            frame[0] = 0.5;
+            frame[1] = -0.25;
+        }
+        meter.process(&block);
+        assert_eq!(meter.peak(0), 0.5);
+        assert_eq!(meter.rms(1), 0.25);
+        assert_eq!(meter.peak_hold(0), 0.5);
+        for _ in 0..150 {
+            meter.process(&[0.0; 64]);
+        }
+        assert!(
+            (meter.peak_hold(0) - 0.0005).abs() < 0.0001,
+            "{}",
+            meter.peak_hold(0)
+        );
+    }
+
+    #[test]
+    fn linear_to_db_reference_points() {
+        assert_eq!(linear_to_db(1.0), 0.0);
+        assert!((linear_to_db(0.1) + 20.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,67 @@
+//! Mid/side conversion and stereo width, in place on interleaved `f32` blocks.
+//!
+//! Mid is `(l + r) / 2`, side `(l - r) / 2`, so [`ms_decode`] is the exact inverse of [`ms_encode`]
+//! and a mono signal has no side at all:
+//! ```ignore
+//! ms_encode(&mut block);
+//! for frame in block.frames_mut() {
+//!     frame.r *= side_gain; // e.g. an EQ or compressor on the side only
+//! }
+//! ms_decode(&mut block);
+//! ```
+use super::StereoFrames;
+
+/// L/R to M/S: left becomes mid, right becomes side.
+pub fn ms_encode(block: &mut [f32]) {
+    for frame in block.frames_mut() {
+        let (l, r) = (frame.l, frame.r);
+        frame.l = (l + r) * 0.5;
+        frame.r = (l - r) * 0.5;
+    }
+}
+
+/// M/S to L/R, the inverse of [`ms_encode`].
+pub fn ms_decode(block: &mut [f32]) {
+    for frame in block.frames_mut() {
+        let (m, s) = (frame.l, frame.r);
+        frame.l = m + s;
+        frame.r = m - s;
+    }
+}
+
+/// Scale the stereo width of an L/R block: 0.0 folds it to mono, 1.0 leaves it alone,
+/// above 1.0 widens it. Negative widths swap the channels.
+pub fn set_width(block: &mut [f32], width: f32) {
+    for frame in block.frames_mut() {
+        let m = (frame.l + frame.r) * 0.5;
+        let s = (frame.l - frame.r) * 0.5 * width;
+        frame.l = m + s;
+        frame.r = m - s;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: [f32; 6] = [0.5, 0.25, -1.0, 1.0, 0.3, 0.3];
+
+    #[test]
+    fn round_trip() {
+        let mut block = BLOCK;
+        ms_encode(&mut block);
+        assert_eq!(block[..2], [0.375, 0.125]);
+        assert_eq!(block[4..], [0.3, 0.0]);
+        ms_decode(&mut block);
+        assert_eq!(block, BLOCK);
+    }
+
+    #[test]
+    fn width() {
+        let mut block = BLOCK;
+        set_width(&mut block, 1.0);
+        assert_eq!(block, BLOCK);
+        set_width(&mut block, 0.0);
+        assert_eq!(block[..4], [0.375, 0.375, 0.0, 0.0]);
+    }
+}
@@ -0,0 +1,78 @@
+//! Input monitoring: the received block mixed into the output at a set level.
+//!
+//! The interface adds the input after the client's processing, in both [`Interface::start`] and
+//! [`Interface::start_callback`], so the client only writes the wet signal:
+//! ```ignore
+//! set_monitor_mix(0.5); // dry signal at -6dB on top of whatever the effect writes
+//! ```
+//! The input is mixed in after the [`ChannelFix`] of the input and before the one of the output,
+//! so left stays left whatever the channel fixes are.
+//!
+//! [`Interface::start`]: super::Interface::start
+//! [`Interface::start_callback`]: super::Interface::start_callback
+//! [`ChannelFix`]: super::ChannelFix
+use super::convert::{round_to_u24, u24_to_i32};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const FULL_SCALE: f32 = 8_388_608.0; // 2^23
+
+// f32 bits of the level, 0.0 (off) to start with
+static MONITOR_LEVEL: AtomicU32 = AtomicU32::new(0);
+
+/// Level of the input in the output, linear, 0.0 (off, the default) to 1.0 (unity).
+/// Takes effect with the next block, from any task.
+pub fn set_monitor_mix(level: f32) {
+    let level = level.clamp(0.0, 1.0);
+    MONITOR_LEVEL.store(level.to_bits(), Ordering::Relaxed);
+}
+
+pub fn monitor_mix() -> f32 {
+    f32::from_bits(MONITOR_LEVEL.load(Ordering::Relaxed))
+}
+
+/// Add `input` at `level` to `output`, both interleaved 24-bit blocks. Saturates at full scale.
+pub fn mix_monitor(input: &[u32], output: &mut [u32], level: f32) {
+    let gain = (level * FULL_SCALE) as i64;
+    for (o, i) in output.iter_mut().zip(input) {
+        let dry = (u24_to_i32(*i) as i64 * gain) >> 23;
+        // within +-2^24, exact in f32
+        *o = round_to_u24((u24_to_i32(*o) as i64 + dry) as f32);
+    }
+}
+
+// Called by the interface loops.
+#[cfg(feature = "hal")]
+pub(super) fn apply_monitor(input: &[u32], output: &mut [u32]) {
+    let level = monitor_mix();
+    if level > 0.0 {
+        mix_monitor(input, output, level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{f32_to_u24, u24_to_f32};
+
+    #[test]
+    fn mixes_and_saturates() {
+        let input = [0.5, -0.5, 1.0, -1.0].map(f32_to_u24);
+        let mut out = [0.25, 0.0, 0.5, -0.5].map(f32_to_u24);
+        mix_monitor(&input, &mut out, 0.5);
+        let expected = [0.5, -0.25, 1.0, -1.0];
+        for (out, expected) in out.iter().zip(expected) {
+            assert!((u24_to_f32(*out) - expected).abs() < 1e-5);
+        }
+        let mut out = [0u32; 4];
+        mix_monitor(&input, &mut out, 1.0);
+        assert_eq!(out, input);
+    }
+
+    #[test]
+    fn level_is_clamped() {
+        set_monitor_mix(2.0);
+        assert_eq!(monitor_mix(), 1.0);
+        set_monitor_mix(0.0);
+        assert_eq!(monitor_mix(), 0.0);
+    }
+}
@@ -0,0 +1,153 @@
+//! Processing that doesn't fit into the audio deadline (FFTs, long convolutions, ...) moved to a
+//! lower priority task, with the blocks passed through an [`OffloadChannel`] in both directions.
+//!
+//! The callback side never waits: it hands over the input with [`OffloadChannel::offload`] and
+//! picks up whatever the task has finished with [`OffloadChannel::collect`]. The task awaits
+//! [`OffloadChannel::receive`] and [`OffloadChannel::send`]. The output lags the input by at
+//! least one and at most `2 * N` blocks, so an `N` of 2 to 4 is usually right.
+//! ```ignore
+//! type Block = [f32; HALF_DMA_BUFFER_LENGTH];
+//! static OFFLOAD: OffloadChannel<Block, 4> = OffloadChannel::new();
+//!
+//! // audio callback
+//! interface.start_callback(|input, output| {
+//!     let mut block = [0.0; HALF_DMA_BUFFER_LENGTH];
+//!     block.copy_from_slice(input);
+//!     let _ = OFFLOAD.offload(block);
+//!     match OFFLOAD.collect() {
+//!         Ok(done) => output.copy_from_slice(&done),
+//!         Err(_) => output.fill(0.0),
+//!     }
+//! })
+//!
+//! // low priority task
+//! loop {
+//!     let mut block = OFFLOAD.receive().await;
+//!     spectral_freeze(&mut block);
+//!     OFFLOAD.send(block).await;
+//! }
+//! ```
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+
+/// Why a block didn't go through, see [`OffloadChannel::overruns`] and
+/// [`OffloadChannel::underruns`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OffloadError {
+    /// The task is `N` blocks behind, the block handed to [`OffloadChannel::offload`] was dropped.
+    Overrun,
+    /// No processed block was ready for [`OffloadChannel::collect`].
+    Underrun,
+}
+
+/// Queues of up to `N` blocks to a lower priority task and back. Put it in a `static`.
+pub struct OffloadChannel<T, const N: usize> {
+    to_task: Channel<CriticalSectionRawMutex, T, N>,
+    from_task: Channel<CriticalSectionRawMutex, T, N>,
+    overruns: AtomicU32,
+    underruns: AtomicU32,
+    // the first blocks after start are missing by design, they don't count as underruns
+    primed: AtomicBool,
+}
+
+impl<T, const N: usize> OffloadChannel<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            to_task: Channel::new(),
+            from_task: Channel::new(),
+            overruns: AtomicU32::new(0),
+            underruns: AtomicU32::new(0),
+            primed: AtomicBool::new(false),
+        }
+    }
+    /// Callback side: queue `block` for the task. Doesn't wait.
+    pub fn offload(&self, block: T) -> Result<(), OffloadError> {
+        self.to_task.try_send(block).map_err(|_| {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+            OffloadError::Overrun
+        })
+    }
+    /// Callback side: the oldest block the task has finished. Doesn't wait.
+    pub fn collect(&self) -> Result<T, OffloadError> {
+        match self.from_task.try_receive() {
+            Ok(block) => {
+                self.primed.store(true, Ordering::Relaxed);
+                Ok(block)
+            }
+            Err(_) => {
+                if self.primed.load(Ordering::Relaxed) {
+                    self.underruns.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(OffloadError::Underrun)
+            }
+        }
+    }
+    /// Task side: wait for the next block to process.
+    pub async fn receive(&self) -> T {
+        self.to_task.receive().await
+    }
+    /// Task side: hand back a processed block. Waits while `N` blocks are waiting to be collected.
+    pub async fn send(&self, block: T) {
+        self.from_task.send(block).await
+    }
+    /// Blocks dropped because the task fell behind.
+    pub fn overruns(&self) -> u32 {
+        self.overruns.load(Ordering::Relaxed)
+    }
+    /// Callbacks without a processed block, not counting those before the first one came back.
+    pub fn underruns(&self) -> u32 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+    /// Drop all queued blocks and reset the counters, e.g. when the audio restarts.
+    pub fn clear(&self) {
+        self.to_task.clear();
+        self.from_task.clear();
+        self.overruns.store(0, Ordering::Relaxed);
+        self.underruns.store(0, Ordering::Relaxed);
+        self.primed.store(false, Ordering::Relaxed);
+    }
+}
+
+impl<T, const N: usize> Default for OffloadChannel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+
+    fn poll_once<F: Future>(future: F) -> Option<F::Output> {
+        let mut cx = Context::from_waker(Waker::noop());
+        match pin!(future).poll(&mut cx) {
+            Poll::Ready(output) => Some(output),
+            Poll::Pending => None,
+        }
+    }
+
+    #[test]
+    fn round_trip_and_counters() {
+        let offload: OffloadChannel<u32, 2> = OffloadChannel::new();
+        // nothing back yet isn't an underrun
+        assert_eq!(offload.collect(), Err(OffloadError::Underrun));
+        assert_eq!(offload.underruns(), 0);
+        offload.offload(1).unwrap();
+        offload.offload(2).unwrap();
+        assert_eq!(offload.offload(3), Err(OffloadError::Overrun));
+        assert_eq!(offload.overruns(), 1);
+        let block = poll_once(offload.receive()).unwrap();
+        poll_once(offload.send(block * 10)).unwrap();
+        assert_eq!(offload.collect(), Ok(10));
+        assert_eq!(offload.collect(), Err(OffloadError::Underrun));
+        assert_eq!(offload.underruns(), 1);
+        offload.clear();
+        assert_eq!((offload.overruns(), offload.underruns()), (0, 0));
+        assert!(poll_once(offload.receive()).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+//! Oscillator for test tones and simple synthesis.
+use super::sine_table::{SINE_TABLE, SINE_TABLE_SIZE};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Waveform {
+    /// Interpolated lookup table.
+    Sine,
+    /// Rising saw, anti-aliased with PolyBLEP.
+    Saw,
+    /// 50% duty square, anti-aliased with PolyBLEP.
+    Square,
+    Triangle,
+}
+
+/// Allocation free oscillator, cheap enough to run per sample in the audio callback.
+pub struct Oscillator {
+    sample_rate: f32,
+    waveform: Waveform,
+    amplitude: f32,
+    // 0.0..1.0
+    phase: f32,
+    // phase increment per sample
+    increment: f32,
+}
+
+impl Oscillator {
+    /// A silent (0Hz) sine oscillator at full amplitude.
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate as f32,
+            waveform: Waveform::Sine,
+            amplitude: 1.0,
+            phase: 0.0,
+            increment: 0.0,
+        }
+    }
+    pub fn set_frequency(&mut self, hz: f32) {
+        self.increment = (hz / self.sample_rate).clamp(0.0, 0.5);
+    }
+    pub fn frequency(&self) -> f32 {
+        self.increment * self.sample_rate
+    }
+    pub fn set_waveform(&mut self, waveform: Waveform) {
+        self.waveform = waveform;
+    }
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        self.amplitude = amplitude;
+    }
+    /// Restart the waveform from the beginning of its cycle.
+    pub fn reset_phase(&mut self) {
+        self.phase = 0.0;
+    }
+    pub fn next_sample(&mut self) -> f32 {
+        let t = self.phase;
+        let dt = self.increment;
+        let out = match self.waveform {
+            Waveform::Sine => sine(t),
+            Waveform::Saw => 2.0 * t - 1.0 - poly_blep(t, dt),
+            Waveform::Square => {
+                let naive = if t < 0.5 { 1.0 } else { -1.0 };
+                naive + poly_blep(t, dt) - poly_blep(wrap(t + 0.5), dt)
+            }
+            Waveform::Triangle => {
+                if t < 0.5 {
+                    4.0 * t - 1.0
+                } else {
+                    3.0 - 4.0 * t
+                }
+            }
+        };
+        self.phase = wrap(t + dt);
+        out * self.amplitude
+    }
+    /// Fill a mono block.
+    pub fn process(&mut self, block: &mut [f32]) {
+        for smp in block.iter_mut() {
+            *smp = self.next_sample();
+        }
+    }
+}
+
+fn wrap(phase: f32) -> f32 {
+    if phase >= 1.0 {
+        phase - 1.0
+    } else {
+        phase
+    }
+}
+
+pub(super) fn sine(phase: f32) -> f32 {
+    let pos = phase * SINE_TABLE_SIZE as f32;
+    let index = pos as usize;
+    let frac = pos - index as f32;
+    let a = SINE_TABLE[index];
+    let b = SINE_TABLE[index + 1];
+    a + (b - a) * frac
+}
+
+// polynomial correction around the discontinuity at phase 0
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if dt <= 0.0 {
+        0.0
+    } else if t < dt {
+        let t = t / dt;
+        t + t - t * t - 1.0
+    } else if t > 1.0 - dt {
+        let t = (t - 1.0) / dt;
+        t * t + t + t + 1.0
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sine_matches_reference() {
+        let mut osc = Oscillator::new(48_000);
+        osc.set_frequency(1000.0);
+        let mut block = [0.0f32; 48];
+        osc.process(&mut block);
+        for (i, sample) in block.iter().enumerate() {
+            let expected = libm::sinf(2.0 * core::f32::consts::PI * i as f32 / 48.0);
+            assert!((sample - expected).abs() < 1e-3, "{i}: {sample} {expected}");
+        }
+    }
+
+    #[test]
+    fn waveforms_stay_in_range() {
+        let mut osc = Oscillator::new(48_000);
+        osc.set_frequency(1000.0);
+        for waveform in [Waveform::Saw, Waveform::Square, Waveform::Triangle] {
+            osc.set_waveform(waveform);
+            for _ in 0..1000 {
+                let sample = osc.next_sample();
+                assert!(sample.abs() <= 1.01, "{sample}");
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+//! Decimation of a PDM bitstream (from a MEMS microphone) to PCM. See [`crate::pdm`] for the hardware side.
+
+// 4th order gives about 100dB of alias rejection around the passband at 64x, plenty for MEMS mics.
+const ORDER: usize = 4;
+// integrators wrap, that's fine as long as the output fits: log2(R^ORDER) + 1 <= 32
+const MAX_DECIMATION: u32 = 128;
+
+/// Cascaded integrator-comb decimator for a 1-bit PDM stream.
+///
+/// Delays by `ORDER * (decimation - 1) / 2` bits, about 2 output samples.
+/// The passband droops by about 3dB at a quarter of the output rate,
+/// which is usually covered by the mic's own roll-off.
+pub struct CicDecimator {
+    integrators: [i32; ORDER],
+    combs: [i32; ORDER],
+    decimation: u32,
+    count: u32,
+    // 1 / decimation^ORDER, maps the output to -1.0..1.0
+    scale: f32,
+}
+
+impl CicDecimator {
+    /// `decimation`: PDM bits per PCM sample, 8 to 128. 64 is the usual ratio (3.072MHz mic clock for 48kHz).
+    pub fn new(decimation: u32) -> Self {
+        assert!(
+            (8..=MAX_DECIMATION).contains(&decimation),
+            "decimation out of range"
+        );
+        Self {
+            integrators: [0; ORDER],
+            combs: [0; ORDER],
+            decimation,
+            count: 0,
+            scale: 1.0 / (decimation as u64).pow(ORDER as u32) as f32,
+        }
+    }
+    pub fn decimation(&self) -> u32 {
+        self.decimation
+    }
+    /// Largest number of samples [`CicDecimator::process`] writes for `bytes` bytes of PDM data.
+    pub fn max_output_len(&self, bytes: usize) -> usize {
+        (bytes * 8).div_ceil(self.decimation as usize)
+    }
+    /// Decimate `bits`, packed MSB first (the first bit in time is the MSB of the first byte).
+    /// Returns the number of samples written to `output`. Samples that don't fit are dropped.
+    pub fn process(&mut self, bits: &[u8], output: &mut [f32]) -> usize {
+        let mut written = 0;
+        for byte in bits {
+            for bit in (0..8).rev() {
+                let mut acc: i32 = if byte & (1 << bit) != 0 { 1 } else { -1 };
+                for integrator in self.integrators.iter_mut() {
+                    *integrator = integrator.wrapping_add(acc);
+                    acc = *integrator;
+                }
+                self.count += 1;
+                if self.count < self.decimation {
+                    continue;
+                }
+                self.count = 0;
+                for comb in self.combs.iter_mut() {
+                    let delayed = *comb;
+                    *comb = acc;
+                    acc = acc.wrapping_sub(delayed);
+                }
+                if let Some(smp) = output.get_mut(written) {
+                    *smp = acc as f32 * self.scale;
+                    written += 1;
+                }
+            }
+        }
+        written
+    }
+    pub fn reset(&mut self) {
+        self.integrators = [0; ORDER];
+        self.combs = [0; ORDER];
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn density_to_level() {
+        let mut decimator = CicDecimator::new(64);
+        let mut out = [0.0f32; 64];
+        assert_eq!(decimator.max_output_len(256), 32);
+        // all ones settle at +1
+        assert_eq!(decimator.process(&[0xff; 256], &mut out), 32);
+        assert!((out[31] - 1.0).abs() < 1e-4, "{}", out[31]);
+        // 50% density is 0
+        assert_eq!(decimator.process(&[0xaa; 256], &mut out), 32);
+        assert!(out[31].abs() < 1e-3, "{}", out[31]);
+        // 75% density is 0.5
+        decimator.process(&[0xee; 256], &mut out);
+        assert!((out[31] - 0.5).abs() < 1e-3, "{}", out[31]);
+    }
+}
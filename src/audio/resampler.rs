@@ -0,0 +1,124 @@
+//! Sample rate conversion between two fixed rates, e.g. a USB stream at 44.1kHz into a 48kHz codec.
+
+const CHANNELS: usize = 2;
+
+/// Linear interpolating resampler for interleaved stereo blocks.
+///
+/// The read position is tracked as an exact fraction of the two rates,
+/// so blocks join without discontinuities and the conversion doesn't drift.
+/// Adds one frame of latency.
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    // read position between `prev` and the next input frame, in 1/output_rate units
+    phase: u32,
+    prev: [f32; CHANNELS],
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        Self {
+            input_rate,
+            output_rate,
+            phase: 0,
+            prev: [0.0; CHANNELS],
+        }
+    }
+    /// Change the rates, e.g. when the host picks a different rate. Keeps the stream continuous.
+    pub fn set_rates(&mut self, input_rate: u32, output_rate: u32) {
+        // keep the fractional position
+        self.phase = (self.phase as u64 * output_rate as u64 / self.output_rate as u64) as u32;
+        self.input_rate = input_rate;
+        self.output_rate = output_rate;
+    }
+    /// Largest number of samples [`Resampler::process`] writes for `input_len` samples.
+    pub fn max_output_len(&self, input_len: usize) -> usize {
+        let frames = input_len / CHANNELS;
+        let max_frames =
+            (frames * self.output_rate as usize).div_ceil(self.input_rate as usize) + 1;
+        max_frames * CHANNELS
+    }
+    /// Convert an interleaved block and return the number of samples written to `output`.
+    ///
+    /// The count varies from block to block. Size `output` with [`Resampler::max_output_len`],
+    /// input frames that don't fit are dropped.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> usize {
+        let mut frames = input.chunks_exact(CHANNELS);
+        let mut next = frames.next();
+        let mut written = 0;
+        loop {
+            // step `prev` forward until the read position is between `prev` and `next`
+            while self.phase >= self.output_rate {
+                let Some(frame) = next else {
+                    return written;
+                };
+                self.prev.copy_from_slice(frame);
+                next = frames.next();
+                self.phase -= self.output_rate;
+            }
+            let Some(frame) = next else {
+                return written;
+            };
+            let Some(out) = output.get_mut(written..written + CHANNELS) else {
+                return written;
+            };
+            let t = self.phase as f32 / self.output_rate as f32;
+            for ((out, prev), next) in out.iter_mut().zip(self.prev).zip(frame) {
+                *out = prev + (next - prev) * t;
+            }
+            written += CHANNELS;
+            self.phase += self.input_rate;
+        }
+    }
+    /// Forget the previous frame and start over at the next block.
+    pub fn reset(&mut self) {
+        self.phase = 0;
+        self.prev = [0.0; CHANNELS];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsampled_ramp_is_continuous_and_rate_exact() {
+        let mut resampler = Resampler::new(44_100, 48_000);
+        let mut frames = 0;
+        let mut t = 0.0f32;
+        let mut out = [0.0f32; 128];
+        let step = 44_100.0 / 48_000.0;
+        for _ in 0..441 {
+            let mut input = [0.0f32; 64];
+            for frame in input.chunks_exact_mut(2) {
+                frame[0] = t;
+                frame[1] = -t;
+                t += 1.0;
+            }
+            let n = resampler.process(&input, &mut out[..resampler.max_output_len(64)]);
+            for frame in out[..n].chunks_exact(2) {
+                if frames >= 2 {
+                    let expected = frames as f32 * step - 1.0;
+                    assert!((frame[0] - expected).abs() < 1e-2, "{frames}: {}", frame[0]);
+                }
+                assert_eq!(frame[1], -frame[0]);
+                frames += 1;
+            }
+        }
+        // 441 * 32 input frames make 480 * 32 output frames
+        assert!((frames as i64 - 480 * 32).abs() <= 1, "{frames}");
+    }
+
+    #[test]
+    fn downsample_rate_exact() {
+        let mut resampler = Resampler::new(48_000, 44_100);
+        let input = [1.0f32; 64];
+        let mut out = [0.0; 128];
+        let mut frames = 0;
+        for _ in 0..480 {
+            let len = resampler.max_output_len(64);
+            frames += resampler.process(&input, &mut out[..len]) / 2;
+        }
+        assert!((frames as i64 - 441 * 32).abs() <= 1);
+    }
+}
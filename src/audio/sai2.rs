@@ -0,0 +1,184 @@
+//! Second stereo pair on SAI2, for boards with two codecs (4 in / 4 out), behind the `sai2` feature.
+//!
+//! SAI2 is available on the seed pins with the Daisy Patch's assignment:
+//!
+//! | signal          | seed pin      | MCU pin |
+//! |-----------------|---------------|---------|
+//! | MCLK_B          | `SEED_PIN_24` | PA1     |
+//! | SD_B (out)      | `SEED_PIN_25` | PA0     |
+//! | SD_A (in)       | `SEED_PIN_26` | PD11    |
+//! | FS_B            | `SEED_PIN_27` | PG9     |
+//! | SCK_B           | `SEED_PIN_28` | PA2     |
+//!
+//! Block B is the transmitter and clock master, block A receives synchronous to it. The framing is
+//! the same as SAI1's (24-bit left justified), which suits codecs without a control port like the
+//! Daisy Patch's AK4556. Configure a codec with a control port over I2C yourself.
+//!
+//! Alignment: the `sai2` feature switches the SAI2 kernel clock to PLL3_P as well, so both
+//! pairs run on the same clock and can't drift apart. Started right after the main interface
+//! (see [`SecondInterface::start`]), the two frame clocks keep a constant offset of less than
+//! a frame; blocks of both pairs then belong to the same sample period.
+//! ```ignore
+//! let (mut second, (mut to_second, mut from_second)) =
+//!     SecondInterface::new(sai2_pins, p.SAI2, p.DMA1_CH3, p.DMA1_CH4, Sai2Config::default())?;
+//! join3(interface.start(), second.start(), callbacks).await;
+//! ```
+use super::{
+    sai_tx_base_config, AudioBlockBuffers, AudioError, Fs, InterleavedBlock, DMA_BUFFER_LENGTH,
+    HALF_DMA_BUFFER_LENGTH, MAX_BUFFER_COUNT,
+};
+use crate::pins::{SeedPin24, SeedPin25, SeedPin26, SeedPin27, SeedPin28};
+use defmt::{info, warn};
+use embassy_stm32 as hal;
+use embassy_sync::{
+    blocking_mutex::raw::NoopRawMutex,
+    zerocopy_channel::{Channel, Receiver, Sender},
+};
+use grounded::uninit::GroundedArrayCell;
+use hal::peripherals::{DMA1_CH3, DMA1_CH4, SAI2};
+use hal::sai::{ClockStrobe, Mode, Sai, TxRx};
+use static_cell::StaticCell;
+
+#[link_section = ".sram1_bss"]
+static mut TX_BUFFER: GroundedArrayCell<u32, DMA_BUFFER_LENGTH> = GroundedArrayCell::uninit();
+#[link_section = ".sram1_bss"]
+static mut RX_BUFFER: GroundedArrayCell<u32, DMA_BUFFER_LENGTH> = GroundedArrayCell::uninit();
+
+#[allow(non_snake_case)]
+pub struct Sai2Pins {
+    pub MCLK_B: SeedPin24,
+    pub SD_B: SeedPin25,
+    pub SD_A: SeedPin26,
+    pub FS_B: SeedPin27,
+    pub SCK_B: SeedPin28,
+}
+
+pub struct Sai2Config {
+    /// Has to match the main interface's rate for the pairs to stay aligned.
+    pub fs: Fs,
+    /// See [`AudioConfig::buffer_count`](super::AudioConfig::buffer_count).
+    pub buffer_count: usize,
+}
+
+impl Default for Sai2Config {
+    fn default() -> Self {
+        Self {
+            fs: Fs::Fs48000,
+            buffer_count: 2,
+        }
+    }
+}
+
+/// The second stereo pair. Hands blocks to the client like [`super::Interface`].
+pub struct SecondInterface<'a> {
+    sai_tx: Sai<'a, SAI2, u32>,
+    sai_rx: Sai<'a, SAI2, u32>,
+    started: bool,
+    pub to_client: Sender<'static, NoopRawMutex, InterleavedBlock>,
+    pub from_client: Receiver<'static, NoopRawMutex, InterleavedBlock>,
+}
+
+impl<'a> SecondInterface<'a> {
+    /// Set up SAI2 with DMA1_CH3 (tx) and DMA1_CH4 (rx). Fails if the SAI2 kernel clock
+    /// isn't the same as SAI1's, see [`crate::rcc`].
+    pub fn new(
+        pins: Sai2Pins,
+        sai2: SAI2,
+        dma1_ch3: DMA1_CH3,
+        dma1_ch4: DMA1_CH4,
+        config: Sai2Config,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        if hal::rcc::frequency::<SAI2>() != hal::rcc::frequency::<hal::peripherals::SAI1>() {
+            return Err(AudioError::UnsupportedConfig(
+                "SAI2 kernel clock differs from SAI1's",
+            ));
+        }
+        let (sub_block_rx, sub_block_tx) = hal::sai::split_subblocks(sai2);
+
+        info!("set up sai2_tx");
+        let mut tx_config = sai_tx_base_config();
+        tx_config.mode = Mode::Master;
+        tx_config.sync_output = false;
+        tx_config.master_clock_divider = config.fs.into_clock_divider()?;
+        let (tx_buffer, rx_buffer) = unsafe { dma_buffers() };
+        let sai_tx = Sai::new_asynchronous_with_mclk(
+            sub_block_tx,
+            pins.SCK_B,
+            pins.SD_B,
+            pins.FS_B,
+            pins.MCLK_B,
+            dma1_ch3,
+            tx_buffer,
+            tx_config,
+        );
+
+        info!("set up sai2_rx");
+        let mut rx_config = tx_config;
+        rx_config.tx_rx = TxRx::Receiver;
+        rx_config.clock_strobe = ClockStrobe::Rising;
+        let sai_rx = Sai::new_synchronous(sub_block_rx, pins.SD_A, dma1_ch4, rx_buffer, rx_config);
+
+        let buffer_count = config.buffer_count.clamp(2, MAX_BUFFER_COUNT);
+        static TO_INTERFACE_BUF: StaticCell<[InterleavedBlock; MAX_BUFFER_COUNT]> =
+            StaticCell::new();
+        let to_interface_buf = &mut TO_INTERFACE_BUF
+            .init([[0; HALF_DMA_BUFFER_LENGTH]; MAX_BUFFER_COUNT])[..buffer_count];
+        static TO_INTERFACE: StaticCell<Channel<'_, NoopRawMutex, InterleavedBlock>> =
+            StaticCell::new();
+        let (client_to_if_tx, client_to_if_rx) =
+            TO_INTERFACE.init(Channel::new(to_interface_buf)).split();
+        static FROM_INTERFACE_BUF: StaticCell<[InterleavedBlock; MAX_BUFFER_COUNT]> =
+            StaticCell::new();
+        let from_interface_buf = &mut FROM_INTERFACE_BUF
+            .init([[0; HALF_DMA_BUFFER_LENGTH]; MAX_BUFFER_COUNT])[..buffer_count];
+        static FROM_INTERFACE: StaticCell<Channel<'_, NoopRawMutex, InterleavedBlock>> =
+            StaticCell::new();
+        let (if_to_client_tx, if_to_client_rx) = FROM_INTERFACE
+            .init(Channel::new(from_interface_buf))
+            .split();
+
+        Ok((
+            Self {
+                sai_tx,
+                sai_rx,
+                started: false,
+                to_client: if_to_client_tx,
+                from_client: client_to_if_rx,
+            },
+            (client_to_if_tx, if_to_client_rx),
+        ))
+    }
+    pub async fn start(&mut self) -> ! {
+        if !self.started {
+            self.started = true;
+            info!("start SAI2");
+            self.sai_rx.start();
+            self.sai_tx.start();
+        }
+        loop {
+            let buf = self.to_client.send().await;
+            if let Err(e) = self.sai_rx.read(buf).await {
+                warn!("SAI2 rx: {}", e);
+                buf.fill(0);
+            }
+            self.to_client.send_done();
+            let buf = self.from_client.receive().await;
+            if let Err(e) = self.sai_tx.write(buf).await {
+                warn!("SAI2 tx: {}", e);
+            }
+            self.from_client.receive_done();
+        }
+    }
+}
+
+// Safety: hands out the static DMA buffers, only one SecondInterface may exist at a time.
+unsafe fn dma_buffers() -> (&'static mut [u32], &'static mut [u32]) {
+    TX_BUFFER.initialize_all_copied(0);
+    RX_BUFFER.initialize_all_copied(0);
+    let (tx_ptr, tx_len) = TX_BUFFER.get_ptr_len();
+    let (rx_ptr, rx_len) = RX_BUFFER.get_ptr_len();
+    (
+        core::slice::from_raw_parts_mut(tx_ptr, tx_len),
+        core::slice::from_raw_parts_mut(rx_ptr, rx_len),
+    )
+}
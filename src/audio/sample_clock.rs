@@ -0,0 +1,71 @@
+//! Time measured in audio samples.
+//!
+//! [`SAMPLE_CLOCK`] is advanced by the [`Interface`](super::Interface) every time a block is received,
+//! so it stays locked to the codec's clock instead of drifting against `embassy_time`.
+//! Use it for sequencers and other musical timing.
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::waitqueue::MultiWakerRegistration;
+
+// tasks waiting at the same time. When exceeded, all waiters are woken up and register again.
+const MAX_WAITERS: usize = 8;
+
+/// The sample clock driven by the audio interface.
+pub static SAMPLE_CLOCK: SampleClock = SampleClock::new();
+
+pub struct SampleClock {
+    state: Mutex<CriticalSectionRawMutex, RefCell<State>>,
+}
+
+struct State {
+    samples: u64,
+    wakers: MultiWakerRegistration<MAX_WAITERS>,
+}
+
+impl SampleClock {
+    const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(State {
+                samples: 0,
+                wakers: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+    #[cfg(feature = "hal")]
+    pub(crate) fn advance(&self, samples: u32) {
+        self.state.lock(|state| {
+            let mut state = state.borrow_mut();
+            state.samples += samples as u64;
+            state.wakers.wake();
+        });
+    }
+    /// Samples processed since the audio interface started.
+    pub fn samples_elapsed(&self) -> u64 {
+        self.state.lock(|state| state.borrow().samples)
+    }
+    /// Wait for `n` more samples to be processed.
+    /// The resolution is one block (`BLOCK_LENGTH` samples).
+    pub async fn await_samples(&self, n: u64) {
+        let target = self.samples_elapsed() + n;
+        self.await_until(target).await;
+    }
+    /// Wait until `samples_elapsed()` reaches `target`.
+    /// Awaiting consecutive targets (`t`, `t + n`, `t + 2n`, ...) keeps a steady period without accumulating error.
+    pub async fn await_until(&self, target: u64) {
+        poll_fn(|cx| {
+            self.state.lock(|state| {
+                let mut state = state.borrow_mut();
+                if state.samples >= target {
+                    Poll::Ready(())
+                } else {
+                    state.wakers.register(cx.waker());
+                    Poll::Pending
+                }
+            })
+        })
+        .await;
+    }
+}
@@ -0,0 +1,155 @@
+//! Stereo frames over interleaved blocks.
+//!
+//! ```ignore
+//! to_f32_block(input, &mut block);
+//! for frame in block.frames_mut() {
+//!     frame.l *= gain;
+//!     frame.r = frame.l;
+//! }
+//! ```
+use core::mem::{align_of, size_of};
+
+/// One stereo frame. Laid out like two interleaved samples, left first.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stereo<T> {
+    pub l: T,
+    pub r: T,
+}
+
+impl<T> Stereo<T> {
+    pub const fn new(l: T, r: T) -> Self {
+        Self { l, r }
+    }
+    /// Apply `f` to both channels.
+    pub fn map<U>(self, mut f: impl FnMut(T) -> U) -> Stereo<U> {
+        Stereo {
+            l: f(self.l),
+            r: f(self.r),
+        }
+    }
+}
+
+impl<T: Copy> Stereo<T> {
+    /// The same sample on both channels.
+    pub const fn splat(v: T) -> Self {
+        Self { l: v, r: v }
+    }
+}
+
+// repr(C) with two fields of the same type never has padding, check it anyway
+// for the sample types used in this crate.
+const _: () = assert!(size_of::<Stereo<f32>>() == 2 * size_of::<f32>());
+const _: () = assert!(align_of::<Stereo<f32>>() == align_of::<f32>());
+const _: () = assert!(size_of::<Stereo<u32>>() == 2 * size_of::<u32>());
+const _: () = assert!(align_of::<Stereo<u32>>() == align_of::<u32>());
+
+/// Interleaved stereo blocks viewed as frames.
+pub trait StereoFrames<T> {
+    /// Panics if the block has an odd number of samples.
+    fn frames(&self) -> &[Stereo<T>];
+    /// Panics if the block has an odd number of samples.
+    fn frames_mut(&mut self) -> &mut [Stereo<T>];
+}
+
+impl<T> StereoFrames<T> for [T] {
+    fn frames(&self) -> &[Stereo<T>] {
+        assert!(
+            self.len().is_multiple_of(2),
+            "interleaved stereo block of odd length"
+        );
+        // Safety: Stereo<T> is repr(C) of two T, so it has the size of [T; 2] and the alignment of T.
+        // The length is even, so the frames cover exactly the block.
+        unsafe { core::slice::from_raw_parts(self.as_ptr().cast(), self.len() / 2) }
+    }
+    fn frames_mut(&mut self) -> &mut [Stereo<T>] {
+        assert!(
+            self.len().is_multiple_of(2),
+            "interleaved stereo block of odd length"
+        );
+        // Safety: see frames(), the borrow of the block is carried over.
+        unsafe { core::slice::from_raw_parts_mut(self.as_mut_ptr().cast(), self.len() / 2) }
+    }
+}
+
+/// Frames back to the interleaved samples.
+pub fn interleaved<T>(frames: &[Stereo<T>]) -> &[T] {
+    // Safety: see StereoFrames::frames().
+    unsafe { core::slice::from_raw_parts(frames.as_ptr().cast(), frames.len() * 2) }
+}
+
+/// Frames back to the interleaved samples.
+pub fn interleaved_mut<T>(frames: &mut [Stereo<T>]) -> &mut [T] {
+    // Safety: see StereoFrames::frames().
+    unsafe { core::slice::from_raw_parts_mut(frames.as_mut_ptr().cast(), frames.len() * 2) }
+}
+
+/// Iterate over the left and right channels of an interleaved block separately.
+pub fn channels<T: Copy>(
+    block: &[T],
+) -> (impl Iterator<Item = T> + '_, impl Iterator<Item = T> + '_) {
+    (
+        block.iter().step_by(2).copied(),
+        block.iter().skip(1).step_by(2).copied(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_view_the_block() {
+        let mut block = [1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0];
+        assert_eq!(
+            block.frames(),
+            [
+                Stereo::new(1.0, 2.0),
+                Stereo::new(3.0, 4.0),
+                Stereo::new(5.0, 6.0)
+            ]
+        );
+        for frame in block.frames_mut() {
+            *frame = frame.map(|smp| smp * 10.0);
+            frame.r = frame.l;
+        }
+        assert_eq!(block, [10.0, 10.0, 30.0, 30.0, 50.0, 50.0]);
+        let frames = [Stereo::splat(7u32), Stereo::new(8, 9)];
+        assert_eq!(interleaved(&frames), [7, 7, 8, 9]);
+    }
+
+    #[test]
+    fn interleaved_mut_writes_through() {
+        let mut frames = [Stereo::<u32>::default(); 2];
+        interleaved_mut(&mut frames).copy_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(frames, [Stereo::new(1, 2), Stereo::new(3, 4)]);
+    }
+
+    #[test]
+    fn empty_block_has_no_frames() {
+        let block: [u32; 0] = [];
+        assert!(block.frames().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "odd length")]
+    fn frames_of_odd_block_panic() {
+        let block = [0u32; 3];
+        block.frames();
+    }
+
+    #[test]
+    #[should_panic(expected = "odd length")]
+    fn frames_mut_of_odd_block_panic() {
+        let mut block = [0u32; 5];
+        block.frames_mut();
+    }
+
+    #[test]
+    fn channels_of_odd_block() {
+        let (l, r) = channels(&[1, 2, 3, 4, 5]);
+        assert!(l.eq([1, 3, 5]));
+        assert!(r.eq([2, 4]));
+    }
+}
@@ -0,0 +1,244 @@
+//! Per unit level trims of the inputs and outputs, for factory calibration of the channel balance.
+//!
+//! Measure each channel against a reference level, set the difference as trim and store it:
+//! ```ignore
+//! let mut trim = ChannelTrim::UNITY;
+//! trim.set_input(1, measured_left_db - measured_right_db);
+//! store(&trim.to_bytes());
+//! // on every boot, unity if the unit was never calibrated
+//! let trim = ChannelTrim::from_bytes(&load()).unwrap_or(ChannelTrim::UNITY);
+//! let config = AudioConfig { channel_trim: trim, ..Default::default() };
+//! ```
+//! Store [`ChannelTrim::to_bytes`] in non-volatile memory to keep it across power cycles.
+//! The crate has no flash driver, so where the 16 bytes go (internal flash, QSPI, an EEPROM)
+//! is up to the application.
+//!
+//! While the interface runs, [`set_channel_trim`] changes a single trim from any task or interrupt,
+//! e.g. during a calibration step, and [`channel_trim`] reads back all of them to store:
+//! ```ignore
+//! set_channel_trim(TrimChannel::Output(1), -0.4);
+//! store(&channel_trim().to_bytes());
+//! ```
+use super::convert::{round_to_u24, u24_to_i32};
+use super::db_to_linear;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const CHANNELS: usize = 2;
+
+// f32 bits of the trims in dB, inputs then outputs, 0.0 (unity) to start with
+static TRIM_DB: [AtomicU32; 2 * CHANNELS] = [const { AtomicU32::new(0) }; 2 * CHANNELS];
+
+/// A channel to trim with [`set_channel_trim`], 0 (left) or 1 (right).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrimChannel {
+    Input(usize),
+    Output(usize),
+}
+
+impl TrimChannel {
+    fn index(self) -> usize {
+        match self {
+            TrimChannel::Input(ch) => {
+                assert!(ch < CHANNELS, "trim channel out of range");
+                ch
+            }
+            TrimChannel::Output(ch) => {
+                assert!(ch < CHANNELS, "trim channel out of range");
+                CHANNELS + ch
+            }
+        }
+    }
+}
+
+/// Trim `ch` by `db`, clamped to [`ChannelTrim::MAX_DB`]. Takes effect with the next block, from
+/// any task or interrupt, without a ramp. Panics if the channel is neither 0 nor 1.
+pub fn set_channel_trim(ch: TrimChannel, db: f32) {
+    let db = if db.is_nan() {
+        0.0
+    } else {
+        db.clamp(-ChannelTrim::MAX_DB, ChannelTrim::MAX_DB)
+    };
+    TRIM_DB[ch.index()].store(db.to_bits(), Ordering::Relaxed);
+}
+
+/// The trims in use, [`AudioConfig::channel_trim`](super::AudioConfig::channel_trim)
+/// with the changes of [`set_channel_trim`] since.
+pub fn channel_trim() -> ChannelTrim {
+    let mut trim = ChannelTrim::UNITY;
+    refresh(&mut trim);
+    trim
+}
+
+// Set all trims at once, what the interface does with the configured ones.
+#[cfg(any(feature = "hal", test))]
+pub(super) fn store(trim: &ChannelTrim) {
+    let trims = trim.input_db.iter().chain(&trim.output_db);
+    for (atomic, db) in TRIM_DB.iter().zip(trims) {
+        atomic.store(db.to_bits(), Ordering::Relaxed);
+    }
+}
+
+// Bring `trim` up to date with the stored trims, once per block. Gains are only recalculated
+// for the trims that changed.
+pub(super) fn refresh(trim: &mut ChannelTrim) {
+    for ch in 0..CHANNELS {
+        let db = f32::from_bits(TRIM_DB[ch].load(Ordering::Relaxed));
+        if db != trim.input_db[ch] {
+            trim.set_input(ch, db);
+        }
+        let db = f32::from_bits(TRIM_DB[CHANNELS + ch].load(Ordering::Relaxed));
+        if db != trim.output_db[ch] {
+            trim.set_output(ch, db);
+        }
+    }
+}
+
+/// Gain trims in dB of the first two input and output channels,
+/// see [`AudioConfig::channel_trim`](super::AudioConfig::channel_trim).
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ChannelTrim {
+    input_db: [f32; CHANNELS],
+    output_db: [f32; CHANNELS],
+    input_gain: [f32; CHANNELS],
+    output_gain: [f32; CHANNELS],
+}
+
+impl ChannelTrim {
+    pub const SERIALIZED_LENGTH: usize = 16;
+    /// Trims are limited to +-12dB, anything more isn't a tolerance.
+    pub const MAX_DB: f32 = 12.0;
+    /// No trim, what uncalibrated units use.
+    pub const UNITY: ChannelTrim = ChannelTrim {
+        input_db: [0.0; CHANNELS],
+        output_db: [0.0; CHANNELS],
+        input_gain: [1.0; CHANNELS],
+        output_gain: [1.0; CHANNELS],
+    };
+
+    /// Trim input channel `ch` (0 left, 1 right) by `db`, clamped to [`ChannelTrim::MAX_DB`].
+    pub fn set_input(&mut self, ch: usize, db: f32) {
+        let db = db.clamp(-Self::MAX_DB, Self::MAX_DB);
+        self.input_db[ch] = db;
+        self.input_gain[ch] = db_to_linear(db);
+    }
+    /// The same for output channel `ch`.
+    pub fn set_output(&mut self, ch: usize, db: f32) {
+        let db = db.clamp(-Self::MAX_DB, Self::MAX_DB);
+        self.output_db[ch] = db;
+        self.output_gain[ch] = db_to_linear(db);
+    }
+    pub fn input_db(&self, ch: usize) -> f32 {
+        self.input_db[ch]
+    }
+    pub fn output_db(&self, ch: usize) -> f32 {
+        self.output_db[ch]
+    }
+    pub fn is_unity(&self) -> bool {
+        self.input_db == [0.0; CHANNELS] && self.output_db == [0.0; CHANNELS]
+    }
+    /// Apply the input trims in place to a block of the SAI's 24 bit words with `slot_count`
+    /// channels per frame. The result saturates at full scale.
+    pub fn apply_input(&self, block: &mut [u32], slot_count: usize) {
+        if self.input_db != [0.0; CHANNELS] {
+            apply(&self.input_gain, block, slot_count);
+        }
+    }
+    /// The same with the output trims.
+    pub fn apply_output(&self, block: &mut [u32], slot_count: usize) {
+        if self.output_db != [0.0; CHANNELS] {
+            apply(&self.output_gain, block, slot_count);
+        }
+    }
+    /// Little endian input trims, then output trims, in dB.
+    pub fn to_bytes(&self) -> [u8; Self::SERIALIZED_LENGTH] {
+        let mut bytes = [0; Self::SERIALIZED_LENGTH];
+        let trims = self.input_db.iter().chain(&self.output_db);
+        for (chunk, db) in bytes.chunks_exact_mut(4).zip(trims) {
+            chunk.copy_from_slice(&db.to_le_bytes());
+        }
+        bytes
+    }
+    /// `None` for erased flash (all 0xff) or trims that aren't finite or out of range.
+    pub fn from_bytes(bytes: &[u8; Self::SERIALIZED_LENGTH]) -> Option<Self> {
+        let mut db = [0.0; 2 * CHANNELS];
+        for (db, chunk) in db.iter_mut().zip(bytes.chunks_exact(4)) {
+            *db = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        if !db
+            .iter()
+            .all(|db| db.is_finite() && db.abs() <= Self::MAX_DB)
+        {
+            return None;
+        }
+        let mut trim = Self::UNITY;
+        for ch in 0..CHANNELS {
+            trim.set_input(ch, db[ch]);
+            trim.set_output(ch, db[CHANNELS + ch]);
+        }
+        Some(trim)
+    }
+}
+
+impl Default for ChannelTrim {
+    fn default() -> Self {
+        Self::UNITY
+    }
+}
+
+fn apply(gain: &[f32; CHANNELS], block: &mut [u32], slot_count: usize) {
+    for frame in block.chunks_exact_mut(slot_count) {
+        for (smp, gain) in frame.iter_mut().zip(gain) {
+            *smp = round_to_u24(u24_to_i32(*smp) as f32 * gain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut trim = ChannelTrim::UNITY;
+        trim.set_input(1, -1.5);
+        trim.set_output(0, 20.0);
+        assert_eq!(trim.output_db(0), ChannelTrim::MAX_DB);
+        assert_eq!(ChannelTrim::from_bytes(&trim.to_bytes()), Some(trim));
+        assert_eq!(ChannelTrim::from_bytes(&[0xFF; 16]), None);
+        assert_eq!(ChannelTrim::from_bytes(&[0; 16]), Some(ChannelTrim::UNITY));
+    }
+
+    #[test]
+    fn trims_saturate() {
+        let mut trim = ChannelTrim::UNITY;
+        trim.set_output(0, 6.0);
+        trim.set_output(1, -6.0);
+        let mut block = [0x60_0000, 0xA0_0000, 0x10_0000, 0x10_0000];
+        trim.apply_output(&mut block, 2);
+        assert_eq!(block[0], 0x7F_FFFF);
+        assert_eq!(block[2], 0x1F_EC98);
+        assert_eq!(block[3], 0x08_04DD);
+        assert_eq!(block[1], 0xCF_E2D3);
+        trim.apply_input(&mut block, 2);
+        assert_eq!(block[0], 0x7F_FFFF);
+    }
+
+    // the only test that touches the global trims
+    #[test]
+    fn runtime_trims() {
+        let mut trim = ChannelTrim::UNITY;
+        trim.set_input(0, 3.0);
+        store(&trim);
+        set_channel_trim(TrimChannel::Output(1), -30.0);
+        set_channel_trim(TrimChannel::Input(1), f32::NAN);
+        let mut expected = trim;
+        expected.set_output(1, -ChannelTrim::MAX_DB);
+        assert_eq!(channel_trim(), expected);
+        refresh(&mut trim);
+        assert_eq!(trim, expected);
+        store(&ChannelTrim::UNITY);
+        assert!(channel_trim().is_unity());
+    }
+}
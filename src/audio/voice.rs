@@ -0,0 +1,196 @@
+//! Note to voice allocation for polyphonic instruments.
+//!
+//! [`VoiceAllocator`] only tracks which note plays on which voice, the DSP is up to the user:
+//! ```ignore
+//! let mut voices = VoiceAllocator::<4>::new(StealPolicy::Oldest);
+//! let mut oscillators: [Oscillator; 4] = core::array::from_fn(|_| Oscillator::new(48_000));
+//! // on MIDI note on / note off
+//! voices.note_on(note, velocity);
+//! voices.note_off(note);
+//! // in the audio callback
+//! for (voice, osc) in voices.voices().iter().zip(oscillators.iter_mut()) {
+//!     osc.set_frequency(voice.frequency());
+//!     osc.set_amplitude(if voice.gate { voice.velocity as f32 / 127.0 } else { 0.0 });
+//! }
+//! ```
+
+/// Which voice to take over when a note comes in and all voices are playing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StealPolicy {
+    /// The voice whose note started first.
+    Oldest,
+    /// The voice playing the lowest note.
+    Lowest,
+    /// The voice playing the highest note.
+    Highest,
+    /// Ignore the new note.
+    None,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Voice {
+    /// MIDI note number, kept after note off so the release can sound at the same pitch.
+    pub note: u8,
+    pub velocity: u8,
+    pub gate: bool,
+    // allocation order, for the oldest note and least recently released voice
+    age: u32,
+}
+
+impl Voice {
+    const IDLE: Voice = Voice {
+        note: 0,
+        velocity: 0,
+        gate: false,
+        age: 0,
+    };
+
+    /// Equal tempered frequency of the note in Hz, A4 (note 69) at 440Hz.
+    pub fn frequency(&self) -> f32 {
+        note_to_hz(self.note)
+    }
+}
+
+/// Assigns notes to `N` voices.
+pub struct VoiceAllocator<const N: usize> {
+    voices: [Voice; N],
+    policy: StealPolicy,
+    counter: u32,
+}
+
+impl<const N: usize> VoiceAllocator<N> {
+    pub fn new(policy: StealPolicy) -> Self {
+        Self {
+            voices: [Voice::IDLE; N],
+            policy,
+            counter: 0,
+        }
+    }
+    /// Start `note` and return the voice it was assigned to.
+    ///
+    /// A note that is already playing is retriggered on its voice. Otherwise a free voice is used,
+    /// the one released longest ago first, or one is stolen according to the [`StealPolicy`].
+    /// Velocity 0 is a note off, as in MIDI.
+    pub fn note_on(&mut self, note: u8, velocity: u8) -> Option<usize> {
+        if velocity == 0 {
+            self.note_off(note);
+            return None;
+        }
+        let index = self
+            .playing(note)
+            .or_else(|| self.free())
+            .or_else(|| self.steal())?;
+        let age = self.next_age();
+        self.voices[index] = Voice {
+            note,
+            velocity,
+            gate: true,
+            age,
+        };
+        Some(index)
+    }
+    /// Release `note` and return the voice it was playing on.
+    pub fn note_off(&mut self, note: u8) -> Option<usize> {
+        let index = self.playing(note)?;
+        let age = self.next_age();
+        let voice = &mut self.voices[index];
+        voice.gate = false;
+        voice.age = age;
+        Some(index)
+    }
+    /// Release all voices.
+    pub fn all_notes_off(&mut self) {
+        for voice in self.voices.iter_mut() {
+            voice.gate = false;
+        }
+    }
+    pub fn voice(&self, index: usize) -> &Voice {
+        &self.voices[index]
+    }
+    pub fn voices(&self) -> &[Voice; N] {
+        &self.voices
+    }
+    /// Number of voices with the gate on.
+    pub fn active_count(&self) -> usize {
+        self.voices.iter().filter(|v| v.gate).count()
+    }
+    fn playing(&self, note: u8) -> Option<usize> {
+        self.voices.iter().position(|v| v.gate && v.note == note)
+    }
+    fn free(&self) -> Option<usize> {
+        self.oldest(|v| !v.gate)
+    }
+    fn steal(&self) -> Option<usize> {
+        let indexed = self.voices.iter().enumerate();
+        match self.policy {
+            StealPolicy::Oldest => self.oldest(|v| v.gate),
+            StealPolicy::Lowest => indexed.min_by_key(|(_, v)| v.note).map(|(i, _)| i),
+            StealPolicy::Highest => indexed.max_by_key(|(_, v)| v.note).map(|(i, _)| i),
+            StealPolicy::None => None,
+        }
+    }
+    fn oldest(&self, filter: impl Fn(&Voice) -> bool) -> Option<usize> {
+        self.voices
+            .iter()
+            .enumerate()
+            // max_by_key returns the last of equals, reversed that's the lowest index
+            .rev()
+            .filter(|(_, v)| filter(v))
+            // ages wrap, compare relative to the newest
+            .max_by_key(|(_, v)| self.counter.wrapping_sub(v.age))
+            .map(|(i, _)| i)
+    }
+    fn next_age(&mut self) -> u32 {
+        self.counter = self.counter.wrapping_add(1);
+        self.counter
+    }
+}
+
+/// Equal tempered frequency of MIDI note `note` in Hz, A4 (note 69) at 440Hz.
+pub fn note_to_hz(note: u8) -> f32 {
+    440.0 * libm::exp2f((note as f32 - 69.0) / 12.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steals_the_oldest_voice() {
+        let mut voices = VoiceAllocator::<2>::new(StealPolicy::Oldest);
+        assert_eq!(voices.note_on(60, 100), Some(0));
+        assert_eq!(voices.note_on(64, 100), Some(1));
+        assert_eq!(voices.note_on(67, 100), Some(0));
+        // 60 was stolen
+        assert_eq!(voices.note_off(60), None);
+        assert_eq!(voices.note_off(64), Some(1));
+        // velocity 0 is a note off
+        assert_eq!(voices.note_on(70, 0), None);
+        assert_eq!(voices.note_on(72, 1), Some(1));
+        // retriggering reuses the voice
+        assert_eq!(voices.note_on(72, 1), Some(1));
+        assert_eq!(voices.active_count(), 2);
+        assert!((voices.voice(1).frequency() - 523.25).abs() < 0.1);
+        voices.all_notes_off();
+        assert_eq!(voices.active_count(), 0);
+    }
+
+    #[test]
+    fn other_policies() {
+        let mut voices = VoiceAllocator::<1>::new(StealPolicy::None);
+        voices.note_on(1, 1);
+        assert_eq!(voices.note_on(2, 1), None);
+        let mut voices = VoiceAllocator::<2>::new(StealPolicy::Lowest);
+        voices.note_on(50, 1);
+        voices.note_on(40, 1);
+        assert_eq!(voices.note_on(60, 1), Some(1));
+    }
+
+    #[test]
+    fn note_frequencies() {
+        assert!((note_to_hz(69) - 440.0).abs() < 1e-3);
+        assert!((note_to_hz(57) - 220.0).abs() < 1e-3);
+    }
+}
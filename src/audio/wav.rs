@@ -0,0 +1,397 @@
+//! RIFF/WAVE header parsing and PCM decoding, for sample playback from SD card or flash.
+//!
+//! ```ignore
+//! let info = wav::parse(FILE)?;
+//! for frame in info.frames(info.data(FILE)?) {
+//!     // frame.l, frame.r in -1.0..1.0
+//! }
+//! ```
+//! When streaming from a reader, [`WavReader`] reads the header and then the sample data in whole frames:
+//! ```ignore
+//! let mut header = [0; 512]; // covers the usual headers
+//! let mut wav = WavReader::new(file, &mut header).await?;
+//! let mut buf = [0; 1024];
+//! while let n @ 1.. = wav.read_data(&mut buf).await? {
+//!     for frame in wav.info().frames(&buf[..n]) { /* ... */ }
+//! }
+//! ```
+use super::Stereo;
+#[cfg(feature = "hal")]
+use embedded_io_async::Read;
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WavError {
+    /// The file doesn't start with a `RIFF` header.
+    NotRiff,
+    /// The RIFF form type isn't `WAVE`.
+    NotWave,
+    /// The `data` chunk came before a `fmt ` chunk, or there was none.
+    MissingFmt,
+    /// The bytes ended before the `data` chunk started.
+    MissingData,
+    /// A chunk header or the `fmt ` chunk was cut short, or a chunk size runs past the address space.
+    Truncated,
+    /// Only (integer) PCM is supported, this is the format tag found.
+    UnsupportedFormat(u16),
+    /// Only 16, 24 and 32-bit samples are supported.
+    UnsupportedBitDepth(u16),
+}
+
+/// What the `fmt ` and `data` chunks say about a file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct WavInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    /// Byte offset of the sample data from the start of the file.
+    pub data_offset: usize,
+    /// Length of the sample data in bytes, as given in the header.
+    pub data_len: usize,
+}
+
+/// Parse the header of a WAV file. `bytes` has to reach up to the start of the `data` chunk,
+/// the sample data itself doesn't need to be included.
+pub fn parse(bytes: &[u8]) -> Result<WavInfo, WavError> {
+    if bytes.get(0..4) != Some(b"RIFF") {
+        return Err(WavError::NotRiff);
+    }
+    if bytes.get(8..12) != Some(b"WAVE") {
+        return Err(WavError::NotWave);
+    }
+    let mut format = None;
+    let mut pos: usize = 12;
+    loop {
+        // sizes come from the file, so all offsets are checked against overflow
+        let body = pos.checked_add(8).ok_or(WavError::Truncated)?;
+        let Some(header) = bytes.get(pos..body) else {
+            return Err(WavError::MissingData);
+        };
+        let size = u32_le(&header[4..8]) as usize;
+        let end = body.checked_add(size).ok_or(WavError::Truncated)?;
+        match &header[0..4] {
+            b"fmt " => {
+                let fmt = bytes.get(body..end).ok_or(WavError::Truncated)?;
+                format = Some(parse_fmt(fmt)?);
+            }
+            b"data" => {
+                let (sample_rate, channels, bits_per_sample) =
+                    format.ok_or(WavError::MissingFmt)?;
+                return Ok(WavInfo {
+                    sample_rate,
+                    channels,
+                    bits_per_sample,
+                    data_offset: body,
+                    data_len: size,
+                });
+            }
+            // LIST, fact, cue and the like
+            _ => {}
+        }
+        // chunks are padded to an even length
+        pos = end.checked_add(size & 1).ok_or(WavError::Truncated)?;
+    }
+}
+
+// (sample rate, channels, bits per sample)
+fn parse_fmt(fmt: &[u8]) -> Result<(u32, u16, u16), WavError> {
+    if fmt.len() < 16 {
+        return Err(WavError::Truncated);
+    }
+    let mut tag = u16_le(&fmt[0..2]);
+    let channels = u16_le(&fmt[2..4]);
+    let sample_rate = u32_le(&fmt[4..8]);
+    let bits_per_sample = u16_le(&fmt[14..16]);
+    if tag == WAVE_FORMAT_EXTENSIBLE {
+        // the first two bytes of the sub format GUID are the actual format tag
+        let sub_format = fmt.get(24..26).ok_or(WavError::Truncated)?;
+        tag = u16_le(sub_format);
+    }
+    if tag != WAVE_FORMAT_PCM || channels == 0 {
+        return Err(WavError::UnsupportedFormat(tag));
+    }
+    if !matches!(bits_per_sample, 16 | 24 | 32) {
+        return Err(WavError::UnsupportedBitDepth(bits_per_sample));
+    }
+    Ok((sample_rate, channels, bits_per_sample))
+}
+
+impl WavInfo {
+    /// Bytes per frame (one sample of each channel).
+    pub fn block_align(&self) -> usize {
+        self.channels as usize * self.bytes_per_sample()
+    }
+    pub fn frame_count(&self) -> usize {
+        self.data_len / self.block_align()
+    }
+    fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample as usize / 8
+    }
+    /// The sample data within the whole file `file`, cut short if the file is.
+    pub fn data<'a>(&self, file: &'a [u8]) -> Result<&'a [u8], WavError> {
+        let end = self
+            .data_offset
+            .checked_add(self.data_len)
+            .ok_or(WavError::Truncated)?;
+        let start = self.data_offset.min(file.len());
+        Ok(&file[start..end.min(file.len())])
+    }
+    /// Interleaved samples in -1.0..1.0 from (a part of) the sample data.
+    pub fn samples<'a>(&self, data: &'a [u8]) -> Samples<'a> {
+        Samples {
+            samples: data.chunks_exact(self.bytes_per_sample()),
+        }
+    }
+    /// Stereo frames from (a part of) the sample data, which has to start on a frame.
+    /// Mono is copied to both channels, channels after the first two are skipped.
+    pub fn frames<'a>(&self, data: &'a [u8]) -> Frames<'a> {
+        Frames {
+            frames: data.chunks_exact(self.block_align()),
+            bytes_per_sample: self.bytes_per_sample(),
+        }
+    }
+}
+
+pub struct Samples<'a> {
+    samples: core::slice::ChunksExact<'a, u8>,
+}
+
+impl Iterator for Samples<'_> {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+        self.samples.next().map(decode)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.samples.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Samples<'_> {}
+
+pub struct Frames<'a> {
+    frames: core::slice::ChunksExact<'a, u8>,
+    bytes_per_sample: usize,
+}
+
+impl Iterator for Frames<'_> {
+    type Item = Stereo<f32>;
+    fn next(&mut self) -> Option<Stereo<f32>> {
+        let frame = self.frames.next()?;
+        let l = decode(&frame[..self.bytes_per_sample]);
+        let r = match frame.get(self.bytes_per_sample..self.bytes_per_sample * 2) {
+            Some(sample) => decode(sample),
+            None => l,
+        };
+        Some(Stereo::new(l, r))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Frames<'_> {}
+
+#[cfg(feature = "hal")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WavReadError<E> {
+    Wav(WavError),
+    Io(E),
+}
+
+/// Sample data streamed from a reader (a file on an SD card, flash, ...), see the module docs.
+#[cfg(feature = "hal")]
+pub struct WavReader<'b, R: Read> {
+    reader: R,
+    info: WavInfo,
+    // data read along with the header, handed out first
+    header: &'b [u8],
+    remaining: usize,
+}
+
+#[cfg(feature = "hal")]
+impl<'b, R: Read> WavReader<'b, R> {
+    /// Read the header from `reader` into `header`. Everything up to the `data` chunk has to fit
+    /// in it, otherwise this fails with [`WavError::MissingData`].
+    pub async fn new(mut reader: R, header: &'b mut [u8]) -> Result<Self, WavReadError<R::Error>> {
+        let mut filled = 0;
+        let info = loop {
+            let n = reader
+                .read(&mut header[filled..])
+                .await
+                .map_err(WavReadError::Io)?;
+            filled += n;
+            match parse(&header[..filled]) {
+                Ok(info) => break info,
+                // more of the header to come, the first reads may not even cover the RIFF header
+                Err(_) if n > 0 && filled < 12 => {}
+                Err(WavError::MissingData | WavError::Truncated)
+                    if n > 0 && filled < header.len() => {}
+                Err(e) => return Err(WavReadError::Wav(e)),
+            }
+        };
+        let header: &'b [u8] = header;
+        let read_ahead = &header[info.data_offset.min(filled)..filled];
+        let read_ahead = &read_ahead[..read_ahead.len().min(info.data_len)];
+        Ok(Self {
+            reader,
+            info,
+            header: read_ahead,
+            remaining: info.data_len - read_ahead.len(),
+        })
+    }
+    pub fn info(&self) -> &WavInfo {
+        &self.info
+    }
+    /// Fill `buf` with the next whole frames of sample data. Returns the number of bytes read,
+    /// a multiple of [`WavInfo::block_align`], and 0 at the end of the data.
+    /// `buf` has to hold at least one frame.
+    pub async fn read_data(&mut self, buf: &mut [u8]) -> Result<usize, R::Error> {
+        let len = buf.len() - buf.len() % self.info.block_align();
+        let mut filled = self.header.len().min(len);
+        buf[..filled].copy_from_slice(&self.header[..filled]);
+        self.header = &self.header[filled..];
+        while filled < len && self.remaining > 0 {
+            let end = len.min(filled + self.remaining);
+            let n = self.reader.read(&mut buf[filled..end]).await?;
+            if n == 0 {
+                // the file is shorter than its header says
+                self.remaining = 0;
+                break;
+            }
+            filled += n;
+            self.remaining -= n;
+        }
+        Ok(filled - filled % self.info.block_align())
+    }
+}
+
+// little endian signed PCM of 2, 3 or 4 bytes
+fn decode(sample: &[u8]) -> f32 {
+    match *sample {
+        [a, b] => i16::from_le_bytes([a, b]) as f32 / 32_768.0,
+        // shifted up into an i32 to sign extend
+        [a, b, c] => (i32::from_le_bytes([0, a, b, c]) >> 8) as f32 / 8_388_608.0,
+        [a, b, c, d] => i32::from_le_bytes([a, b, c, d]) as f32 / 2_147_483_648.0,
+        _ => 0.0,
+    }
+}
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // RIFF header, a 16 byte fmt chunk and a data chunk header
+    fn header(channels: u16, bits: u16, data_len: u32) -> [u8; 44] {
+        let mut h = [0; 44];
+        h[0..4].copy_from_slice(b"RIFF");
+        h[8..12].copy_from_slice(b"WAVE");
+        h[12..16].copy_from_slice(b"fmt ");
+        h[16..20].copy_from_slice(&16u32.to_le_bytes());
+        h[20..22].copy_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+        h[22..24].copy_from_slice(&channels.to_le_bytes());
+        h[24..28].copy_from_slice(&48_000u32.to_le_bytes());
+        h[34..36].copy_from_slice(&bits.to_le_bytes());
+        h[36..40].copy_from_slice(b"data");
+        h[40..44].copy_from_slice(&data_len.to_le_bytes());
+        h
+    }
+
+    #[test]
+    fn parses_pcm_header() {
+        let info = parse(&header(2, 24, 60)).unwrap();
+        assert_eq!(info.sample_rate, 48_000);
+        assert_eq!((info.channels, info.bits_per_sample), (2, 24));
+        assert_eq!((info.data_offset, info.data_len), (44, 60));
+        assert_eq!((info.block_align(), info.frame_count()), (6, 10));
+    }
+
+    #[test]
+    fn decodes_frames() {
+        let mut file = [0u8; 52];
+        file[..44].copy_from_slice(&header(2, 16, 8));
+        file[44..52].copy_from_slice(&[0x00, 0x40, 0x00, 0xC0, 0xFF, 0x7F, 0x00, 0x80]);
+        let info = parse(&file).unwrap();
+        let frames: [Stereo<f32>; 2] = {
+            let mut frames = info.frames(info.data(&file).unwrap());
+            [frames.next().unwrap(), frames.next().unwrap()]
+        };
+        assert_eq!((frames[0].l, frames[0].r), (0.5, -0.5));
+        assert_eq!(frames[1].r, -1.0);
+        assert!((frames[1].l - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn decodes_24_bit_mono() {
+        let mut h = header(1, 24, 3);
+        h[40..44].copy_from_slice(&3u32.to_le_bytes());
+        let info = parse(&h).unwrap();
+        let mut frames = info.frames(&[0x00, 0x00, 0xC0]);
+        let frame = frames.next().unwrap();
+        assert_eq!((frame.l, frame.r), (-0.5, -0.5));
+        assert!(frames.next().is_none());
+    }
+
+    #[test]
+    fn rejects_unsupported() {
+        assert_eq!(parse(b"RIFX\0\0\0\0WAVE"), Err(WavError::NotRiff));
+        assert_eq!(parse(b"RIFF\0\0\0\0AVI "), Err(WavError::NotWave));
+        let mut h = header(2, 8, 0);
+        assert_eq!(parse(&h), Err(WavError::UnsupportedBitDepth(8)));
+        h[20..22].copy_from_slice(&3u16.to_le_bytes()); // IEEE float
+        assert_eq!(parse(&h), Err(WavError::UnsupportedFormat(3)));
+        // data before fmt
+        let mut h = header(2, 16, 0);
+        h[12..16].copy_from_slice(b"data");
+        assert_eq!(parse(&h), Err(WavError::MissingFmt));
+    }
+
+    #[test]
+    fn truncated_chunk_headers() {
+        let h = header(2, 16, 0);
+        // cut inside the fmt chunk
+        assert_eq!(parse(&h[..30]), Err(WavError::Truncated));
+        // cut inside the data chunk header
+        assert_eq!(parse(&h[..40]), Err(WavError::MissingData));
+        assert_eq!(parse(&h[..12]), Err(WavError::MissingData));
+    }
+
+    #[test]
+    fn oversized_chunk_sizes() {
+        // a LIST chunk claiming 4GB: skipping it runs past the end, or overflows on 32-bit
+        let mut h = header(2, 16, 0);
+        h[12..16].copy_from_slice(b"LIST");
+        h[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            parse(&h),
+            Err(WavError::MissingData | WavError::Truncated)
+        ));
+        // a fmt chunk claiming more than there is
+        let mut h = header(2, 16, 0);
+        h[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(parse(&h), Err(WavError::Truncated));
+        // a data length past the address space
+        let info = WavInfo {
+            data_offset: usize::MAX - 4,
+            data_len: 8,
+            ..parse(&header(2, 16, 0)).unwrap()
+        };
+        assert_eq!(info.data(&[0; 4]), Err(WavError::Truncated));
+        // and a data chunk longer than the file is cut short
+        let h = header(2, 16, u32::MAX);
+        let info = parse(&h).unwrap();
+        assert_eq!(info.data(&h), Ok(&[][..]));
+    }
+}
@@ -0,0 +1,102 @@
+//! The 4KB backup SRAM, which keeps its contents across resets (watchdog, software, NRST),
+//! for state that should come back instantly, without a flash write.
+//!
+//! Contents are lost when the power goes, so every record carries a marker and a CRC, and
+//! [`BackupRam::load`] only returns what a [`BackupRam::store`] of this firmware wrote:
+//! ```ignore
+//! let backup_ram = board.backup_ram.as_mut().unwrap();
+//! let preset = backup_ram.load().and_then(|data| data.first().copied()).unwrap_or(0);
+//! // on every preset change
+//! backup_ram.store(&[preset]);
+//! ```
+use crate::crc::crc32_software;
+use crate::memory::{BACKUP_SRAM_BASE, BACKUP_SRAM_SIZE};
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_stm32 as hal;
+
+const BASE: *mut u8 = BACKUP_SRAM_BASE as *mut u8;
+const SIZE: usize = BACKUP_SRAM_SIZE;
+// marker, length, CRC-32 of the data
+const HEADER_LEN: usize = 12;
+const MAGIC: u32 = 0x4441_4953; // "DAIS"
+/// Bytes [`BackupRam::store`] takes at most.
+pub const CAPACITY: usize = SIZE - HEADER_LEN;
+
+// Cortex-M7 data cache maintenance: clean by address to the point of coherency
+const DCCMVAC: *mut u32 = 0xE000_EF68 as *mut u32;
+const CACHE_LINE: usize = 32;
+
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// The backup SRAM. Only one exists, see [`BackupRam::take`].
+pub struct BackupRam {
+    _private: (),
+}
+
+impl BackupRam {
+    /// Enable the backup SRAM's clock and lift the backup domain write protection.
+    /// `None` if it was taken already; [`crate::new_daisy_p`] takes it for [`crate::DaisyBoard`],
+    /// which then has none if the application took it first.
+    pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::Relaxed) {
+            return None;
+        }
+        hal::pac::RCC.ahb4enr().modify(|w| w.set_bkpramen(true));
+        hal::pac::PWR.cr1().modify(|w| w.set_dbp(true));
+        // DBP takes effect after a few bus cycles
+        while !hal::pac::PWR.cr1().read().dbp() {}
+        Some(Self { _private: () })
+    }
+    /// The data of the last [`BackupRam::store`], `None` after power up or [`BackupRam::clear`].
+    pub fn load(&self) -> Option<&[u8]> {
+        let magic = self.read_u32(0);
+        let len = self.read_u32(4) as usize;
+        let crc = self.read_u32(8);
+        if magic != MAGIC || len > CAPACITY {
+            return None;
+        }
+        // Safety: within the backup SRAM, which only this struct writes to
+        let data = unsafe { core::slice::from_raw_parts(BASE.add(HEADER_LEN), len) };
+        (crc32_software(data) == crc).then_some(data)
+    }
+    /// Replace the stored data. Panics if `data` is longer than [`CAPACITY`].
+    pub fn store(&mut self, data: &[u8]) {
+        assert!(data.len() <= CAPACITY, "too much data for the backup SRAM");
+        // invalid while half written, in case a reset comes in between
+        self.write_u32(0, 0);
+        // Safety: within the backup SRAM
+        unsafe {
+            core::ptr::copy_nonoverlapping(data.as_ptr(), BASE.add(HEADER_LEN), data.len());
+        }
+        self.write_u32(4, data.len() as u32);
+        self.write_u32(8, crc32_software(data));
+        self.write_u32(0, MAGIC);
+        clean_dcache(HEADER_LEN + data.len());
+    }
+    /// Forget the stored data.
+    pub fn clear(&mut self) {
+        self.write_u32(0, 0);
+        clean_dcache(HEADER_LEN);
+    }
+    fn read_u32(&self, offset: usize) -> u32 {
+        // Safety: aligned and within the backup SRAM
+        unsafe { BASE.add(offset).cast::<u32>().read_volatile() }
+    }
+    fn write_u32(&mut self, offset: usize, value: u32) {
+        // Safety: aligned and within the backup SRAM
+        unsafe { BASE.add(offset).cast::<u32>().write_volatile(value) }
+    }
+}
+
+// With the data cache on, writes may sit in the cache and a reset would throw them away.
+// Cleaning is harmless with the cache off.
+fn clean_dcache(len: usize) {
+    // Safety: write only cache maintenance registers, see the ARMv7-M ARM, B2.2.7
+    unsafe {
+        core::arch::asm!("dsb sy");
+        for offset in (0..len).step_by(CACHE_LINE) {
+            DCCMVAC.write_volatile(BASE as u32 + offset as u32);
+        }
+        core::arch::asm!("dsb sy", "isb sy");
+    }
+}
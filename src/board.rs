@@ -1,14 +1,18 @@
-use crate::audio::{self, AudioBlockBuffers, AudioConfig, Interface};
+use crate::audio::{self, AudioBlockBuffers, AudioConfig, AudioError, Interface};
+use crate::backup_ram::BackupRam;
 use crate::pins::*;
 use crate::{led::UserLed, usb::DaisyUsb};
 use embassy_stm32 as hal;
-use hal::peripherals::USB_OTG_FS;
-use hal::{bind_interrupts, i2c, peripherals, usb};
+use hal::peripherals::{RNG, USB_OTG_FS};
+use hal::rng::Rng;
+use hal::{bind_interrupts, i2c, peripherals, rng, usb};
 
 bind_interrupts!(pub struct Irqs {
     OTG_FS => usb::InterruptHandler<peripherals::USB_OTG_FS>;
+    OTG_HS => usb::InterruptHandler<peripherals::USB_OTG_HS>;
     I2C2_EV => i2c::EventInterruptHandler<peripherals::I2C2>;
     I2C2_ER => i2c::ErrorInterruptHandler<peripherals::I2C2>;
+    RNG => rng::InterruptHandler<peripherals::RNG>;
 });
 
 #[allow(non_snake_case)]
@@ -21,6 +25,11 @@ pub struct DaisyBoard<'a> {
     pub FMC: (),   //TODO
     pub SDRAM: (), // TODO
     pub daisy_usb: DaisyUsb,
+    /// Hardware random number generator, see [`crate::audio::white_noise`].
+    pub rng: Rng<'a, RNG>,
+    /// State that survives a reset, see [`crate::backup_ram`].
+    /// `None` if the application took it with [`BackupRam::take`] before.
+    pub backup_ram: Option<BackupRam>,
 }
 
 pub struct DaisyPeripherals {
@@ -30,14 +39,19 @@ pub struct DaisyPeripherals {
     pub audio_peripherals: audio::Peripherals,
     pub usb2_pins: USB2Pins,
     pub usb_otg_fs: USB_OTG_FS,
+    pub rng: RNG,
+    pub backup_ram: Option<BackupRam>,
 }
 
 impl<'a> DaisyBoard<'a> {
-    pub async fn new(p: DaisyPeripherals, audio_config: AudioConfig) -> (Self, AudioBlockBuffers) {
+    pub async fn new(
+        p: DaisyPeripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
         let usb_driver = crate::usb::init(p.usb_otg_fs, p.usb2_pins);
         let (interface, buffers) =
-            Interface::new(p.wm8731_pin, p.audio_peripherals, audio_config).await;
-        (
+            Interface::new(p.wm8731_pin, p.audio_peripherals, audio_config).await?;
+        Ok((
             Self {
                 daisy_pins: p.daisy_pins,
                 user_led: UserLed::new(p.led_user_pin),
@@ -45,8 +59,10 @@ impl<'a> DaisyBoard<'a> {
                 FMC: (),
                 SDRAM: (),
                 daisy_usb: usb_driver,
+                rng: Rng::new(p.rng, Irqs),
+                backup_ram: p.backup_ram,
             },
             buffers,
-        )
+        ))
     }
 }
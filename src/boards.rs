@@ -0,0 +1,49 @@
+//! Daisy platforms other than the bare Daisy Seed.
+//! Each board lives behind its own cargo feature.
+//!
+//! Boards with a panel implement [`BoardControls`], so UI code (menus, parameter pages) can be
+//! written once for all of them:
+//! ```ignore
+//! fn show_knobs(board: &mut impl BoardControls) {
+//!     for i in 0..board.knob_count().min(board.led_count()) {
+//!         let value = board.knob(i);
+//!         board.set_led(i, Rgb::new(value, 0.0, 1.0 - value));
+//!     }
+//!     board.update_leds();
+//! }
+//! ```
+use crate::led::Rgb;
+use crate::switch::Switch;
+
+#[cfg(feature = "patch_sm")]
+pub mod patch_sm;
+#[cfg(feature = "petal")]
+pub mod petal;
+#[cfg(feature = "seed_2_dfm")]
+pub mod seed2_dfm;
+#[cfg(feature = "versio")]
+pub mod versio;
+
+/// Knobs, buttons and LEDs of a board, by index.
+///
+/// Counts differ from board to board; indices from `0` to the count are valid, others panic.
+/// What doesn't fit the common shape (three position switches, encoders, gate inputs)
+/// stays on the board's own type.
+pub trait BoardControls {
+    fn knob_count(&self) -> usize;
+    /// Position of knob `index` (plus its CV input, where summed in hardware), 0.0 to 1.0.
+    /// Reads the ADC.
+    fn knob(&mut self, index: usize) -> f32;
+    /// Momentary switches: buttons and footswitches.
+    fn switch_count(&self) -> usize;
+    fn switch(&self, index: usize) -> &Switch<'_>;
+    /// Sample all switches, every [`crate::switch::UPDATE_INTERVAL`].
+    fn update_switches(&mut self);
+    fn led_count(&self) -> usize;
+    /// Set LED `index`. Single color LEDs take the brightest component of `color`.
+    /// Takes effect with [`BoardControls::update_leds`].
+    fn set_led(&mut self, index: usize, color: Rgb);
+    /// Bring the LEDs up to date. Call it regularly, e.g. once per audio block:
+    /// some boards dim their LEDs in software and need a steady rate.
+    fn update_leds(&mut self);
+}
@@ -0,0 +1,233 @@
+//! Daisy Patch Submodule (Patch SM, also the core of Daisy Patch.init()).
+//!
+//! The Patch SM has its own pinout, a PCM3060 codec on SAI1 (configured over I2C2 on PB10/PB11),
+//! eight bipolar CV inputs, two CV outputs driven by the MCU DAC, and two gate inputs/outputs.
+//! See: https://github.com/electro-smith/libDaisy/blob/master/src/daisy_patch_sm.cpp
+use crate::audio::{self, AudioBlockBuffers, AudioConfig, AudioError, Interface};
+use crate::board::Irqs;
+use crate::pins::{LedUserPin, Pcm3060Pins, USB2Pins};
+use crate::{led::UserLed, usb::DaisyUsb};
+use embassy_stm32 as hal;
+use hal::dac::{Dac, DacCh1, DacCh2, Value};
+use hal::dma::NoDma;
+use hal::gpio::{Input, Level, Output, Pull, Speed};
+use hal::peripherals::{DAC1, RNG, USB_OTG_FS};
+use hal::rng::Rng;
+
+// - types --------------------------------------------------------------------
+
+#[allow(non_snake_case)]
+pub struct PatchSmPins {
+    pub CV_1: hal::peripherals::PA3,   // C5, ADC
+    pub CV_2: hal::peripherals::PA6,   // C4, ADC
+    pub CV_3: hal::peripherals::PA2,   // C3, ADC
+    pub CV_4: hal::peripherals::PA7,   // C2, ADC
+    pub CV_5: hal::peripherals::PB1,   // C6, ADC
+    pub CV_6: hal::peripherals::PC4,   // C7, ADC
+    pub CV_7: hal::peripherals::PC0,   // C8, ADC
+    pub CV_8: hal::peripherals::PC1,   // C9, ADC
+    pub ADC_9: hal::peripherals::PA1,  // ADC, unipolar
+    pub ADC_10: hal::peripherals::PA0, // ADC, unipolar
+    pub ADC_11: hal::peripherals::PC3, // ADC, unipolar
+    pub ADC_12: hal::peripherals::PC2, // ADC, unipolar
+    pub A8: hal::peripherals::PB14,    // USB1 D-, USART1 Tx
+    pub A9: hal::peripherals::PB15,    // USB1 D+, USART1 Rx
+    pub B7: hal::peripherals::PB8,     // I2C1 SCL, UART4 Rx
+    pub B8: hal::peripherals::PB9,     // I2C1 SDA, UART4 Tx
+    pub D1: hal::peripherals::PB4,     // GPIO
+    pub D2: hal::peripherals::PC11,    // SD Data3, USART3 Rx
+    pub D3: hal::peripherals::PC10,    // SD Data2, USART3 Tx
+    pub D4: hal::peripherals::PC9,     // SD Data1
+    pub D5: hal::peripherals::PC8,     // SD Data0
+    pub D6: hal::peripherals::PC12,    // SD CLK, UART5 Tx
+    pub D7: hal::peripherals::PD2,     // SD CMD, UART5 Rx
+}
+
+#[allow(non_snake_case)]
+pub struct GatePins {
+    pub GATE_IN_1: hal::peripherals::PG13,  // B10
+    pub GATE_IN_2: hal::peripherals::PG14,  // B9
+    pub GATE_OUT_1: hal::peripherals::PC14, // B5
+    pub GATE_OUT_2: hal::peripherals::PC13, // B6
+}
+
+#[allow(non_snake_case)]
+pub struct CvOutPins {
+    pub CV_OUT_1: hal::peripherals::PA4, // C10, DAC OUT 1
+    pub CV_OUT_2: hal::peripherals::PA5, // C1, DAC OUT 2, also drives the LED on Patch.init()
+}
+
+pub struct PatchSmPeripherals {
+    pub patch_sm_pins: PatchSmPins,
+    pub gate_pins: GatePins,
+    pub cv_out_pins: CvOutPins,
+    pub dac1: DAC1,
+    pub led_user_pin: LedUserPin,
+    pub pcm3060_pin: Pcm3060Pins,
+    pub audio_peripherals: audio::Peripherals,
+    pub usb2_pins: USB2Pins,
+    pub usb_otg_fs: USB_OTG_FS,
+    pub rng: RNG,
+}
+
+/// Gate input.
+/// The Patch SM gate inputs are buffered by an inverting transistor stage,
+/// so the raw pin level is inverted here.
+pub struct GateIn<'a>(Input<'a>);
+
+impl<'a> GateIn<'a> {
+    pub fn is_high(&self) -> bool {
+        self.0.is_low()
+    }
+    pub fn is_low(&self) -> bool {
+        self.0.is_high()
+    }
+}
+
+/// CV outputs driven by the MCU DAC.
+/// The DAC's 0V..3.3V range is scaled to 0V..5V on the Patch SM.
+pub struct CvOut<'a> {
+    ch1: DacCh1<'a, DAC1, NoDma>,
+    ch2: DacCh2<'a, DAC1, NoDma>,
+}
+
+pub enum CvOutChannel {
+    One,
+    Two,
+}
+
+impl<'a> CvOut<'a> {
+    const MAX_VOLTAGE: f32 = 5.0;
+    const MAX_VALUE: u16 = 4095;
+
+    /// Write a raw 12 bit value.
+    pub fn set_raw(&mut self, ch: CvOutChannel, value: u16) {
+        let value = Value::Bit12Right(value.min(Self::MAX_VALUE));
+        match ch {
+            CvOutChannel::One => self.ch1.set(value),
+            CvOutChannel::Two => self.ch2.set(value),
+        }
+    }
+    /// Write a voltage between 0V and 5V. Out of range values are clamped.
+    pub fn set_voltage(&mut self, ch: CvOutChannel, volts: f32) {
+        let volts = volts.clamp(0.0, Self::MAX_VOLTAGE);
+        let value = (volts / Self::MAX_VOLTAGE * Self::MAX_VALUE as f32) as u16;
+        self.set_raw(ch, value);
+    }
+}
+
+pub struct PatchSmBoard<'a> {
+    pub patch_sm_pins: PatchSmPins,
+
+    // board peripherals
+    pub user_led: UserLed<'a>,
+    pub interface: Interface<'a>,
+    pub cv_out: CvOut<'a>,
+    pub gate_in_1: GateIn<'a>,
+    pub gate_in_2: GateIn<'a>,
+    pub gate_out_1: Output<'a>,
+    pub gate_out_2: Output<'a>,
+    pub daisy_usb: DaisyUsb,
+    /// Hardware random number generator, see [`crate::audio::white_noise`].
+    pub rng: Rng<'a, RNG>,
+}
+
+impl<'a> PatchSmBoard<'a> {
+    pub async fn new(
+        p: PatchSmPeripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        let usb_driver = crate::usb::init(p.usb_otg_fs, p.usb2_pins);
+        let (interface, buffers) =
+            Interface::new_pcm3060(p.pcm3060_pin, p.audio_peripherals, audio_config).await?;
+        let (ch1, ch2) = Dac::new(
+            p.dac1,
+            NoDma,
+            NoDma,
+            p.cv_out_pins.CV_OUT_1,
+            p.cv_out_pins.CV_OUT_2,
+        )
+        .split();
+        Ok((
+            Self {
+                patch_sm_pins: p.patch_sm_pins,
+                user_led: UserLed::new(p.led_user_pin),
+                interface,
+                cv_out: CvOut { ch1, ch2 },
+                gate_in_1: GateIn(Input::new(p.gate_pins.GATE_IN_1, Pull::None)),
+                gate_in_2: GateIn(Input::new(p.gate_pins.GATE_IN_2, Pull::None)),
+                gate_out_1: Output::new(p.gate_pins.GATE_OUT_1, Level::Low, Speed::Low),
+                gate_out_2: Output::new(p.gate_pins.GATE_OUT_2, Level::Low, Speed::Low),
+                daisy_usb: usb_driver,
+                rng: Rng::new(p.rng, Irqs),
+            },
+            buffers,
+        ))
+    }
+}
+
+#[macro_export]
+macro_rules! new_patch_sm_p {
+    ($p:ident) => {
+        $crate::boards::patch_sm::PatchSmPeripherals {
+            patch_sm_pins: $crate::boards::patch_sm::PatchSmPins {
+                CV_1: $p.PA3,
+                CV_2: $p.PA6,
+                CV_3: $p.PA2,
+                CV_4: $p.PA7,
+                CV_5: $p.PB1,
+                CV_6: $p.PC4,
+                CV_7: $p.PC0,
+                CV_8: $p.PC1,
+                ADC_9: $p.PA1,
+                ADC_10: $p.PA0,
+                ADC_11: $p.PC3,
+                ADC_12: $p.PC2,
+                A8: $p.PB14,
+                A9: $p.PB15,
+                B7: $p.PB8,
+                B8: $p.PB9,
+                D1: $p.PB4,
+                D2: $p.PC11,
+                D3: $p.PC10,
+                D4: $p.PC9,
+                D5: $p.PC8,
+                D6: $p.PC12,
+                D7: $p.PD2,
+            },
+            gate_pins: $crate::boards::patch_sm::GatePins {
+                GATE_IN_1: $p.PG13,
+                GATE_IN_2: $p.PG14,
+                GATE_OUT_1: $p.PC14,
+                GATE_OUT_2: $p.PC13,
+            },
+            cv_out_pins: $crate::boards::patch_sm::CvOutPins {
+                CV_OUT_1: $p.PA4,
+                CV_OUT_2: $p.PA5,
+            },
+            dac1: $p.DAC1,
+            led_user_pin: $p.PC7,
+            pcm3060_pin: $crate::pins::Pcm3060Pins {
+                SCL: $p.PB10,
+                SDA: $p.PB11,
+                MCLK_A: $p.PE2,
+                SCK_A: $p.PE5,
+                FS_A: $p.PE4,
+                SD_A: $p.PE6,
+                SD_B: $p.PE3,
+            },
+            audio_peripherals: $crate::audio::Peripherals {
+                sai1: $p.SAI1,
+                i2c2: $p.I2C2,
+                dma1_ch1: $p.DMA1_CH1,
+                dma1_ch2: $p.DMA1_CH2,
+            },
+            usb2_pins: $crate::pins::USB2Pins {
+                DN: $p.PA11,
+                DP: $p.PA12,
+            },
+            usb_otg_fs: $p.USB_OTG_FS,
+            rng: $p.RNG,
+        }
+    };
+}
@@ -0,0 +1,435 @@
+//! Daisy Petal, the guitar pedal platform built around a Daisy Seed.
+//!
+//! The Petal has seven switches (four footswitches and three toggles), six knobs,
+//! an expression pedal input, a rotary encoder, and eight RGB LEDs in a ring
+//! plus four footswitch LEDs, driven by two PCA9685 LED drivers on I2C1.
+//! Audio goes through the Seed's own codec.
+//! See: https://github.com/electro-smith/libDaisy/blob/master/src/daisy_petal.cpp
+use crate::audio::{self, AudioBlockBuffers, AudioConfig, AudioError, Interface};
+use crate::board::Irqs;
+use crate::boards::BoardControls;
+pub use crate::led::Rgb;
+use crate::pins::{LedUserPin, USB2Pins, WM8731Pins};
+use crate::switch::Switch;
+use crate::{led::UserLed, usb::DaisyUsb};
+use defmt::warn;
+use embassy_stm32 as hal;
+use hal::adc::{Adc, Resolution, SampleTime};
+use hal::gpio::{Input, Level, Pull};
+use hal::i2c::I2c;
+use hal::peripherals::{ADC1, I2C1, RNG, USB_OTG_FS};
+use hal::rng::Rng;
+use hal::time::Hertz;
+
+// - types --------------------------------------------------------------------
+
+#[allow(non_snake_case)]
+pub struct PetalPins {
+    pub SW_1: hal::peripherals::PG11,      // SEED_PIN_8, footswitch
+    pub SW_2: hal::peripherals::PB4,       // SEED_PIN_9, footswitch
+    pub SW_3: hal::peripherals::PB5,       // SEED_PIN_10, footswitch
+    pub SW_4: hal::peripherals::PB6,       // SEED_PIN_13, footswitch
+    pub SW_5: hal::peripherals::PA0,       // SEED_PIN_25, toggle
+    pub SW_6: hal::peripherals::PD11,      // SEED_PIN_26, toggle
+    pub SW_7: hal::peripherals::PG10,      // SEED_PIN_7, toggle
+    pub ENC_A: hal::peripherals::PA2,      // SEED_PIN_28
+    pub ENC_B: hal::peripherals::PG9,      // SEED_PIN_27
+    pub ENC_CLICK: hal::peripherals::PB7,  // SEED_PIN_14
+    pub EXPRESSION: hal::peripherals::PC0, // SEED_PIN_15, ADC
+    pub KNOB_1: hal::peripherals::PA3,     // SEED_PIN_16, ADC
+    pub KNOB_2: hal::peripherals::PA6,     // SEED_PIN_19, ADC
+    pub KNOB_3: hal::peripherals::PB1,     // SEED_PIN_17, ADC
+    pub KNOB_4: hal::peripherals::PC1,     // SEED_PIN_20, ADC
+    pub KNOB_5: hal::peripherals::PA7,     // SEED_PIN_18, ADC
+    pub KNOB_6: hal::peripherals::PC4,     // SEED_PIN_21, ADC
+}
+
+#[allow(non_snake_case)]
+pub struct LedDriverPins {
+    pub SCL: hal::peripherals::PB8, // SEED_PIN_11, I2C1 SCL
+    pub SDA: hal::peripherals::PB9, // SEED_PIN_12, I2C1 SDA
+}
+
+pub struct PetalPeripherals {
+    pub petal_pins: PetalPins,
+    pub led_driver_pins: LedDriverPins,
+    pub i2c1: I2C1,
+    pub adc1: ADC1,
+    pub led_user_pin: LedUserPin,
+    pub wm8731_pin: WM8731Pins,
+    pub audio_peripherals: audio::Peripherals,
+    pub usb2_pins: USB2Pins,
+    pub usb_otg_fs: USB_OTG_FS,
+    pub rng: RNG,
+}
+
+/// Footswitches and toggles, in board order.
+/// Call [`Switch::update`] on each of them every [`crate::switch::UPDATE_INTERVAL`],
+/// e.g. with [`PetalBoard::update_switches`].
+pub const SWITCH_COUNT: usize = 7;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Knob {
+    One,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+    Expression,
+}
+
+impl Knob {
+    /// In board order, the indices of [`BoardControls::knob`].
+    pub const ALL: [Knob; 7] = [
+        Knob::One,
+        Knob::Two,
+        Knob::Three,
+        Knob::Four,
+        Knob::Five,
+        Knob::Six,
+        Knob::Expression,
+    ];
+}
+
+/// Knobs and the expression input, read from ADC1.
+pub struct Knobs<'a> {
+    adc: Adc<'a, ADC1>,
+    knob_1: hal::peripherals::PA3,
+    knob_2: hal::peripherals::PA6,
+    knob_3: hal::peripherals::PB1,
+    knob_4: hal::peripherals::PC1,
+    knob_5: hal::peripherals::PA7,
+    knob_6: hal::peripherals::PC4,
+    expression: hal::peripherals::PC0,
+}
+
+impl<'a> Knobs<'a> {
+    const MAX_VALUE: f32 = u16::MAX as f32;
+
+    /// Position from 0.0 (fully counter clockwise, heel down) to 1.0.
+    pub fn read(&mut self, knob: Knob) -> f32 {
+        let raw = match knob {
+            Knob::One => self.adc.read(&mut self.knob_1),
+            Knob::Two => self.adc.read(&mut self.knob_2),
+            Knob::Three => self.adc.read(&mut self.knob_3),
+            Knob::Four => self.adc.read(&mut self.knob_4),
+            Knob::Five => self.adc.read(&mut self.knob_5),
+            Knob::Six => self.adc.read(&mut self.knob_6),
+            Knob::Expression => self.adc.read(&mut self.expression),
+        };
+        raw as f32 / Self::MAX_VALUE
+    }
+}
+
+pub const RING_LED_COUNT: usize = 8;
+pub const FOOTSWITCH_LED_COUNT: usize = 4;
+
+// PCA9685 registers
+const PCA9685_BASE_ADDRESS: u8 = 0x40;
+const PCA9685_MODE1: u8 = 0x00;
+const PCA9685_MODE2: u8 = 0x01;
+const PCA9685_LED0_ON_L: u8 = 0x06;
+// auto increment on, normal mode (oscillator running)
+const PCA9685_MODE1_AUTO_INCREMENT: u8 = 0b0010_0000;
+// outputs inverted, totem pole, high impedance while OE is high.
+// The LEDs sink into the driver, so an inverted output turns them on with a high duty.
+const PCA9685_MODE2_INVERTED: u8 = 0b0001_0110;
+// bit 4 of LEDn_ON_H / LEDn_OFF_H
+const PCA9685_FULL: u8 = 0b0001_0000;
+const PCA9685_CHANNELS: usize = 16;
+const PCA9685_MAX_VALUE: u16 = 4095;
+
+// Hardware address pins of the two drivers, added to PCA9685_BASE_ADDRESS.
+const LED_DRIVER_ADDRESSES: [u8; 2] = [0x00, 0x02];
+const LED_CHANNELS: usize = PCA9685_CHANNELS * LED_DRIVER_ADDRESSES.len();
+
+// Driver channels (driver * 16 + output) of each LED, as wired on the Petal.
+const RING_LED_CHANNELS: [[usize; 3]; RING_LED_COUNT] = [
+    [0, 1, 2],    // ring 1
+    [6, 7, 8],    // ring 2
+    [12, 13, 14], // ring 3
+    [16, 17, 18], // ring 4
+    [3, 4, 5],    // ring 5
+    [9, 10, 11],  // ring 6
+    [19, 20, 21], // ring 7
+    [22, 23, 24], // ring 8
+];
+const FOOTSWITCH_LED_CHANNELS: [usize; FOOTSWITCH_LED_COUNT] = [15, 25, 26, 27];
+
+/// The LED ring and footswitch LEDs.
+///
+/// Setters only change a local frame, [`PetalLeds::update`] sends it to the LED drivers.
+pub struct PetalLeds<'a> {
+    i2c: I2c<'a, hal::mode::Blocking>,
+    // 12 bit duty per driver channel
+    frame: [u16; LED_CHANNELS],
+    // frame changed since the last update
+    dirty: bool,
+}
+
+impl<'a> PetalLeds<'a> {
+    const I2C_FS: Hertz = Hertz(1_000_000);
+
+    fn new(i2c1: I2C1, pins: LedDriverPins) -> Result<Self, hal::i2c::Error> {
+        let i2c = I2c::new_blocking(
+            i2c1,
+            pins.SCL,
+            pins.SDA,
+            Self::I2C_FS,
+            hal::i2c::Config::default(),
+        );
+        let mut leds = Self {
+            i2c,
+            frame: [0; LED_CHANNELS],
+            dirty: false,
+        };
+        for address in LED_DRIVER_ADDRESSES {
+            let address = PCA9685_BASE_ADDRESS + address;
+            leds.i2c
+                .blocking_write(address, &[PCA9685_MODE1, PCA9685_MODE1_AUTO_INCREMENT])?;
+            leds.i2c
+                .blocking_write(address, &[PCA9685_MODE2, PCA9685_MODE2_INVERTED])?;
+        }
+        // The outputs follow the PWM registers once the oscillator has started, 500us after leaving sleep.
+        leds.update()?;
+        Ok(leds)
+    }
+    /// Set ring LED `index` (0..8, clockwise from the top left) to `color`.
+    pub fn set_ring(&mut self, index: usize, color: Rgb) {
+        let [r, g, b] = RING_LED_CHANNELS[index];
+        self.frame[r] = brightness_to_duty(color.r);
+        self.frame[g] = brightness_to_duty(color.g);
+        self.frame[b] = brightness_to_duty(color.b);
+        self.dirty = true;
+    }
+    /// Set the LED above footswitch `index` (0..4) to `brightness`, from 0.0 to 1.0.
+    pub fn set_footswitch(&mut self, index: usize, brightness: f32) {
+        self.frame[FOOTSWITCH_LED_CHANNELS[index]] = brightness_to_duty(brightness);
+        self.dirty = true;
+    }
+    /// Turn every LED off. Takes effect on the next [`PetalLeds::update`].
+    pub fn clear(&mut self) {
+        self.frame = [0; LED_CHANNELS];
+        self.dirty = true;
+    }
+    /// Send the frame to both LED drivers.
+    pub fn update(&mut self) -> Result<(), hal::i2c::Error> {
+        for (address, frame) in LED_DRIVER_ADDRESSES
+            .iter()
+            .zip(self.frame.chunks_exact(PCA9685_CHANNELS))
+        {
+            // register address, then ON_L, ON_H, OFF_L, OFF_H per channel (auto incremented)
+            let mut buf = [0u8; 1 + PCA9685_CHANNELS * 4];
+            buf[0] = PCA9685_LED0_ON_L;
+            for (regs, duty) in buf[1..].chunks_exact_mut(4).zip(frame) {
+                regs.copy_from_slice(&pca9685_channel_registers(*duty));
+            }
+            self.i2c
+                .blocking_write(PCA9685_BASE_ADDRESS + address, &buf)?;
+        }
+        self.dirty = false;
+        Ok(())
+    }
+    /// [`PetalLeds::update`], if anything was set since the last one. Cheap enough to call often.
+    pub fn update_if_changed(&mut self) -> Result<(), hal::i2c::Error> {
+        if self.dirty {
+            self.update()?;
+        }
+        Ok(())
+    }
+}
+
+// Perceived brightness is roughly quadratic in duty.
+fn brightness_to_duty(brightness: f32) -> u16 {
+    let brightness = brightness.clamp(0.0, 1.0);
+    (brightness * brightness * PCA9685_MAX_VALUE as f32) as u16
+}
+
+// The output turns on at count 0 and off at `duty`.
+// 0 and PCA9685_MAX_VALUE use the full off / full on bits, which the counter can't express.
+fn pca9685_channel_registers(duty: u16) -> [u8; 4] {
+    match duty {
+        0 => [0, 0, 0, PCA9685_FULL],
+        PCA9685_MAX_VALUE => [0, PCA9685_FULL, 0, 0],
+        duty => [0, 0, (duty & 0xff) as u8, (duty >> 8) as u8],
+    }
+}
+
+pub struct PetalBoard<'a> {
+    // board peripherals
+    pub user_led: UserLed<'a>,
+    pub interface: Interface<'a>,
+    /// SW_1 to SW_7, see [`SWITCH_COUNT`].
+    pub switches: [Switch<'a>; SWITCH_COUNT],
+    pub knobs: Knobs<'a>,
+    pub leds: PetalLeds<'a>,
+    pub enc_a: Input<'a>,
+    pub enc_b: Input<'a>,
+    pub enc_click: Switch<'a>,
+    pub daisy_usb: DaisyUsb,
+    /// Hardware random number generator, see [`crate::audio::white_noise`].
+    pub rng: Rng<'a, RNG>,
+}
+
+/// Errors from [`PetalBoard::new`].
+#[derive(Debug, defmt::Format)]
+pub enum PetalError {
+    Audio(AudioError),
+    LedDriver(hal::i2c::Error),
+}
+
+impl<'a> PetalBoard<'a> {
+    pub async fn new(
+        p: PetalPeripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), PetalError> {
+        let usb_driver = crate::usb::init(p.usb_otg_fs, p.usb2_pins);
+        let (interface, buffers) = Interface::new(p.wm8731_pin, p.audio_peripherals, audio_config)
+            .await
+            .map_err(PetalError::Audio)?;
+        let leds = PetalLeds::new(p.i2c1, p.led_driver_pins).map_err(PetalError::LedDriver)?;
+
+        let mut adc = Adc::new(p.adc1);
+        adc.set_resolution(Resolution::BITS16);
+        adc.set_sample_time(SampleTime::CYCLES32_5);
+
+        // all switches close to GND
+        let switch = |pin: Input<'a>| Switch::new(pin, Level::Low);
+        let pins = p.petal_pins;
+        Ok((
+            Self {
+                user_led: UserLed::new(p.led_user_pin),
+                interface,
+                switches: [
+                    switch(Input::new(pins.SW_1, Pull::Up)),
+                    switch(Input::new(pins.SW_2, Pull::Up)),
+                    switch(Input::new(pins.SW_3, Pull::Up)),
+                    switch(Input::new(pins.SW_4, Pull::Up)),
+                    switch(Input::new(pins.SW_5, Pull::Up)),
+                    switch(Input::new(pins.SW_6, Pull::Up)),
+                    switch(Input::new(pins.SW_7, Pull::Up)),
+                ],
+                knobs: Knobs {
+                    adc,
+                    knob_1: pins.KNOB_1,
+                    knob_2: pins.KNOB_2,
+                    knob_3: pins.KNOB_3,
+                    knob_4: pins.KNOB_4,
+                    knob_5: pins.KNOB_5,
+                    knob_6: pins.KNOB_6,
+                    expression: pins.EXPRESSION,
+                },
+                leds,
+                enc_a: Input::new(pins.ENC_A, Pull::Up),
+                enc_b: Input::new(pins.ENC_B, Pull::Up),
+                enc_click: switch(Input::new(pins.ENC_CLICK, Pull::Up)),
+                daisy_usb: usb_driver,
+                rng: Rng::new(p.rng, Irqs),
+            },
+            buffers,
+        ))
+    }
+    /// Sample all switches and the encoder button once.
+    pub fn update_switches(&mut self) {
+        for switch in self.switches.iter_mut() {
+            switch.update();
+        }
+        self.enc_click.update();
+    }
+}
+
+/// Knobs in [`Knob::ALL`] order, SW_1 to SW_7 and the encoder button as switches 0 to 7,
+/// the ring LEDs as LEDs 0 to 7 and the footswitch LEDs as 8 to 11.
+/// [`BoardControls::update_leds`] only talks to the LED drivers when something changed.
+impl BoardControls for PetalBoard<'_> {
+    fn knob_count(&self) -> usize {
+        Knob::ALL.len()
+    }
+    fn knob(&mut self, index: usize) -> f32 {
+        self.knobs.read(Knob::ALL[index])
+    }
+    fn switch_count(&self) -> usize {
+        SWITCH_COUNT + 1
+    }
+    fn switch(&self, index: usize) -> &Switch<'_> {
+        match index {
+            SWITCH_COUNT => &self.enc_click,
+            index => &self.switches[index],
+        }
+    }
+    fn update_switches(&mut self) {
+        PetalBoard::update_switches(self);
+    }
+    fn led_count(&self) -> usize {
+        RING_LED_COUNT + FOOTSWITCH_LED_COUNT
+    }
+    fn set_led(&mut self, index: usize, color: Rgb) {
+        match index.checked_sub(RING_LED_COUNT) {
+            None => self.leds.set_ring(index, color),
+            Some(footswitch) => self
+                .leds
+                .set_footswitch(footswitch, color.r.max(color.g).max(color.b)),
+        }
+    }
+    fn update_leds(&mut self) {
+        if let Err(e) = self.leds.update_if_changed() {
+            warn!("LED driver: {}", e);
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! new_petal_p {
+    ($p:ident) => {
+        $crate::boards::petal::PetalPeripherals {
+            petal_pins: $crate::boards::petal::PetalPins {
+                SW_1: $p.PG11,
+                SW_2: $p.PB4,
+                SW_3: $p.PB5,
+                SW_4: $p.PB6,
+                SW_5: $p.PA0,
+                SW_6: $p.PD11,
+                SW_7: $p.PG10,
+                ENC_A: $p.PA2,
+                ENC_B: $p.PG9,
+                ENC_CLICK: $p.PB7,
+                EXPRESSION: $p.PC0,
+                KNOB_1: $p.PA3,
+                KNOB_2: $p.PA6,
+                KNOB_3: $p.PB1,
+                KNOB_4: $p.PC1,
+                KNOB_5: $p.PA7,
+                KNOB_6: $p.PC4,
+            },
+            led_driver_pins: $crate::boards::petal::LedDriverPins {
+                SCL: $p.PB8,
+                SDA: $p.PB9,
+            },
+            i2c1: $p.I2C1,
+            adc1: $p.ADC1,
+            led_user_pin: $p.PC7,
+            wm8731_pin: $crate::pins::WM8731Pins {
+                SCL: $p.PH4,
+                SDA: $p.PB11,
+                MCLK_A: $p.PE2,
+                SCK_A: $p.PE5,
+                FS_A: $p.PE4,
+                SD_A: $p.PE6,
+                SD_B: $p.PE3,
+            },
+            audio_peripherals: $crate::audio::Peripherals {
+                sai1: $p.SAI1,
+                i2c2: $p.I2C2,
+                dma1_ch1: $p.DMA1_CH1,
+                dma1_ch2: $p.DMA1_CH2,
+            },
+            usb2_pins: $crate::pins::USB2Pins {
+                DN: $p.PA11,
+                DP: $p.PA12,
+            },
+            usb_otg_fs: $p.USB_OTG_FS,
+            rng: $p.RNG,
+        }
+    };
+}
@@ -0,0 +1,51 @@
+//! Daisy Seed 2 DFM, the Seed with a PCM3060 codec instead of the WM8731.
+//!
+//! Pinout, SAI1 wiring and the codec's I2C bus (I2C2 on PH4/PB11) are the same as on the Seed 1.1,
+//! so [`crate::new_daisy_p!`] builds the peripherals for it as well. The QSPI flash is the same
+//! 8MB part (see [`crate::info::FLASH_SIZE`]). Following libDaisy's `daisy_seed.cpp`, the codec
+//! runs as slave with 24-bit left justified data, like the PCM3060 on the Patch SM.
+//! See: https://github.com/electro-smith/libDaisy/blob/master/src/daisy_seed.cpp
+//! ```ignore
+//! let p = hal::init(daisy_embassy::default_rcc());
+//! let (board, buffers) = Seed2DfmBoard::new(new_daisy_p!(p), Default::default()).await.unwrap();
+//! ```
+use crate::audio::{AudioBlockBuffers, AudioConfig, AudioError, Interface};
+use crate::board::{DaisyPeripherals, Irqs};
+use crate::pins::DaisyPins;
+use crate::{led::UserLed, usb::DaisyUsb};
+use embassy_stm32 as hal;
+use hal::peripherals::RNG;
+use hal::rng::Rng;
+
+pub struct Seed2DfmBoard<'a> {
+    pub daisy_pins: DaisyPins,
+
+    // board peripherals
+    pub user_led: UserLed<'a>,
+    pub interface: Interface<'a>,
+    pub daisy_usb: DaisyUsb,
+    /// Hardware random number generator, see [`crate::audio::white_noise`].
+    pub rng: Rng<'a, RNG>,
+}
+
+impl<'a> Seed2DfmBoard<'a> {
+    pub async fn new(
+        p: DaisyPeripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        let usb_driver = crate::usb::init(p.usb_otg_fs, p.usb2_pins);
+        let (interface, buffers) =
+            Interface::new_pcm3060_on_seed_pins(p.wm8731_pin, p.audio_peripherals, audio_config)
+                .await?;
+        Ok((
+            Self {
+                daisy_pins: p.daisy_pins,
+                user_led: UserLed::new(p.led_user_pin),
+                interface,
+                daisy_usb: usb_driver,
+                rng: Rng::new(p.rng, Irqs),
+            },
+            buffers,
+        ))
+    }
+}
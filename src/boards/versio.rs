@@ -0,0 +1,329 @@
+//! Noise Engineering Versio, a Eurorack platform built around a Daisy Seed.
+//!
+//! The Versio has seven knobs (each summed with a CV input), two three position switches,
+//! a tap button, a gate input, and four RGB LEDs on GPIOs. Audio goes through the Seed's own codec.
+//! The pin assignment follows libDaisy's `daisy_versio.cpp`.
+//! See: https://github.com/electro-smith/libDaisy/blob/master/src/daisy_versio.cpp
+use crate::audio::{self, AudioBlockBuffers, AudioConfig, AudioError, Interface};
+use crate::board::Irqs;
+use crate::boards::BoardControls;
+pub use crate::led::Rgb;
+use crate::led::RgbLed;
+use crate::pins::{LedUserPin, USB2Pins, WM8731Pins};
+use crate::switch::Switch;
+use crate::{led::UserLed, usb::DaisyUsb};
+use embassy_stm32 as hal;
+use hal::adc::{Adc, Resolution, SampleTime};
+use hal::gpio::{AnyPin, Input, Level, Output, Pin, Pull, Speed};
+use hal::peripherals::{ADC1, RNG, USB_OTG_FS};
+use hal::rng::Rng;
+
+// - types --------------------------------------------------------------------
+
+#[allow(non_snake_case)]
+pub struct VersioPins {
+    pub KNOB_0: hal::peripherals::PC4,   // SEED_PIN_21, ADC
+    pub KNOB_1: hal::peripherals::PA5,   // SEED_PIN_22, ADC
+    pub KNOB_2: hal::peripherals::PA2,   // SEED_PIN_28, ADC
+    pub KNOB_3: hal::peripherals::PC0,   // SEED_PIN_15, ADC
+    pub KNOB_4: hal::peripherals::PA3,   // SEED_PIN_16, ADC
+    pub KNOB_5: hal::peripherals::PB1,   // SEED_PIN_17, ADC
+    pub KNOB_6: hal::peripherals::PA6,   // SEED_PIN_19, ADC
+    pub SW_0_A: hal::peripherals::PC12,  // SEED_PIN_6
+    pub SW_0_B: hal::peripherals::PG10,  // SEED_PIN_7
+    pub SW_1_A: hal::peripherals::PB4,   // SEED_PIN_9
+    pub SW_1_B: hal::peripherals::PG11,  // SEED_PIN_8
+    pub TAP: hal::peripherals::PB15,     // SEED_PIN_30
+    pub GATE: hal::peripherals::PB14,    // SEED_PIN_29
+    pub LED_0_R: hal::peripherals::PB5,  // SEED_PIN_10
+    pub LED_0_G: hal::peripherals::PC9,  // SEED_PIN_3
+    pub LED_0_B: hal::peripherals::PC8,  // SEED_PIN_4
+    pub LED_1_R: hal::peripherals::PB9,  // SEED_PIN_12
+    pub LED_1_G: hal::peripherals::PB6,  // SEED_PIN_13
+    pub LED_1_B: hal::peripherals::PB8,  // SEED_PIN_11
+    pub LED_2_R: hal::peripherals::PA0,  // SEED_PIN_25
+    pub LED_2_G: hal::peripherals::PA1,  // SEED_PIN_24
+    pub LED_2_B: hal::peripherals::PB7,  // SEED_PIN_14
+    pub LED_3_R: hal::peripherals::PB12, // SEED_PIN_0
+    pub LED_3_G: hal::peripherals::PC11, // SEED_PIN_1
+    pub LED_3_B: hal::peripherals::PD2,  // SEED_PIN_5
+}
+
+pub struct VersioPeripherals {
+    pub versio_pins: VersioPins,
+    pub adc1: ADC1,
+    pub led_user_pin: LedUserPin,
+    pub wm8731_pin: WM8731Pins,
+    pub audio_peripherals: audio::Peripherals,
+    pub usb2_pins: USB2Pins,
+    pub usb_otg_fs: USB_OTG_FS,
+    pub rng: RNG,
+}
+
+pub const KNOB_COUNT: usize = 7;
+pub const LED_COUNT: usize = 4;
+
+/// The knobs, numbered as in libDaisy (`KNOB_0` to `KNOB_6`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Knob {
+    Zero,
+    One,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+}
+
+impl Knob {
+    /// In board order, the indices of [`BoardControls::knob`].
+    pub const ALL: [Knob; KNOB_COUNT] = [
+        Knob::Zero,
+        Knob::One,
+        Knob::Two,
+        Knob::Three,
+        Knob::Four,
+        Knob::Five,
+        Knob::Six,
+    ];
+}
+
+/// Knobs (plus their CV inputs), read from ADC1.
+pub struct Knobs<'a> {
+    adc: Adc<'a, ADC1>,
+    knob_0: hal::peripherals::PC4,
+    knob_1: hal::peripherals::PA5,
+    knob_2: hal::peripherals::PA2,
+    knob_3: hal::peripherals::PC0,
+    knob_4: hal::peripherals::PA3,
+    knob_5: hal::peripherals::PB1,
+    knob_6: hal::peripherals::PA6,
+}
+
+impl<'a> Knobs<'a> {
+    const MAX_VALUE: f32 = u16::MAX as f32;
+
+    /// Position of the knob plus its CV, from 0.0 to 1.0.
+    pub fn read(&mut self, knob: Knob) -> f32 {
+        let raw = match knob {
+            Knob::Zero => self.adc.read(&mut self.knob_0),
+            Knob::One => self.adc.read(&mut self.knob_1),
+            Knob::Two => self.adc.read(&mut self.knob_2),
+            Knob::Three => self.adc.read(&mut self.knob_3),
+            Knob::Four => self.adc.read(&mut self.knob_4),
+            Knob::Five => self.adc.read(&mut self.knob_5),
+            Knob::Six => self.adc.read(&mut self.knob_6),
+        };
+        raw as f32 / Self::MAX_VALUE
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum SwitchPosition {
+    Left,
+    Center,
+    Right,
+}
+
+/// Three position switch with a pin to GND on each side.
+pub struct ThreePositionSwitch<'a> {
+    a: Input<'a>,
+    b: Input<'a>,
+}
+
+impl<'a> ThreePositionSwitch<'a> {
+    pub fn position(&self) -> SwitchPosition {
+        if self.a.is_low() {
+            SwitchPosition::Left
+        } else if self.b.is_low() {
+            SwitchPosition::Right
+        } else {
+            SwitchPosition::Center
+        }
+    }
+}
+
+pub struct VersioBoard<'a> {
+    // board peripherals
+    pub user_led: UserLed<'a>,
+    pub interface: Interface<'a>,
+    pub knobs: Knobs<'a>,
+    pub switches: [ThreePositionSwitch<'a>; 2],
+    pub tap: Switch<'a>,
+    gate: Input<'a>,
+    /// Call [`RgbLed::update`] on each at a steady rate.
+    pub leds: [RgbLed<'a>; LED_COUNT],
+    pub daisy_usb: DaisyUsb,
+    /// Hardware random number generator, see [`crate::audio::white_noise`].
+    pub rng: Rng<'a, RNG>,
+}
+
+impl<'a> VersioBoard<'a> {
+    pub async fn new(
+        p: VersioPeripherals,
+        audio_config: AudioConfig,
+    ) -> Result<(Self, AudioBlockBuffers), AudioError> {
+        let usb_driver = crate::usb::init(p.usb_otg_fs, p.usb2_pins);
+        let (interface, buffers) =
+            Interface::new(p.wm8731_pin, p.audio_peripherals, audio_config).await?;
+
+        let mut adc = Adc::new(p.adc1);
+        adc.set_resolution(Resolution::BITS16);
+        adc.set_sample_time(SampleTime::CYCLES32_5);
+
+        let pins = p.versio_pins;
+        // the LEDs are wired between 3.3V and the pins
+        let led_pin = |pin: AnyPin| Output::new(pin, Level::High, Speed::Low);
+        Ok((
+            Self {
+                user_led: UserLed::new(p.led_user_pin),
+                interface,
+                knobs: Knobs {
+                    adc,
+                    knob_0: pins.KNOB_0,
+                    knob_1: pins.KNOB_1,
+                    knob_2: pins.KNOB_2,
+                    knob_3: pins.KNOB_3,
+                    knob_4: pins.KNOB_4,
+                    knob_5: pins.KNOB_5,
+                    knob_6: pins.KNOB_6,
+                },
+                switches: [
+                    ThreePositionSwitch {
+                        a: Input::new(pins.SW_0_A, Pull::Up),
+                        b: Input::new(pins.SW_0_B, Pull::Up),
+                    },
+                    ThreePositionSwitch {
+                        a: Input::new(pins.SW_1_A, Pull::Up),
+                        b: Input::new(pins.SW_1_B, Pull::Up),
+                    },
+                ],
+                tap: Switch::new(Input::new(pins.TAP, Pull::Up), Level::Low),
+                gate: Input::new(pins.GATE, Pull::None),
+                leds: [
+                    RgbLed::new(
+                        led_pin(pins.LED_0_R.degrade()),
+                        led_pin(pins.LED_0_G.degrade()),
+                        led_pin(pins.LED_0_B.degrade()),
+                        true,
+                    ),
+                    RgbLed::new(
+                        led_pin(pins.LED_1_R.degrade()),
+                        led_pin(pins.LED_1_G.degrade()),
+                        led_pin(pins.LED_1_B.degrade()),
+                        true,
+                    ),
+                    RgbLed::new(
+                        led_pin(pins.LED_2_R.degrade()),
+                        led_pin(pins.LED_2_G.degrade()),
+                        led_pin(pins.LED_2_B.degrade()),
+                        true,
+                    ),
+                    RgbLed::new(
+                        led_pin(pins.LED_3_R.degrade()),
+                        led_pin(pins.LED_3_G.degrade()),
+                        led_pin(pins.LED_3_B.degrade()),
+                        true,
+                    ),
+                ],
+                daisy_usb: usb_driver,
+                rng: Rng::new(p.rng, Irqs),
+            },
+            buffers,
+        ))
+    }
+    /// Gate input state. The input stage inverts, so the pin level is inverted here.
+    pub fn gate(&self) -> bool {
+        self.gate.is_low()
+    }
+}
+
+/// Knobs 0 to 6, the tap button as switch 0, and the four RGB LEDs.
+/// [`BoardControls::update_leds`] is one step of the software dimming.
+impl BoardControls for VersioBoard<'_> {
+    fn knob_count(&self) -> usize {
+        KNOB_COUNT
+    }
+    fn knob(&mut self, index: usize) -> f32 {
+        self.knobs.read(Knob::ALL[index])
+    }
+    fn switch_count(&self) -> usize {
+        1
+    }
+    fn switch(&self, index: usize) -> &Switch<'_> {
+        assert!(index == 0, "switch index out of range");
+        &self.tap
+    }
+    fn update_switches(&mut self) {
+        self.tap.update();
+    }
+    fn led_count(&self) -> usize {
+        LED_COUNT
+    }
+    fn set_led(&mut self, index: usize, color: Rgb) {
+        self.leds[index].set(color);
+    }
+    fn update_leds(&mut self) {
+        for led in self.leds.iter_mut() {
+            led.update();
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! new_versio_p {
+    ($p:ident) => {
+        $crate::boards::versio::VersioPeripherals {
+            versio_pins: $crate::boards::versio::VersioPins {
+                KNOB_0: $p.PC4,
+                KNOB_1: $p.PA5,
+                KNOB_2: $p.PA2,
+                KNOB_3: $p.PC0,
+                KNOB_4: $p.PA3,
+                KNOB_5: $p.PB1,
+                KNOB_6: $p.PA6,
+                SW_0_A: $p.PC12,
+                SW_0_B: $p.PG10,
+                SW_1_A: $p.PB4,
+                SW_1_B: $p.PG11,
+                TAP: $p.PB15,
+                GATE: $p.PB14,
+                LED_0_R: $p.PB5,
+                LED_0_G: $p.PC9,
+                LED_0_B: $p.PC8,
+                LED_1_R: $p.PB9,
+                LED_1_G: $p.PB6,
+                LED_1_B: $p.PB8,
+                LED_2_R: $p.PA0,
+                LED_2_G: $p.PA1,
+                LED_2_B: $p.PB7,
+                LED_3_R: $p.PB12,
+                LED_3_G: $p.PC11,
+                LED_3_B: $p.PD2,
+            },
+            adc1: $p.ADC1,
+            led_user_pin: $p.PC7,
+            wm8731_pin: $crate::pins::WM8731Pins {
+                SCL: $p.PH4,
+                SDA: $p.PB11,
+                MCLK_A: $p.PE2,
+                SCK_A: $p.PE5,
+                FS_A: $p.PE4,
+                SD_A: $p.PE6,
+                SD_B: $p.PE3,
+            },
+            audio_peripherals: $crate::audio::Peripherals {
+                sai1: $p.SAI1,
+                i2c2: $p.I2C2,
+                dma1_ch1: $p.DMA1_CH1,
+                dma1_ch2: $p.DMA1_CH2,
+            },
+            usb2_pins: $crate::pins::USB2Pins {
+                DN: $p.PA11,
+                DP: $p.PA12,
+            },
+            usb_otg_fs: $p.USB_OTG_FS,
+            rng: $p.RNG,
+        }
+    };
+}
@@ -0,0 +1,205 @@
+//! Named, scaled and smoothed control values, for panels with more than a couple of knobs.
+//!
+//! [`named_controls!`](crate::named_controls) declares a struct with an `f32` field per control
+//! and the [`ControlMap`] of each. Readings in 0.0..=1.0 (e.g. from `Knobs::read` or
+//! [`BoardControls::knob`](crate::boards::BoardControls::knob)) are given in field order:
+//! ```ignore
+//! daisy_embassy::named_controls! {
+//!     pub struct Controls {
+//!         cutoff: ControlMap::exponential(20.0, 20_000.0).smoothed(0.9),
+//!         resonance: ControlMap::linear(0.0, 0.95),
+//!         drive: ControlMap::linear(1.0, 10.0),
+//!     }
+//! }
+//!
+//! let mut controls = Controls::new();
+//! // once per control cycle
+//! controls.scan(|i| knobs.read(Knob::ALL[i]));
+//! filter.set(controls.cutoff, controls.resonance);
+//! ```
+
+/// Response of a control over its travel.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Curve {
+    Linear,
+    /// Equal ratios per distance, for frequencies and times. `min` has to be above 0.
+    Exponential,
+}
+
+/// How a reading in 0.0..=1.0 becomes a control value.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ControlMap {
+    pub min: f32,
+    pub max: f32,
+    pub curve: Curve,
+    /// One-pole smoothing per update, 0.0 (none) to just below 1.0 (very slow).
+    /// The time constant is `1 / (1 - smoothing)` updates.
+    pub smoothing: f32,
+}
+
+impl ControlMap {
+    pub const fn linear(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            curve: Curve::Linear,
+            smoothing: 0.0,
+        }
+    }
+    pub const fn exponential(min: f32, max: f32) -> Self {
+        Self {
+            min,
+            max,
+            curve: Curve::Exponential,
+            smoothing: 0.0,
+        }
+    }
+    pub const fn smoothed(self, smoothing: f32) -> Self {
+        Self { smoothing, ..self }
+    }
+    /// The control value of `reading`, without smoothing. Readings outside 0.0..=1.0 are clamped.
+    pub fn scale(&self, reading: f32) -> f32 {
+        let x = reading.clamp(0.0, 1.0);
+        match self.curve {
+            Curve::Linear => self.min + (self.max - self.min) * x,
+            Curve::Exponential => self.min * libm::powf(self.max / self.min, x),
+        }
+    }
+    /// The next smoothed value, from the `previous` one. The first update (`primed` false)
+    /// takes the reading as is, so controls don't glide in from 0 after start up.
+    pub fn step(&self, previous: f32, reading: f32, primed: bool) -> f32 {
+        let target = self.scale(reading);
+        if primed {
+            target + (previous - target) * self.smoothing
+        } else {
+            target
+        }
+    }
+}
+
+/// Declare a struct of named control values, see the [`controls`](crate::controls) module.
+///
+/// Besides the fields, the struct gets `COUNT`, `MAPS`, a `const fn new()` with all values 0.0,
+/// `update(&[f32])` from readings in field order and `scan(|index| reading)`. It also has a
+/// private `__primed` field, so that name can't be used for a control.
+#[macro_export]
+macro_rules! named_controls {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field:ident : $map:expr),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(pub $field: f32,)+
+            __primed: bool,
+        }
+
+        impl $name {
+            pub const COUNT: usize = [$(stringify!($field)),+].len();
+            pub const MAPS: &'static [$crate::controls::ControlMap] = &[$($map),+];
+
+            pub const fn new() -> Self {
+                Self {
+                    $($field: 0.0,)+
+                    __primed: false,
+                }
+            }
+            /// Update from readings in 0.0..=1.0, in field order. Missing readings keep their values.
+            pub fn update(&mut self, readings: &[f32]) {
+                let mut inputs = Self::MAPS.iter().zip(readings);
+                $(
+                    if let Some((map, reading)) = inputs.next() {
+                        self.$field = map.step(self.$field, *reading, self.__primed);
+                    }
+                )+
+                self.__primed = true;
+            }
+            /// Update from `read(index)`, called once per field in field order.
+            pub fn scan(&mut self, mut read: impl FnMut(usize) -> f32) {
+                let mut readings = [0.0; Self::COUNT];
+                for (index, reading) in readings.iter_mut().enumerate() {
+                    *reading = read(index);
+                }
+                self.update(&readings);
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::named_controls! {
+        struct Controls {
+            cutoff: ControlMap::exponential(20.0, 20_000.0).smoothed(0.5),
+            mix: ControlMap::linear(-1.0, 1.0),
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() <= 1e-3 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn linear_scale() {
+        let map = ControlMap::linear(-1.0, 3.0);
+        assert_eq!(map.scale(0.0), -1.0);
+        assert_eq!(map.scale(0.5), 1.0);
+        assert_eq!(map.scale(1.0), 3.0);
+        assert_eq!(map.scale(-0.5), -1.0);
+        assert_eq!(map.scale(2.0), 3.0);
+    }
+
+    #[test]
+    fn exponential_scale() {
+        let map = ControlMap::exponential(20.0, 20_000.0);
+        assert!(close(map.scale(0.0), 20.0));
+        assert!(close(map.scale(1.0 / 3.0), 200.0));
+        assert!(close(map.scale(2.0 / 3.0), 2_000.0));
+        assert!(close(map.scale(1.0), 20_000.0));
+    }
+
+    #[test]
+    fn step_smooths_once_primed() {
+        let map = ControlMap::linear(0.0, 10.0).smoothed(0.75);
+        assert_eq!(map.step(0.0, 1.0, false), 10.0);
+        assert_eq!(map.step(0.0, 1.0, true), 2.5);
+        assert_eq!(map.step(2.5, 1.0, true), 4.375);
+        assert_eq!(ControlMap::linear(0.0, 10.0).step(3.0, 0.5, true), 5.0);
+    }
+
+    #[test]
+    fn first_update_primes() {
+        let mut controls = Controls::new();
+        assert_eq!(Controls::COUNT, 2);
+        controls.update(&[1.0, 0.75]);
+        assert!(close(controls.cutoff, 20_000.0));
+        assert_eq!(controls.mix, 0.5);
+        controls.update(&[0.0, 0.25]);
+        assert!(close(controls.cutoff, 10_010.0));
+        assert_eq!(controls.mix, -0.5);
+    }
+
+    #[test]
+    fn scan_reads_in_field_order() {
+        let mut controls = Controls::default();
+        controls.scan(|index| [0.0, 1.0][index]);
+        assert!(close(controls.cutoff, 20.0));
+        assert_eq!(controls.mix, 1.0);
+        // Missing readings keep their values.
+        controls.update(&[1.0]);
+        assert!(close(controls.cutoff, 10_010.0));
+        assert_eq!(controls.mix, 1.0);
+    }
+}
@@ -0,0 +1,99 @@
+//! CRC-32 on the CRC peripheral, for integrity checks of flash or SD card data.
+//!
+//! ```ignore
+//! let mut crc = Crc32::new(p.CRC);
+//! assert_eq!(crc.crc32(b"123456789"), 0xCBF4_3926);
+//! // or in pieces, e.g. sector by sector
+//! crc.reset();
+//! crc.update(first);
+//! crc.update(second);
+//! let checksum = crc.finish();
+//! ```
+//! The checksum is the common CRC-32 of zip, PNG and Ethernet (CRC-32/ISO-HDLC).
+#[cfg(feature = "hal")]
+use embassy_stm32 as hal;
+#[cfg(feature = "hal")]
+use hal::crc::{Config, Crc, InputReverseConfig, PolySize};
+#[cfg(feature = "hal")]
+use hal::peripherals::CRC;
+
+#[cfg(feature = "hal")]
+const POLY: u32 = 0x04C1_1DB7;
+// reflected polynomial for the bitwise implementation
+const POLY_REFLECTED: u32 = 0xEDB8_8320;
+const INIT: u32 = 0xFFFF_FFFF;
+const XOR_OUT: u32 = 0xFFFF_FFFF;
+
+#[cfg(feature = "hal")]
+pub struct Crc32<'a> {
+    crc: Crc<'a>,
+}
+
+#[cfg(feature = "hal")]
+impl<'a> Crc32<'a> {
+    pub fn new(crc: CRC) -> Self {
+        // reflected input and output; the final XOR isn't done by the hardware, see finish()
+        let config = Config::new(
+            InputReverseConfig::Byte,
+            true,
+            PolySize::Width32,
+            INIT,
+            POLY,
+        )
+        .unwrap();
+        Self {
+            crc: Crc::new(crc, config),
+        }
+    }
+    /// Start a new checksum.
+    pub fn reset(&mut self) {
+        self.crc.reset();
+    }
+    /// Feed the next piece of data.
+    pub fn update(&mut self, data: &[u8]) {
+        self.crc.feed_bytes(data);
+    }
+    /// Checksum of the data fed since the last [`Crc32::reset`]. More data can be fed afterwards.
+    pub fn finish(&self) -> u32 {
+        self.crc.read() ^ XOR_OUT
+    }
+    /// Checksum of `data` alone.
+    pub fn crc32(&mut self, data: &[u8]) -> u32 {
+        self.reset();
+        self.update(data);
+        self.finish()
+    }
+}
+
+/// The same checksum as [`Crc32`] in software, e.g. while the peripheral is used elsewhere.
+pub fn crc32_software(data: &[u8]) -> u32 {
+    let mut crc = INIT;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (POLY_REFLECTED & mask);
+        }
+    }
+    crc ^ XOR_OUT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32_software(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn known_vectors() {
+        assert_eq!(crc32_software(b""), 0);
+        assert_eq!(crc32_software(b"a"), 0xE8B7_BE43);
+        assert_eq!(
+            crc32_software(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Control voltage input scaling.
+//!
+//! Raw ADC readings differ from board to board because of resistor tolerances.
+//! For pitch CV (1V/oct) that is audible, so record readings at two known voltages
+//! and scale with the resulting [`Calibration`]:
+//! ```ignore
+//! let mut calibrator = Calibrator::new(1.0, 3.0);
+//! // patch 1V into CV_1, press the button
+//! calibrator.record(average(|| adc.read(&mut cv_1), 64));
+//! // patch 3V into CV_1, press the button
+//! calibrator.record(average(|| adc.read(&mut cv_1), 64));
+//! let calibration = calibrator.finish().unwrap();
+//! let volts = calibration.adc_to_volts(adc.read(&mut cv_1));
+//! ```
+//! Store [`Calibration::to_bytes`] in non-volatile memory to keep it across resets.
+
+/// Linear mapping from raw 16-bit ADC readings to volts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Calibration {
+    /// volts per ADC step
+    pub scale: f32,
+    /// volts at raw value 0
+    pub offset: f32,
+}
+
+impl Calibration {
+    pub const SERIALIZED_LENGTH: usize = 8;
+
+    /// Nominal scaling of the Patch SM's bipolar CV inputs: -5V..5V, inverted by the input stage.
+    pub const PATCH_SM_NOMINAL: Calibration = Calibration {
+        scale: -10.0 / u16::MAX as f32,
+        offset: 5.0,
+    };
+
+    /// Calibration through two (raw reading, volts) points.
+    /// `None` if both readings are the same.
+    pub fn from_points(low: (u16, f32), high: (u16, f32)) -> Option<Self> {
+        let (raw_low, volts_low) = low;
+        let (raw_high, volts_high) = high;
+        if raw_low == raw_high {
+            return None;
+        }
+        let scale = (volts_high - volts_low) / (raw_high as f32 - raw_low as f32);
+        Some(Self {
+            scale,
+            offset: volts_low - raw_low as f32 * scale,
+        })
+    }
+    pub fn adc_to_volts(&self, raw: u16) -> f32 {
+        raw as f32 * self.scale + self.offset
+    }
+    /// Little endian `scale`, then `offset`.
+    pub fn to_bytes(&self) -> [u8; Self::SERIALIZED_LENGTH] {
+        let mut bytes = [0; Self::SERIALIZED_LENGTH];
+        bytes[..4].copy_from_slice(&self.scale.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.offset.to_le_bytes());
+        bytes
+    }
+    /// `None` for erased flash (all 0xff) or values that aren't finite.
+    pub fn from_bytes(bytes: &[u8; Self::SERIALIZED_LENGTH]) -> Option<Self> {
+        let scale = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        let offset = f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        (scale.is_finite() && offset.is_finite() && scale != 0.0).then_some(Self { scale, offset })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalibrationStep {
+    /// Apply this voltage to the input, then call [`Calibrator::record`].
+    Apply(f32),
+    Done,
+}
+
+/// Guides through a two point calibration.
+pub struct Calibrator {
+    volts: [f32; 2],
+    readings: [u16; 2],
+    recorded: usize,
+}
+
+impl Calibrator {
+    /// Calibrate at `low_volts` and `high_volts`. 1V and 3V suit a 1V/oct input.
+    pub fn new(low_volts: f32, high_volts: f32) -> Self {
+        Self {
+            volts: [low_volts, high_volts],
+            readings: [0; 2],
+            recorded: 0,
+        }
+    }
+    /// What the user has to do next.
+    pub fn step(&self) -> CalibrationStep {
+        match self.volts.get(self.recorded) {
+            Some(volts) => CalibrationStep::Apply(*volts),
+            None => CalibrationStep::Done,
+        }
+    }
+    /// Record the reading for the current step.
+    pub fn record(&mut self, raw: u16) {
+        if let Some(reading) = self.readings.get_mut(self.recorded) {
+            *reading = raw;
+            self.recorded += 1;
+        }
+    }
+    /// Start over.
+    pub fn reset(&mut self) {
+        self.recorded = 0;
+    }
+    /// The calibration, once both points are recorded and differ.
+    pub fn finish(&self) -> Option<Calibration> {
+        if self.step() != CalibrationStep::Done {
+            return None;
+        }
+        Calibration::from_points(
+            (self.readings[0], self.volts[0]),
+            (self.readings[1], self.volts[1]),
+        )
+    }
+}
+
+/// Average `n` readings, to take the noise out of calibration points.
+pub fn average(mut read: impl FnMut() -> u16, n: u32) -> u16 {
+    let n = n.max(1);
+    let sum: u32 = (0..n).map(|_| read() as u32).sum();
+    (sum / n) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_point_calibration() {
+        let mut calibrator = Calibrator::new(1.0, 3.0);
+        assert_eq!(calibrator.step(), CalibrationStep::Apply(1.0));
+        calibrator.record(30000);
+        assert_eq!(calibrator.step(), CalibrationStep::Apply(3.0));
+        assert!(calibrator.finish().is_none());
+        calibrator.record(20000);
+        assert_eq!(calibrator.step(), CalibrationStep::Done);
+        let calibration = calibrator.finish().unwrap();
+        assert!((calibration.adc_to_volts(30000) - 1.0).abs() < 1e-4);
+        assert!((calibration.adc_to_volts(20000) - 3.0).abs() < 1e-4);
+        assert!((calibration.adc_to_volts(25000) - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn serialization() {
+        let calibration = Calibration::PATCH_SM_NOMINAL;
+        assert_eq!(
+            Calibration::from_bytes(&calibration.to_bytes()),
+            Some(calibration)
+        );
+        // erased flash
+        assert_eq!(
+            Calibration::from_bytes(&[0xff; Calibration::SERIALIZED_LENGTH]),
+            None
+        );
+        assert!((calibration.adc_to_volts(0) - 5.0).abs() < 1e-4);
+        assert!((calibration.adc_to_volts(u16::MAX) + 5.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn averaging() {
+        assert_eq!(average(|| 7, 10), 7);
+    }
+}
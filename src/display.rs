@@ -0,0 +1,68 @@
+//! SSD1306/SSD1309 OLED of the Daisy Patch and Daisy Field, behind the `display` feature.
+//!
+//! Both panels drive the display over SPI1, write only, so the MISO pin serves as D/C:
+//!
+//! | signal | seed pin      | MCU pin |
+//! |--------|---------------|---------|
+//! | CS     | `SEED_PIN_7`  | PG10    |
+//! | SCK    | `SEED_PIN_8`  | PG11    |
+//! | D/C    | `SEED_PIN_9`  | PB4     |
+//! | MOSI   | `SEED_PIN_10` | PB5     |
+//! | RESET  | `SEED_PIN_30` | PB15    |
+//!
+//! The returned interface goes straight into the `ssd1306` crate, and from there to `embedded-graphics`:
+//! ```ignore
+//! let interface = display::oled(p.SPI1, oled_pins).await;
+//! let mut oled = Ssd1306::new(interface, DisplaySize128x64, DisplayRotation::Rotate0)
+//!     .into_buffered_graphics_mode();
+//! oled.init().unwrap();
+//! ```
+//! Neither panel puts its display on the codec's I2C bus, so there is nothing to share with the
+//! audio interface. RESET is `SEED_PIN_30`, the Seed's USB1 D+ on the external USB pins, so
+//! [`usb::init_hs`](crate::usb::init_hs) can't be used at the same time. The on-board port is USB2
+//! (PA11/PA12) and isn't affected.
+use crate::pins::{SeedPin10, SeedPin30, SeedPin7, SeedPin8, SeedPin9};
+use display_interface_spi::SPIInterface;
+use embassy_stm32 as hal;
+use embassy_time::Timer;
+use embedded_hal_bus::spi::{ExclusiveDevice, NoDelay};
+use hal::gpio::{Level, Output, Speed};
+use hal::peripherals::SPI1;
+use hal::spi::{Config, Spi};
+use hal::time::Hertz;
+
+/// The SSD1309 is specified up to 10MHz.
+const SPI_FREQUENCY: Hertz = Hertz::mhz(8);
+
+#[allow(non_snake_case)]
+pub struct OledPins {
+    pub CS: SeedPin7,
+    pub SCK: SeedPin8,
+    pub DC: SeedPin9,
+    pub MOSI: SeedPin10,
+    pub RESET: SeedPin30,
+}
+
+/// `display-interface` over SPI1, for `ssd1306::Ssd1306::new`.
+pub type DisplayInterface<'a> =
+    SPIInterface<ExclusiveDevice<Spi<'a, hal::mode::Blocking>, Output<'a>, NoDelay>, Output<'a>>;
+
+/// Reset the display and set up SPI1 for it.
+pub async fn oled<'a>(spi1: SPI1, pins: OledPins) -> DisplayInterface<'a> {
+    let mut reset = Output::new(pins.RESET, Level::Low, Speed::Low);
+    // at least 3us low, then a few more until the controller is ready
+    Timer::after_micros(10).await;
+    reset.set_high();
+    Timer::after_micros(10).await;
+    // dropping the Output would let RESET float
+    core::mem::forget(reset);
+
+    let mut config = Config::default();
+    config.frequency = SPI_FREQUENCY;
+    let spi = Spi::new_blocking_txonly(spi1, pins.SCK, pins.MOSI, config);
+    let cs = Output::new(pins.CS, Level::High, Speed::VeryHigh);
+    let dc = Output::new(pins.DC, Level::Low, Speed::VeryHigh);
+    // the CS pin is infallible
+    let device = ExclusiveDevice::new_no_delay(spi, cs).unwrap();
+    SPIInterface::new(device, dc)
+}
@@ -0,0 +1,73 @@
+//! Gate and trigger outputs for sequencers and clock outputs.
+//!
+//! ```ignore
+//! let mut gate = GateOutput::new(board.gate_out_1, false);
+//! loop {
+//!     clock.await_tick().await;
+//!     gate.trigger(Duration::from_millis(5));
+//!     // ...
+//!     gate.update();
+//! }
+//! ```
+use embassy_stm32 as hal;
+use embassy_time::{Duration, Instant, Timer};
+use hal::gpio::Output;
+
+pub struct GateOutput<'a> {
+    pin: Output<'a>,
+    // the output stage inverts
+    inverted: bool,
+    pulse_end: Option<Instant>,
+}
+
+impl<'a> GateOutput<'a> {
+    /// Starts low.
+    pub fn new(pin: Output<'a>, inverted: bool) -> Self {
+        let mut gate = Self {
+            pin,
+            inverted,
+            pulse_end: None,
+        };
+        gate.write(false);
+        gate
+    }
+    /// Sustained gate. Cancels a running trigger pulse.
+    pub fn set_gate(&mut self, high: bool) {
+        self.pulse_end = None;
+        self.write(high);
+    }
+    /// Go high for `width` without blocking. The pulse is ended by [`GateOutput::update`] or
+    /// [`GateOutput::wait_pulse_end`], so its width is accurate to the rate these are called at.
+    /// A trigger during a pulse restarts it.
+    pub fn trigger(&mut self, width: Duration) {
+        self.pulse_end = Some(Instant::now() + width);
+        self.write(true);
+    }
+    /// End the trigger pulse once it's due. Call at a steady rate, e.g. every millisecond.
+    pub fn update(&mut self) {
+        if let Some(end) = self.pulse_end {
+            if Instant::now() >= end {
+                self.set_gate(false);
+            }
+        }
+    }
+    /// Wait for the running trigger pulse to end and end it exactly on time.
+    /// Returns immediately if no pulse is running.
+    pub async fn wait_pulse_end(&mut self) {
+        if let Some(end) = self.pulse_end {
+            Timer::at(end).await;
+            self.set_gate(false);
+        }
+    }
+    /// Whether the gate (or a trigger pulse) is high.
+    pub fn is_high(&self) -> bool {
+        self.pin.is_set_high() != self.inverted
+    }
+    fn write(&mut self, high: bool) {
+        if high != self.inverted {
+            self.pin.set_high();
+        } else {
+            self.pin.set_low();
+        }
+    }
+}
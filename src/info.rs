@@ -0,0 +1,69 @@
+//! What this binary was built for, to log at boot:
+//! ```ignore
+//! defmt::info!("{}", daisy_embassy::board_info());
+//! ```
+//! The codec and SAI fields are filled in once the audio [`Interface`](crate::audio::Interface) is created.
+use crate::audio::{self, Codec};
+
+/// External QSPI flash on the Daisy Seed, Seed 2 DFM and Patch SM (IS25LP064A).
+pub const FLASH_SIZE: usize = crate::memory::QSPI_SIZE;
+
+#[derive(Debug, defmt::Format)]
+pub struct BoardInfo {
+    pub crate_version: &'static str,
+    /// Board modules compiled in. [`crate::DaisyBoard`] (Daisy Seed) is always available.
+    pub patch_sm: bool,
+    pub petal: bool,
+    pub seed_2_dfm: bool,
+    pub versio: bool,
+    pub flash_size: usize,
+    /// `None` until an audio interface has been created.
+    pub codec: Option<CodecInfo>,
+    pub sai: Option<SaiInfo>,
+}
+
+#[derive(Debug, defmt::Format)]
+pub struct CodecInfo {
+    pub codec: Codec,
+    pub name: &'static str,
+    pub i2c_address: u8,
+}
+
+#[derive(Debug, defmt::Format)]
+pub struct SaiInfo {
+    pub sample_rate: u32,
+    /// Samples per channel in a block.
+    pub block_length: usize,
+    pub data_bits: u8,
+    pub format: &'static str,
+}
+
+pub fn board_info() -> BoardInfo {
+    let codec = audio::active_codec().map(|(codec, i2c_address)| CodecInfo {
+        codec,
+        name: match codec {
+            Codec::Wm8731 => "WM8731",
+            Codec::Pcm3060 => "PCM3060",
+        },
+        i2c_address,
+    });
+    let sai = codec.as_ref().map(|_| SaiInfo {
+        sample_rate: audio::active_sample_rate(),
+        block_length: audio::BLOCK_LENGTH,
+        data_bits: 24,
+        format: match audio::active_sai_role() {
+            audio::SaiRole::Master => "SAI1, block A master rx, block B slave tx, left justified",
+            audio::SaiRole::Slave => "SAI1, codec clock master, block B sync tx, left justified",
+        },
+    });
+    BoardInfo {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        patch_sm: cfg!(feature = "patch_sm"),
+        petal: cfg!(feature = "petal"),
+        seed_2_dfm: cfg!(feature = "seed_2_dfm"),
+        versio: cfg!(feature = "versio"),
+        flash_size: FLASH_SIZE,
+        codec,
+        sai,
+    }
+}
@@ -1,5 +1,8 @@
 use embassy_stm32 as hal;
 use hal::gpio::{self, Speed};
+use hal::spi::{self, MosiPin, Spi, TxDma};
+use hal::time::Hertz;
+use hal::Peripheral;
 pub struct UserLed<'a>(gpio::Output<'a>);
 
 impl<'a> UserLed<'a> {
@@ -13,3 +16,186 @@ impl<'a> UserLed<'a> {
         self.0.set_low();
     }
 }
+
+/// Color of an RGB LED, each component from 0.0 to 1.0.
+#[derive(Clone, Copy, PartialEq, Debug, defmt::Format)]
+pub struct Rgb {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+}
+
+impl Rgb {
+    pub const OFF: Rgb = Rgb::new(0.0, 0.0, 0.0);
+
+    pub const fn new(r: f32, g: f32, b: f32) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// RGB LED on three GPIOs, dimmed in software.
+///
+/// Each call to [`RgbLed::update`] outputs one step of a first order delta-sigma modulation
+/// of the brightness. Call it at a steady rate of a few kHz or more (e.g. once per audio block)
+/// to avoid visible flicker at low brightness.
+pub struct RgbLed<'a> {
+    pins: [gpio::Output<'a>; 3],
+    brightness: [f32; 3],
+    error: [f32; 3],
+    // LED on with the pin low
+    inverted: bool,
+}
+
+impl<'a> RgbLed<'a> {
+    pub fn new(
+        r: gpio::Output<'a>,
+        g: gpio::Output<'a>,
+        b: gpio::Output<'a>,
+        inverted: bool,
+    ) -> Self {
+        Self {
+            pins: [r, g, b],
+            brightness: [0.0; 3],
+            error: [0.0; 3],
+            inverted,
+        }
+    }
+    pub fn set(&mut self, color: Rgb) {
+        self.brightness = [color.r, color.g, color.b].map(|c| c.clamp(0.0, 1.0));
+    }
+    pub fn update(&mut self) {
+        for ((pin, brightness), error) in self
+            .pins
+            .iter_mut()
+            .zip(self.brightness)
+            .zip(self.error.iter_mut())
+        {
+            *error += brightness;
+            let on = *error >= 1.0;
+            if on {
+                *error -= 1.0;
+            }
+            pin.set_level((on != self.inverted).into());
+        }
+    }
+}
+
+// Each WS2812 bit is sent as 4 SPI bits: 1000 for a 0 and 1100 for a 1.
+const SPI_BITS_PER_BIT: usize = 4;
+// 24 bits per LED
+const BYTES_PER_LED: usize = 24 * SPI_BITS_PER_BIT / 8;
+const WS2812_SPI_FREQUENCY: Hertz = Hertz(3_200_000);
+// Low time that latches the colors, 280us for current WS2812B (older parts need 50us).
+// 120 bytes at 3.125MHz are 307us.
+const RESET_BYTES: usize = 120;
+
+/// Chain of `N` WS2812 (NeoPixel) LEDs, driven by the MOSI line of an SPI with DMA.
+///
+/// The waveform is generated by the SPI, so [`Ws2812::flush`] doesn't block and is not disturbed
+/// by interrupts. Any SPI MOSI pin works, e.g. SPI1 on `SEED_PIN_10` (PB5):
+/// ```ignore
+/// #[link_section = ".sram1_bss"]
+/// static mut LED_BUFFER: Ws2812Buffer<8> = Ws2812Buffer::new();
+///
+/// let buffer = unsafe { &mut *core::ptr::addr_of_mut!(LED_BUFFER) };
+/// let mut leds = Ws2812::new(p.SPI1, daisy_pins.SEED_PIN_10, p.DMA1_CH3, buffer)?;
+/// leds.set(0, Rgb::new(1.0, 0.0, 0.0));
+/// leds.flush().await.unwrap();
+/// ```
+///
+/// Timing: one WS2812 bit is 4 SPI bits, so the SPI clock has to be between 2.7 and 3.4MHz
+/// (T0H is one SPI bit, 220-380ns, T1H two, 580-1000ns). The driver asks for 3.2MHz and the SPI
+/// divides its kernel clock by a power of two: with the PLL1_Q of 100MHz of [`crate::rcc`]
+/// (SPI1-3's default kernel clock) that is 3.125MHz. With other clock trees check that the
+/// kernel clock divided by 32 (or another power of two) falls into that range.
+///
+/// The LEDs run on 5V, but most accept a 3.3V data line when their supply isn't above 4.5V or so.
+/// Otherwise add a level shifter. DMA1_CH1 and DMA1_CH2 are taken by the audio interface.
+/// The SPI DMA can't read DTCM (where the stack is), so the encoded colors live in a
+/// [`Ws2812Buffer`] in DMA reachable memory, see [`crate::memory`].
+pub struct Ws2812<'a, const N: usize> {
+    spi: Spi<'a, hal::mode::Async>,
+    buffer: &'static mut Ws2812Buffer<N>,
+}
+
+/// The SPI bitstream of [`Ws2812`], for a `static` in DMA reachable memory, e.g. `.sram1_bss`.
+pub struct Ws2812Buffer<const N: usize> {
+    leds: [[u8; BYTES_PER_LED]; N],
+    reset: [u8; RESET_BYTES],
+}
+
+impl<const N: usize> Ws2812Buffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            leds: [[0; BYTES_PER_LED]; N],
+            reset: [0; RESET_BYTES],
+        }
+    }
+}
+
+impl<const N: usize> Default for Ws2812Buffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum Ws2812Error {
+    /// The [`Ws2812Buffer`] isn't in RAM that DMA1/DMA2 can reach, see [`crate::memory::is_dma_reachable`].
+    BufferNotDmaReachable,
+}
+
+impl<'a, const N: usize> Ws2812<'a, N> {
+    pub fn new<T: spi::Instance>(
+        peri: impl Peripheral<P = T> + 'a,
+        mosi: impl Peripheral<P = impl MosiPin<T>> + 'a,
+        tx_dma: impl Peripheral<P = impl TxDma<T>> + 'a,
+        buffer: &'static mut Ws2812Buffer<N>,
+    ) -> Result<Self, Ws2812Error> {
+        let address = &*buffer as *const Ws2812Buffer<N> as usize;
+        if !crate::memory::is_dma_reachable(address, core::mem::size_of::<Ws2812Buffer<N>>()) {
+            return Err(Ws2812Error::BufferNotDmaReachable);
+        }
+        // a NOLOAD section like `.sram1_bss` isn't initialized at startup
+        buffer.leds = [encode_led(Rgb::OFF); N];
+        buffer.reset = [0; RESET_BYTES];
+        let mut config = spi::Config::default();
+        config.frequency = WS2812_SPI_FREQUENCY;
+        Ok(Self {
+            spi: Spi::new_txonly_nosck(peri, mosi, tx_dma, config),
+            buffer,
+        })
+    }
+    /// Set the color of LED `index`, shown on the next [`Ws2812::flush`]. Out of range indices are ignored.
+    pub fn set(&mut self, index: usize, color: Rgb) {
+        if let Some(led) = self.buffer.leds.get_mut(index) {
+            *led = encode_led(color);
+        }
+    }
+    pub fn fill(&mut self, color: Rgb) {
+        self.buffer.leds = [encode_led(color); N];
+    }
+    /// Send the colors to the chain. Takes `N * 30us` plus 300us to latch.
+    pub async fn flush(&mut self) -> Result<(), spi::Error> {
+        self.spi.write(self.buffer.leds.as_flattened()).await?;
+        self.spi.write(&self.buffer.reset).await
+    }
+}
+
+fn encode_led(color: Rgb) -> [u8; BYTES_PER_LED] {
+    let mut out = [0; BYTES_PER_LED];
+    // WS2812 expects green, red, blue, MSB first
+    for (i, c) in [color.g, color.r, color.b].into_iter().enumerate() {
+        let value = (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+        for bit in 0..8 {
+            let pattern = if value & (0x80 >> bit) != 0 {
+                0b1100
+            } else {
+                0b1000
+            };
+            let shift = if bit % 2 == 0 { 4 } else { 0 };
+            out[i * 4 + bit / 2] |= pattern << shift;
+        }
+    }
+    out
+}
@@ -1,18 +1,77 @@
-#![no_std]
+#![cfg_attr(not(feature = "std"), no_std)]
+//! Board support for the Electro-Smith Daisy on embassy.
+//!
+//! The drivers need the `hal` feature (on by default). The DSP, parser and conversion modules
+//! ([`audio`] without the interface, [`controls`], [`cv`], [`crc::crc32_software`],
+//! [`midi::ClockSchedule`], [`midi::SysExAssembler`], [`sync::TempoTracker`]) don't, and build
+//! for the host too, to unit test or fuzz them there:
+//! ```text
+//! cargo test --target x86_64-unknown-linux-gnu --no-default-features --features std
+//! ```
+#[cfg(feature = "hal")]
+pub mod adc;
 pub mod audio;
+#[cfg(feature = "hal")]
+pub mod backup_ram;
+#[cfg(feature = "hal")]
 pub mod board;
+#[cfg(feature = "hal")]
+pub mod boards;
+pub mod controls;
+pub mod crc;
+pub mod cv;
+#[cfg(feature = "display")]
+pub mod display;
+#[cfg(feature = "hal")]
+pub mod gpio;
+#[cfg(feature = "hal")]
+pub mod info;
+#[cfg(feature = "hal")]
 pub mod led;
+#[cfg(feature = "hal")]
+pub mod memory;
+pub mod midi;
+#[cfg(feature = "hal")]
+pub mod pdm;
+#[cfg(feature = "hal")]
+pub mod perf;
+#[cfg(feature = "hal")]
 pub mod pins;
+#[cfg(feature = "hal")]
+pub mod power;
+#[cfg(feature = "hal")]
+pub mod rcc;
+#[cfg(feature = "hal")]
+pub mod reset;
+#[cfg(feature = "hal")]
+pub mod spi;
+#[cfg(feature = "hal")]
+pub mod switch;
+pub mod sync;
+#[cfg(feature = "hal")]
 pub mod usb;
 
+#[cfg(feature = "hal")]
 pub use board::DaisyBoard;
+/// `#[daisy_embassy::main]`, sets up the clocks and the board, see the macro's docs.
+/// `new_daisy_p!` and [`DaisyBoard::new`] remain for more control over the setup.
+#[cfg(feature = "hal")]
+pub use daisy_embassy_macros::main;
+#[cfg(feature = "hal")]
 pub use embassy_stm32 as hal;
+#[cfg(feature = "hal")]
+pub use info::board_info;
+#[cfg(feature = "hal")]
+pub use rcc::default_rcc;
+#[cfg(feature = "hal")]
+pub use reset::{last_reset_cause, ResetCause};
 
+#[cfg(feature = "hal")]
 #[macro_export]
 macro_rules! new_daisy_p {
     ($p:ident) => {
-        daisy_embassy::board::DaisyPeripherals {
-            daisy_pins: DaisyPins {
+        $crate::board::DaisyPeripherals {
+            daisy_pins: $crate::pins::DaisyPins {
                 SEED_PIN_0: $p.PB12,
                 SEED_PIN_1: $p.PC11,
                 SEED_PIN_2: $p.PC10,
@@ -46,7 +105,7 @@ macro_rules! new_daisy_p {
                 SEED_PIN_30: $p.PB15,
             },
             led_user_pin: $p.PC7,
-            wm8731_pin: WM8731Pins {
+            wm8731_pin: $crate::pins::WM8731Pins {
                 SCL: $p.PH4,
                 SDA: $p.PB11,
                 MCLK_A: $p.PE2,
@@ -55,17 +114,19 @@ macro_rules! new_daisy_p {
                 SD_A: $p.PE6,
                 SD_B: $p.PE3,
             },
-            audio_peripherals: daisy_embassy::audio::Peripherals {
+            audio_peripherals: $crate::audio::Peripherals {
                 sai1: $p.SAI1,
                 i2c2: $p.I2C2,
                 dma1_ch1: $p.DMA1_CH1,
                 dma1_ch2: $p.DMA1_CH2,
             },
-            usb2_pins: USB2Pins {
+            usb2_pins: $crate::pins::USB2Pins {
                 DN: $p.PA11,
                 DP: $p.PA12,
             },
             usb_otg_fs: $p.USB_OTG_FS,
+            rng: $p.RNG,
+            backup_ram: $crate::backup_ram::BackupRam::take(),
         }
     };
 }
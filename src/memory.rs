@@ -0,0 +1,173 @@
+//! Memory map of the Daisy Seed (STM32H750IB plus external SDRAM and QSPI flash).
+//! The crate's `memory.x` is generated from it by [`write_memory_x`]. Use these for custom
+//! linker scripts too, e.g. from a `build.rs`:
+//! ```ignore
+//! writeln!(memory_x, "QSPIFLASH (RX) : ORIGIN = {:#x}, LENGTH = {}K",
+//!     memory::BOOTLOADER_QSPI_APP_BASE, memory::BOOTLOADER_QSPI_APP_SIZE / 1024)?;
+//! ```
+
+/// Internal flash. Only 128K on the STM32H750.
+pub const FLASH_BASE: usize = 0x0800_0000;
+pub const FLASH_SIZE: usize = 128 * 1024;
+
+pub const ITCM_BASE: usize = 0x0000_0000;
+pub const ITCM_SIZE: usize = 64 * 1024;
+pub const DTCM_BASE: usize = 0x2000_0000;
+pub const DTCM_SIZE: usize = 128 * 1024;
+
+/// AXI SRAM in domain D1.
+pub const SRAM_D1_BASE: usize = 0x2400_0000;
+pub const SRAM_D1_SIZE: usize = 512 * 1024;
+/// SRAM1-3 in domain D2, DMA1/DMA2 capable. The audio DMA buffers live here (`.sram1_bss`).
+pub const SRAM_D2_BASE: usize = 0x3000_0000;
+pub const SRAM_D2_SIZE: usize = 288 * 1024;
+/// SRAM4 in domain D3, the only RAM BDMA can reach.
+pub const SRAM_D3_BASE: usize = 0x3800_0000;
+pub const SRAM_D3_SIZE: usize = 64 * 1024;
+/// Backup SRAM, kept in VBAT mode.
+pub const BACKUP_SRAM_BASE: usize = 0x3880_0000;
+pub const BACKUP_SRAM_SIZE: usize = 4 * 1024;
+
+/// External SDRAM (AS4C16M32MSA) on FMC. Needs FMC setup before use.
+pub const SDRAM_BASE: usize = 0xC000_0000;
+pub const SDRAM_SIZE: usize = 64 * 1024 * 1024;
+/// External QSPI flash (IS25LP064A), memory mapped.
+pub const QSPI_BASE: usize = 0x9000_0000;
+pub const QSPI_SIZE: usize = 8 * 1024 * 1024;
+
+/// The Daisy bootloader occupies the first 256K of QSPI flash and runs
+/// `BOOT_QSPI` applications from right after it.
+pub const BOOTLOADER_QSPI_APP_BASE: usize = QSPI_BASE + 256 * 1024;
+pub const BOOTLOADER_QSPI_APP_SIZE: usize = QSPI_SIZE - 256 * 1024;
+/// `BOOT_SRAM` applications are copied by the bootloader into AXI SRAM and run from there.
+pub const BOOTLOADER_SRAM_APP_BASE: usize = SRAM_D1_BASE;
+
+/// Whether `len` bytes at `addr` lie entirely in RAM that DMA1 and DMA2 can access:
+/// AXI SRAM, SRAM1-4 or SDRAM. The TCMs and the flashes are not reachable.
+pub fn is_dma_reachable(addr: usize, len: usize) -> bool {
+    const REGIONS: [(usize, usize); 4] = [
+        (SRAM_D1_BASE, SRAM_D1_SIZE),
+        (SRAM_D2_BASE, SRAM_D2_SIZE),
+        (SRAM_D3_BASE, SRAM_D3_SIZE),
+        (SDRAM_BASE, SDRAM_SIZE),
+    ];
+    REGIONS
+        .iter()
+        .any(|&(base, size)| addr >= base && addr.saturating_add(len) <= base + size)
+}
+
+/// Where the application is linked to run from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Boot {
+    /// Internal flash, flashed with a probe or DFU. The crate's default.
+    Flash,
+    /// From QSPI flash under the Daisy bootloader (`BOOT_QSPI`), see [`BOOTLOADER_QSPI_APP_BASE`].
+    BootloaderQspi,
+    /// Copied into AXI SRAM by the Daisy bootloader (`BOOT_SRAM`), see [`BOOTLOADER_SRAM_APP_BASE`].
+    /// The whole AXI SRAM then holds the program, so there is no `SRAM` region.
+    BootloaderSram,
+}
+
+/// What [`write_memory_x`] generates.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LinkerLayout {
+    pub boot: Boot,
+    /// Add the `SDRAM` region and a `.sdram_bss` section in it.
+    /// The SDRAM still needs FMC setup before anything there is touched.
+    pub sdram: bool,
+}
+
+impl LinkerLayout {
+    /// The layout of the crate's own `memory.x` with the default features.
+    pub const DEFAULT: LinkerLayout = LinkerLayout {
+        boot: Boot::Flash,
+        sdram: true,
+    };
+}
+
+impl Default for LinkerLayout {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Write a `memory.x` for cortex-m-rt with the regions of `layout`, plus the `.sram1_bss` section
+/// the audio DMA buffers need. The crate's `build.rs` uses this with the `under_bootloader`,
+/// `under_bootloader_sram` and `use_sdram` features; a custom one can do the same:
+/// ```ignore
+/// let mut memory_x = String::new();
+/// let boot = if env::var_os("CARGO_FEATURE_UNDER_BOOTLOADER").is_some() {
+///     memory::Boot::BootloaderQspi
+/// } else {
+///     memory::Boot::Flash
+/// };
+/// memory::write_memory_x(&mut memory_x, memory::LinkerLayout { boot, sdram: true }).unwrap();
+/// fs::write(out.join("memory.x"), memory_x).unwrap();
+/// ```
+pub fn write_memory_x(out: &mut impl core::fmt::Write, layout: LinkerLayout) -> core::fmt::Result {
+    let (flash_base, flash_size) = match layout.boot {
+        Boot::Flash => (FLASH_BASE, FLASH_SIZE),
+        Boot::BootloaderQspi => (BOOTLOADER_QSPI_APP_BASE, BOOTLOADER_QSPI_APP_SIZE),
+        Boot::BootloaderSram => (BOOTLOADER_SRAM_APP_BASE, SRAM_D1_SIZE),
+    };
+    let region =
+        |out: &mut dyn core::fmt::Write, name: &str, attrs: &str, base: usize, size: usize| {
+            let (size, unit) = if size.is_multiple_of(1024 * 1024) {
+                (size / (1024 * 1024), "M")
+            } else {
+                (size / 1024, "K")
+            };
+            writeln!(
+                out,
+                "    {name:<9} ({attrs}) : ORIGIN = {base:#010x}, LENGTH = {size}{unit}"
+            )
+        };
+    writeln!(
+        out,
+        "/* Generated by daisy_embassy::memory::write_memory_x for {layout:?} */"
+    )?;
+    writeln!(out, "MEMORY\n{{")?;
+    region(out, "FLASH", "RX ", flash_base, flash_size)?;
+    region(out, "DTCMRAM", "RWX", DTCM_BASE, DTCM_SIZE)?;
+    if layout.boot != Boot::BootloaderSram {
+        region(out, "SRAM", "RWX", SRAM_D1_BASE, SRAM_D1_SIZE)?;
+    }
+    region(out, "RAM_D2", "RWX", SRAM_D2_BASE, SRAM_D2_SIZE)?;
+    region(out, "RAM_D3", "RWX", SRAM_D3_BASE, SRAM_D3_SIZE)?;
+    region(out, "ITCMRAM", "RWX", ITCM_BASE, ITCM_SIZE)?;
+    if layout.sdram {
+        region(out, "SDRAM", "RWX", SDRAM_BASE, SDRAM_SIZE)?;
+    }
+    // the application itself lives in QSPI flash under the bootloader
+    if layout.boot != Boot::BootloaderQspi {
+        region(out, "QSPIFLASH", "RX ", QSPI_BASE, QSPI_SIZE)?;
+    }
+    writeln!(out, "}}\n")?;
+    writeln!(
+        out,
+        "/* stm32h7xx-hal uses a PROVIDE that expects RAM symbol to exist */"
+    )?;
+    writeln!(out, "REGION_ALIAS(RAM, DTCMRAM);\n")?;
+    writeln!(out, "SECTIONS\n{{")?;
+    bss_section(out, "sram1_bss", "__sram1_bss", "RAM_D2")?;
+    if layout.sdram {
+        writeln!(out)?;
+        bss_section(out, "sdram_bss", "__sdram_bss", "SDRAM")?;
+    }
+    writeln!(out, "}}")
+}
+
+fn bss_section(
+    out: &mut impl core::fmt::Write,
+    name: &str,
+    provide: &str,
+    region: &str,
+) -> core::fmt::Result {
+    writeln!(out, "    .{name} (NOLOAD) :\n    {{")?;
+    writeln!(out, "        . = ALIGN(4);\n        _s{name} = .;")?;
+    writeln!(out, "        PROVIDE({provide}_start__ = _s{name});")?;
+    writeln!(out, "        *(.{name})\n        *(.{name}*)")?;
+    writeln!(out, "        . = ALIGN(4);\n        _e{name} = .;")?;
+    writeln!(out, "        PROVIDE({provide}_end__ = _e{name});")?;
+    writeln!(out, "    }} > {region}")
+}
@@ -0,0 +1,395 @@
+//! MIDI timing clock output, the counterpart of [`crate::sync::ClockInput`], and SysEx reception
+//! with [`SysExAssembler`].
+//!
+//! Works over anything implementing `embedded_io_async::Write`, e.g. a USART at 31250 baud:
+//! ```ignore
+//! let mut config = usart::Config::default();
+//! config.baudrate = 31_250;
+//! let uart = UartTx::new(p.USART1, p.PB6, p.DMA1_CH3, config).unwrap();
+//! let mut clock = ClockOutput::new(uart, 120.0);
+//! clock.start().await.unwrap();
+//! loop {
+//!     match select(clock.tick(), COMMANDS.receive()).await {
+//!         Either::First(result) => result.unwrap(),
+//!         Either::Second(Command::Bpm(bpm)) => clock.set_bpm(bpm),
+//!         Either::Second(Command::Stop) => clock.stop().await.unwrap(),
+//!     }
+//! }
+//! ```
+#[cfg(feature = "hal")]
+use embassy_time::{Instant, Timer};
+#[cfg(feature = "hal")]
+use embedded_io_async::Write;
+
+pub const TIMING_CLOCK: u8 = 0xF8;
+pub const START: u8 = 0xFA;
+pub const CONTINUE: u8 = 0xFB;
+pub const STOP: u8 = 0xFC;
+pub const SYSEX_START: u8 = 0xF0;
+pub const SYSEX_END: u8 = 0xF7;
+
+/// Timing clocks per quarter note.
+pub const PPQN: u32 = 24;
+/// Tempo a NaN BPM falls back to.
+pub const DEFAULT_BPM: f32 = 120.0;
+
+/// When the clocks are due, independent of the hardware.
+///
+/// Deadlines are computed from a fixed origin rather than by adding up intervals,
+/// so rounding doesn't accumulate and the clock stays in phase over long runs.
+pub struct ClockSchedule {
+    origin_micros: u64,
+    // tempo in 1/1000 BPM, so deadlines are exact integer fractions
+    millibpm: u64,
+    ticks: u64,
+}
+
+impl ClockSchedule {
+    /// `bpm` is clamped to 1 to 1000, NaN gives [`DEFAULT_BPM`].
+    pub fn new(bpm: f32, now_micros: u64) -> Self {
+        Self {
+            origin_micros: now_micros,
+            millibpm: to_millibpm(bpm),
+            ticks: 0,
+        }
+    }
+    /// Change the tempo from the last clock on, without a phase jump.
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.origin_micros = self.deadline_micros(self.ticks);
+        self.ticks = 0;
+        self.millibpm = to_millibpm(bpm);
+    }
+    /// Start counting again with the next clock due at `now_micros`.
+    pub fn restart(&mut self, now_micros: u64) {
+        self.origin_micros = now_micros;
+        self.ticks = 0;
+    }
+    /// When the next clock is due.
+    pub fn next_micros(&self) -> u64 {
+        self.deadline_micros(self.ticks)
+    }
+    /// Mark the next clock as sent.
+    pub fn advance(&mut self) {
+        self.ticks += 1;
+    }
+    fn deadline_micros(&self, ticks: u64) -> u64 {
+        // 60s * 1000 (millibpm) in microseconds, u128 so long runs can't overflow
+        let micros = ticks as u128 * 60_000_000_000 / (self.millibpm as u128 * PPQN as u128);
+        self.origin_micros + micros as u64
+    }
+}
+
+fn to_millibpm(bpm: f32) -> u64 {
+    let bpm = if bpm.is_nan() {
+        DEFAULT_BPM
+    } else {
+        bpm.clamp(1.0, 1000.0)
+    };
+    (bpm * 1000.0 + 0.5) as u64
+}
+
+/// A SysEx message couldn't be delivered, see [`SysExAssembler::push`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SysExError {
+    /// The message didn't fit the buffer and was dropped.
+    Overflow,
+    /// A status byte other than real-time or end of exclusive cut the message short.
+    /// A message that had already overflowed reports [`SysExError::Overflow`] instead.
+    Interrupted,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SysExState {
+    Idle,
+    Receiving,
+    Overflowed,
+}
+
+/// Collects SysEx messages from a byte stream into a caller provided buffer.
+///
+/// Bytes can come in any fragments, e.g. as read from a UART or unpacked from USB MIDI packets:
+/// ```ignore
+/// let mut buf = [0u8; 1024];
+/// let mut sysex = SysExAssembler::new(&mut buf);
+/// loop {
+///     let n = uart.read(&mut rx).await?;
+///     for &byte in &rx[..n] {
+///         match sysex.push(byte) {
+///             Some(Ok(message)) => import_patch(message),
+///             Some(Err(e)) => warn!("SysEx dropped: {}", e),
+///             None => {}
+///         }
+///     }
+/// }
+/// ```
+/// Real-time messages (timing clock and the like) may be interleaved with a SysEx message,
+/// they are skipped here and have to be handled by the caller.
+pub struct SysExAssembler<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    state: SysExState,
+}
+
+impl<'a> SysExAssembler<'a> {
+    /// Messages up to `buf.len()` bytes, including [`SYSEX_START`] and [`SYSEX_END`].
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Self {
+            buf,
+            len: 0,
+            state: SysExState::Idle,
+        }
+    }
+    /// Feed one byte. Returns the complete message, from [`SYSEX_START`] to [`SYSEX_END`],
+    /// on its last byte, an error once for each message that was dropped, `None` otherwise.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&[u8], SysExError>> {
+        match byte {
+            // real-time, may occur anywhere
+            0xF8..=0xFF => None,
+            SYSEX_START => {
+                let dropped = Self::dropped(self.state);
+                self.state = SysExState::Receiving;
+                self.len = 0;
+                self.store(byte);
+                dropped.map(Err)
+            }
+            SYSEX_END => {
+                let state = core::mem::replace(&mut self.state, SysExState::Idle);
+                match state {
+                    SysExState::Idle => None,
+                    SysExState::Overflowed => Some(Err(SysExError::Overflow)),
+                    SysExState::Receiving => {
+                        self.store(byte);
+                        if self.state == SysExState::Overflowed {
+                            self.state = SysExState::Idle;
+                            return Some(Err(SysExError::Overflow));
+                        }
+                        Some(Ok(&self.buf[..self.len]))
+                    }
+                }
+            }
+            0x80..=0xF6 => {
+                let state = core::mem::replace(&mut self.state, SysExState::Idle);
+                Self::dropped(state).map(Err)
+            }
+            _ => {
+                if self.state == SysExState::Receiving {
+                    self.store(byte);
+                }
+                None
+            }
+        }
+    }
+    /// Whether a message is being received.
+    pub fn is_receiving(&self) -> bool {
+        self.state != SysExState::Idle
+    }
+    // why a message cut short in `state` is dropped, an overflow being the first cause
+    fn dropped(state: SysExState) -> Option<SysExError> {
+        match state {
+            SysExState::Idle => None,
+            SysExState::Receiving => Some(SysExError::Interrupted),
+            SysExState::Overflowed => Some(SysExError::Overflow),
+        }
+    }
+    fn store(&mut self, byte: u8) {
+        match self.buf.get_mut(self.len) {
+            Some(slot) => {
+                *slot = byte;
+                self.len += 1;
+            }
+            None => self.state = SysExState::Overflowed,
+        }
+    }
+}
+
+/// Sends 24 PPQN timing clocks and start/stop/continue messages.
+///
+/// Clocks are sent while stopped as well, so receivers can follow the tempo before start,
+/// as the MIDI spec recommends.
+#[cfg(feature = "hal")]
+pub struct ClockOutput<W: Write> {
+    out: W,
+    schedule: ClockSchedule,
+    running: bool,
+}
+
+#[cfg(feature = "hal")]
+impl<W: Write> ClockOutput<W> {
+    pub fn new(out: W, bpm: f32) -> Self {
+        Self {
+            out,
+            schedule: ClockSchedule::new(bpm, Instant::now().as_micros()),
+            running: false,
+        }
+    }
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.schedule.set_bpm(bpm);
+    }
+    /// Wait for the next clock and send it.
+    pub async fn tick(&mut self) -> Result<(), W::Error> {
+        Timer::at(Instant::from_micros(self.schedule.next_micros())).await;
+        self.schedule.advance();
+        self.out.write_all(&[TIMING_CLOCK]).await
+    }
+    /// Send start. The next clock is the first beat of the song, sent right away.
+    pub async fn start(&mut self) -> Result<(), W::Error> {
+        self.running = true;
+        self.out.write_all(&[START]).await?;
+        self.schedule.restart(Instant::now().as_micros());
+        Ok(())
+    }
+    pub async fn stop(&mut self) -> Result<(), W::Error> {
+        self.running = false;
+        self.out.write_all(&[STOP]).await
+    }
+    /// Resume from the song position where the receivers stopped.
+    pub async fn continue_(&mut self) -> Result<(), W::Error> {
+        self.running = true;
+        self.out.write_all(&[CONTINUE]).await
+    }
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a copy of a delivered message (up to 8 bytes) and its length, or the error
+    type Delivered = Option<Result<([u8; 8], usize), SysExError>>;
+
+    // feeds `bytes` and collects what comes out, `N` results at most
+    fn feed<const N: usize>(sysex: &mut SysExAssembler, bytes: &[u8]) -> [Delivered; N] {
+        let mut results = [None; N];
+        let mut count = 0;
+        for &byte in bytes {
+            if let Some(result) = sysex.push(byte) {
+                results[count] = Some(result.map(|message| {
+                    let mut copy = [0; 8];
+                    copy[..message.len()].copy_from_slice(message);
+                    (copy, message.len())
+                }));
+                count += 1;
+            }
+        }
+        results
+    }
+
+    fn message(bytes: &[u8]) -> Delivered {
+        let mut copy = [0; 8];
+        copy[..bytes.len()].copy_from_slice(bytes);
+        Some(Ok((copy, bytes.len())))
+    }
+
+    #[test]
+    fn sysex_in_fragments() {
+        let mut buf = [0u8; 6];
+        let mut sysex = SysExAssembler::new(&mut buf);
+        // a data byte before the start is ignored
+        assert_eq!(feed(&mut sysex, &[0x01, SYSEX_START, 0x7D]), [None]);
+        assert!(sysex.is_receiving());
+        assert_eq!(feed(&mut sysex, &[0x01]), [None]);
+        assert_eq!(
+            feed(&mut sysex, &[0x02, SYSEX_END]),
+            [message(&[SYSEX_START, 0x7D, 1, 2, SYSEX_END])]
+        );
+        assert!(!sysex.is_receiving());
+        // a stray end is ignored
+        assert_eq!(feed(&mut sysex, &[SYSEX_END]), [None]);
+    }
+
+    #[test]
+    fn sysex_with_real_time_bytes() {
+        let mut buf = [0u8; 6];
+        let mut sysex = SysExAssembler::new(&mut buf);
+        let bytes = [SYSEX_START, TIMING_CLOCK, 1, START, 2, 0xFE, SYSEX_END];
+        assert_eq!(
+            feed(&mut sysex, &bytes),
+            [message(&[SYSEX_START, 1, 2, SYSEX_END])]
+        );
+    }
+
+    #[test]
+    fn sysex_overflow() {
+        let mut buf = [0u8; 6];
+        let mut sysex = SysExAssembler::new(&mut buf);
+        // exactly fits
+        let bytes = [SYSEX_START, 1, 2, 3, 4, SYSEX_END];
+        assert_eq!(feed(&mut sysex, &bytes), [message(&bytes)]);
+        // the end doesn't fit
+        let bytes = [SYSEX_START, 1, 2, 3, 4, 5, SYSEX_END];
+        assert_eq!(feed(&mut sysex, &bytes), [Some(Err(SysExError::Overflow))]);
+        let bytes = [SYSEX_START, 1, 2, 3, 4, 5, 6, 7, SYSEX_END];
+        assert_eq!(feed(&mut sysex, &bytes), [Some(Err(SysExError::Overflow))]);
+        // the next message is fine again
+        let bytes = [SYSEX_START, 1, SYSEX_END];
+        assert_eq!(feed(&mut sysex, &bytes), [message(&bytes)]);
+    }
+
+    #[test]
+    fn sysex_interrupted() {
+        let mut buf = [0u8; 6];
+        let mut sysex = SysExAssembler::new(&mut buf);
+        // by a note on
+        let bytes = [SYSEX_START, 1, 0x90, 0x40, 0x7F];
+        assert_eq!(
+            feed(&mut sysex, &bytes),
+            [Some(Err(SysExError::Interrupted))]
+        );
+        assert!(!sysex.is_receiving());
+        // by the next message
+        let bytes = [SYSEX_START, 1, SYSEX_START, 2, SYSEX_END];
+        assert_eq!(
+            feed(&mut sysex, &bytes),
+            [
+                Some(Err(SysExError::Interrupted)),
+                message(&[SYSEX_START, 2, SYSEX_END])
+            ]
+        );
+    }
+
+    #[test]
+    fn sysex_overflow_then_interrupted() {
+        let mut buf = [0u8; 6];
+        let mut sysex = SysExAssembler::new(&mut buf);
+        let bytes = [SYSEX_START, 1, 2, 3, 4, 5, 6, SYSEX_START, 2, SYSEX_END];
+        assert_eq!(
+            feed(&mut sysex, &bytes),
+            [
+                Some(Err(SysExError::Overflow)),
+                message(&[SYSEX_START, 2, SYSEX_END])
+            ]
+        );
+        let bytes = [SYSEX_START, 1, 2, 3, 4, 5, 6, 0x90];
+        assert_eq!(feed(&mut sysex, &bytes), [Some(Err(SysExError::Overflow))]);
+    }
+
+    #[test]
+    fn clock_schedule_doesnt_drift() {
+        let mut schedule = ClockSchedule::new(120.0, 1000);
+        assert_eq!(schedule.next_micros(), 1000);
+        // one hour of clocks
+        for _ in 0..PPQN * 120 * 60 {
+            schedule.advance();
+        }
+        assert_eq!(schedule.next_micros(), 1000 + 3_600_000_000);
+        schedule.set_bpm(60.0);
+        schedule.advance();
+        assert_eq!(schedule.next_micros(), 1000 + 3_600_000_000 + 41_666);
+    }
+
+    #[test]
+    fn clock_schedule_nan_bpm() {
+        let mut schedule = ClockSchedule::new(f32::NAN, 0);
+        schedule.advance();
+        assert_eq!(schedule.next_micros(), 20_833);
+        schedule.set_bpm(f32::NAN);
+        schedule.advance();
+        assert_eq!(schedule.next_micros(), 41_666);
+        schedule.set_bpm(f32::INFINITY);
+        schedule.advance();
+        assert_eq!(schedule.next_micros(), 41_666 + 2_500);
+    }
+}
@@ -0,0 +1,102 @@
+//! PDM MEMS microphone on SPI1.
+//!
+//! SAI1 (whose PDM interface would be the natural fit) is taken by the codec, so the mic is
+//! clocked by SPI1 in receive only master mode and the bitstream is decimated in software
+//! with [`CicDecimator`]:
+//!
+//! | mic signal | seed pin     | MCU pin |
+//! |------------|--------------|---------|
+//! | CLK        | `SEED_PIN_8` | PG11    |
+//! | DATA       | `SEED_PIN_9` | PB4     |
+//!
+//! Tie the mic's L/R select to GND, its data is then valid on the rising clock edge.
+//!
+//! The SPI's DMA writes the bitstream into a buffer the caller places in DMA reachable RAM,
+//! not on the stack, which is in DTCM:
+//! ```ignore
+//! #[link_section = ".sram1_bss"]
+//! static mut PDM_BUFFER: [u8; pdm::BYTES_PER_BLOCK] = [0; pdm::BYTES_PER_BLOCK];
+//!
+//! let bits = unsafe { &mut *core::ptr::addr_of_mut!(PDM_BUFFER) };
+//! let mut mic = PdmMic::new(p.SPI1, p.PG11, p.PB4, p.DMA1_CH3, p.DMA1_CH4, bits, 48_000)?;
+//! let mut block = [0.0; BLOCK_LENGTH];
+//! loop {
+//!     mic.read(&mut block).await.unwrap();
+//! }
+//! ```
+//!
+//! Mic clock rates: the mic clock is `DECIMATION * sample_rate`, 3.072MHz for 48kHz and
+//! 1.536MHz for 24kHz, both within the 1-3.25MHz range of common MEMS mics.
+//! The SPI clock is its kernel clock divided by a power of two, so the actual mic clock
+//! (and with it the output rate) is the closest such division at or below the requested one.
+//! It is also not locked to the codec's clock. Use [`crate::audio::Resampler`] if the samples
+//! are mixed with the codec's stream.
+//!
+//! Latency: one block (`BLOCK_LENGTH` samples) of buffering plus about 2 samples in the decimator.
+//! The mic clock pauses for a few microseconds between blocks, which most MEMS mics tolerate
+//! but shows up as a short settling transient on mics that don't.
+use crate::audio::{CicDecimator, BLOCK_LENGTH};
+use crate::pins::{SeedPin8, SeedPin9};
+use embassy_stm32 as hal;
+use hal::peripherals::SPI1;
+use hal::spi::{BitOrder, Config, RxDma, Spi, TxDma, MODE_0};
+use hal::time::Hertz;
+use hal::Peripheral;
+
+/// PDM bits per PCM sample.
+pub const DECIMATION: u32 = 64;
+/// Length of the bitstream buffer [`PdmMic::new`] takes.
+pub const BYTES_PER_BLOCK: usize = BLOCK_LENGTH * DECIMATION as usize / 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum PdmError {
+    /// The bitstream buffer isn't in RAM that DMA1/DMA2 can reach, see [`crate::memory::is_dma_reachable`].
+    BufferNotDmaReachable,
+    Spi(hal::spi::Error),
+}
+
+impl From<hal::spi::Error> for PdmError {
+    fn from(e: hal::spi::Error) -> Self {
+        PdmError::Spi(e)
+    }
+}
+
+pub struct PdmMic<'a> {
+    spi: Spi<'a, hal::mode::Async>,
+    decimator: CicDecimator,
+    bits: &'static mut [u8; BYTES_PER_BLOCK],
+}
+
+impl<'a> PdmMic<'a> {
+    /// Mono PDM mic with `CLK` on `SEED_PIN_8` and `DATA` on `SEED_PIN_9`.
+    /// DMA1_CH1 and DMA1_CH2 are taken by the audio interface, use any other streams.
+    /// `bits` receives the bitstream by DMA, so it can't be in the TCMs (e.g. use `.sram1_bss`).
+    pub fn new(
+        spi1: SPI1,
+        clk: SeedPin8,
+        data: SeedPin9,
+        tx_dma: impl Peripheral<P = impl TxDma<SPI1>> + 'a,
+        rx_dma: impl Peripheral<P = impl RxDma<SPI1>> + 'a,
+        bits: &'static mut [u8; BYTES_PER_BLOCK],
+        sample_rate: u32,
+    ) -> Result<Self, PdmError> {
+        if !crate::memory::is_dma_reachable(bits.as_ptr() as usize, BYTES_PER_BLOCK) {
+            return Err(PdmError::BufferNotDmaReachable);
+        }
+        let mut config = Config::default();
+        config.mode = MODE_0;
+        config.bit_order = BitOrder::MsbFirst;
+        config.frequency = Hertz(sample_rate * DECIMATION);
+        Ok(Self {
+            spi: Spi::new_rxonly(spi1, clk, data, tx_dma, rx_dma, config),
+            decimator: CicDecimator::new(DECIMATION),
+            bits,
+        })
+    }
+    /// Clock in one block worth of PDM data and decimate it into `block`.
+    pub async fn read(&mut self, block: &mut [f32; BLOCK_LENGTH]) -> Result<(), PdmError> {
+        self.spi.read(&mut self.bits[..]).await?;
+        self.decimator.process(&self.bits[..], block);
+        Ok(())
+    }
+}
@@ -0,0 +1,149 @@
+//! CPU cycle counting with the Cortex-M7 DWT, to check DSP code against the audio deadline.
+//!
+//! One block is `BLOCK_LENGTH` samples, 0.67ms or 266k cycles at 48kHz and 400MHz.
+//! ```ignore
+//! let mut counter = CycleCounter::new();
+//! counter.start();
+//! process(&mut block);
+//! let cycles = counter.stop();
+//! info!("{} cycles, {}us", cycles, cycles_to_micros(cycles));
+//! ```
+use crate::rcc::CPU_CLOCK;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+// DWT and DCB registers, see the ARMv7-M Architecture Reference Manual
+const DEMCR: *mut u32 = 0xE000_EDFC as *mut u32;
+const DEMCR_TRCENA: u32 = 1 << 24;
+const DWT_CTRL: *mut u32 = 0xE000_1000 as *mut u32;
+const DWT_CTRL_CYCCNTENA: u32 = 1;
+const DWT_CYCCNT: *mut u32 = 0xE000_1004 as *mut u32;
+// the Cortex-M7 DWT ignores writes until unlocked
+const DWT_LAR: *mut u32 = 0xE000_1FB0 as *mut u32;
+const DWT_LAR_KEY: u32 = 0xC5AC_CE55;
+
+/// Measures cycles between [`CycleCounter::start`] and [`CycleCounter::stop`].
+///
+/// The DWT counter wraps every 10.7s at 400MHz, shorter spans are measured correctly across the wrap.
+/// A debugger may use the DWT as well; the counter is only enabled, never reset.
+pub struct CycleCounter {
+    started_at: u32,
+    max: u32,
+}
+
+impl CycleCounter {
+    /// Enables the DWT cycle counter.
+    pub fn new() -> Self {
+        enable_cycle_count();
+        Self {
+            started_at: cycle_count(),
+            max: 0,
+        }
+    }
+    pub fn start(&mut self) {
+        self.started_at = cycle_count();
+    }
+    /// Cycles since [`CycleCounter::start`].
+    pub fn stop(&mut self) -> u32 {
+        let cycles = cycle_count().wrapping_sub(self.started_at);
+        self.max = self.max.max(cycles);
+        cycles
+    }
+    /// Longest span measured since creation or [`CycleCounter::reset_max`].
+    pub fn max(&self) -> u32 {
+        self.max
+    }
+    pub fn reset_max(&mut self) {
+        self.max = 0;
+    }
+}
+
+impl Default for CycleCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn enable_cycle_count() {
+    // Safety: fixed system registers, only the trace enable and counter enable bits are set.
+    unsafe {
+        DEMCR.write_volatile(DEMCR.read_volatile() | DEMCR_TRCENA);
+        DWT_LAR.write_volatile(DWT_LAR_KEY);
+        DWT_CTRL.write_volatile(DWT_CTRL.read_volatile() | DWT_CTRL_CYCCNTENA);
+    }
+}
+
+/// Current DWT cycle count. Only counts once a [`CycleCounter`] was created.
+pub fn cycle_count() -> u32 {
+    // Safety: read only access to the counter.
+    unsafe { DWT_CYCCNT.read_volatile() }
+}
+
+/// Cycles to microseconds at [`CPU_CLOCK`].
+pub fn cycles_to_micros(cycles: u32) -> f32 {
+    cycles as f32 * 1_000_000.0 / CPU_CLOCK.0 as f32
+}
+
+/// Share of the time available for one block at `sample_rate` that `cycles` take, 1.0 is the deadline.
+pub fn block_load(cycles: u32, frames_per_block: usize, sample_rate: u32) -> f32 {
+    let budget = CPU_CLOCK.0 as f32 * frames_per_block as f32 / sample_rate as f32;
+    cycles as f32 / budget
+}
+
+// written by the audio interface only, see record_block()
+static BLOCK_TIMESTAMP: AtomicU32 = AtomicU32::new(0);
+static BLOCK_PERIOD: AtomicU32 = AtomicU32::new(0);
+static BLOCK_PERIOD_MIN: AtomicU32 = AtomicU32::new(u32::MAX);
+static BLOCK_PERIOD_MAX: AtomicU32 = AtomicU32::new(0);
+static BLOCKS_TIMED: AtomicU32 = AtomicU32::new(0);
+
+/// Timing of the blocks received by the audio interface, in CPU cycles,
+/// with [`AudioConfig::block_timestamps`](crate::audio::AudioConfig::block_timestamps) enabled.
+///
+/// The nominal period is `frames_per_block / sample_rate`, 266667 cycles for 32 frames at 48kHz.
+/// `max - min` is the jitter of the interface task: a period well above nominal means the executor
+/// was late to pick up the block (the DMA had the data earlier), usually followed by a short one.
+#[derive(Clone, Copy, Debug, defmt::Format)]
+pub struct BlockTiming {
+    /// DWT cycle count when the last block arrived.
+    pub timestamp: u32,
+    /// Time between the last two blocks.
+    pub period: u32,
+    /// Shortest period since the start or [`reset_block_timing`].
+    pub min: u32,
+    /// Longest period since the start or [`reset_block_timing`].
+    pub max: u32,
+    /// Periods measured since the start or [`reset_block_timing`].
+    pub count: u32,
+}
+
+pub fn block_timing() -> BlockTiming {
+    BlockTiming {
+        timestamp: BLOCK_TIMESTAMP.load(Ordering::Relaxed),
+        period: BLOCK_PERIOD.load(Ordering::Relaxed),
+        min: BLOCK_PERIOD_MIN.load(Ordering::Relaxed),
+        max: BLOCK_PERIOD_MAX.load(Ordering::Relaxed),
+        count: BLOCKS_TIMED.load(Ordering::Relaxed),
+    }
+}
+
+/// Start a new min/max measurement, e.g. after the startup transient.
+pub fn reset_block_timing() {
+    BLOCK_PERIOD_MIN.store(u32::MAX, Ordering::Relaxed);
+    BLOCK_PERIOD_MAX.store(0, Ordering::Relaxed);
+    BLOCKS_TIMED.store(0, Ordering::Relaxed);
+}
+
+// Called by the interface when a block has been read from the SAI.
+pub(crate) fn record_block() {
+    let now = cycle_count();
+    let previous = BLOCK_TIMESTAMP.swap(now, Ordering::Relaxed);
+    // the first block has nothing to compare to
+    if previous == 0 {
+        return;
+    }
+    let period = now.wrapping_sub(previous);
+    BLOCK_PERIOD.store(period, Ordering::Relaxed);
+    BLOCK_PERIOD_MIN.fetch_min(period, Ordering::Relaxed);
+    BLOCK_PERIOD_MAX.fetch_max(period, Ordering::Relaxed);
+    BLOCKS_TIMED.fetch_add(1, Ordering::Relaxed);
+}
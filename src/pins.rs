@@ -82,6 +82,27 @@ pub struct WM8731Pins {
     pub SD_B: hal::peripherals::PE3,   // SAI1 SD_B
 }
 
+/// Codec pins of the Daisy Seed. The Seed 1.1's WM8731 and the Seed 2 DFM's PCM3060 use the same ones.
+pub type CodecPins = WM8731Pins;
+
+#[allow(non_snake_case)]
+pub struct Pcm3060Pins {
+    pub SCL: hal::peripherals::PB10,   // I2C SCL
+    pub SDA: hal::peripherals::PB11,   // I2C SDA
+    pub MCLK_A: hal::peripherals::PE2, // SAI1 MCLK_A
+    pub SCK_A: hal::peripherals::PE5,  // SAI1 SCK_A
+    pub FS_A: hal::peripherals::PE4,   // SAI1 FS_A
+    pub SD_A: hal::peripherals::PE6,   // SAI1 SD_A
+    pub SD_B: hal::peripherals::PE3,   // SAI1 SD_B
+}
+
+/// USB_OTG_HS on `SEED_PIN_29`/`SEED_PIN_30`, see [`crate::usb::init_hs`].
+#[allow(non_snake_case)]
+pub struct USB1Pins {
+    pub DN: SeedPin29, // USB1 D-
+    pub DP: SeedPin30, // USB1 D+
+}
+
 #[allow(non_snake_case)]
 pub struct USB2Pins {
     pub DN: hal::peripherals::PA11, // USB2 D-
@@ -0,0 +1,78 @@
+//! Battery or supply voltage monitoring through a resistor divider.
+//!
+//! Wire the supply through a divider into an ADC3 capable seed pin (`SEED_PIN_15`/PC0 or `SEED_PIN_20`/PC1),
+//! keeping the divided voltage below 3.3V. With 100k over 33k, 12V becomes 2.98V, a ratio of 133/33:
+//! ```ignore
+//! let monitor = McuMonitor::new(p.ADC3);
+//! let mut supply = SupplyMonitor::new(monitor, daisy_pins.SEED_PIN_15, (100.0 + 33.0) / 33.0);
+//! supply.on_low_battery(7.0, |volts| warn!("low battery: {}V", volts));
+//! loop {
+//!     let volts = supply.supply_voltage();
+//!     Timer::after_secs(1).await;
+//! }
+//! ```
+//! The reading is scaled with the VDDA measured through VREFINT, not the nominal 3.3V,
+//! so it stays accurate when VDDA itself sags. Divider resistor tolerance still applies,
+//! measure the ratio for better than 1-2%.
+use crate::adc::McuMonitor;
+use embassy_stm32 as hal;
+use hal::adc::AdcChannel;
+use hal::peripherals::ADC3;
+
+// the low battery callback is re-armed once the voltage is this much (relative) above the threshold
+const HYSTERESIS: f32 = 0.05;
+
+/// Volts at the divider input from a 16-bit reading of its output.
+pub fn divider_volts(raw: u16, vdda: f32, divider_ratio: f32) -> f32 {
+    raw as f32 / u16::MAX as f32 * vdda * divider_ratio
+}
+
+pub struct SupplyMonitor<'a, P: AdcChannel<ADC3>> {
+    monitor: McuMonitor<'a>,
+    pin: P,
+    divider_ratio: f32,
+    low_battery: Option<(f32, fn(f32))>,
+    low: bool,
+}
+
+impl<'a, P: AdcChannel<ADC3>> SupplyMonitor<'a, P> {
+    /// `divider_ratio`: input voltage over ADC pin voltage, `(R_top + R_bottom) / R_bottom`.
+    pub fn new(monitor: McuMonitor<'a>, pin: P, divider_ratio: f32) -> Self {
+        Self {
+            monitor,
+            pin,
+            divider_ratio,
+            low_battery: None,
+            low: false,
+        }
+    }
+    /// Call `callback` once when a reading drops below `threshold` volts.
+    /// It fires again only after the voltage recovered 5% above the threshold.
+    pub fn on_low_battery(&mut self, threshold: f32, callback: fn(f32)) {
+        self.low_battery = Some((threshold, callback));
+        self.low = false;
+    }
+    /// Read the supply voltage in volts.
+    pub fn supply_voltage(&mut self) -> f32 {
+        let vdda = self.monitor.read_vref();
+        let raw = self.monitor.read_raw(&mut self.pin);
+        let volts = divider_volts(raw, vdda, self.divider_ratio);
+        if let Some((threshold, callback)) = self.low_battery {
+            if !self.low && volts < threshold {
+                self.low = true;
+                callback(volts);
+            } else if self.low && volts > threshold * (1.0 + HYSTERESIS) {
+                self.low = false;
+            }
+        }
+        volts
+    }
+    /// Whether the last reading was below the low battery threshold.
+    pub fn is_low(&self) -> bool {
+        self.low
+    }
+    /// The MCU temperature and VDDA readings are still available.
+    pub fn monitor(&mut self) -> &mut McuMonitor<'a> {
+        &mut self.monitor
+    }
+}
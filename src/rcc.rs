@@ -0,0 +1,171 @@
+//! Clock profiles for the Daisy Seed.
+//!
+//! Both profiles run the core at 400MHz from the 16MHz HSE crystal and feed SAI1 from PLL3_P,
+//! so that the SAI master clock divider is an integer for the supported sample rates.
+//! Note that all PLLs on the STM32H750 share a single source, so PLL1 is also clocked from the HSE.
+//!
+//! | profile          | PLL3 (M / N / P) | SAI1 kernel clock | exact sample rates  |
+//! |------------------|------------------|-------------------|---------------------|
+//! | [`default_rcc`]  | 5 / 192 / 25     | 24.576MHz         | 32k, 48k, 96k       |
+//! | [`rcc_44100`]    | 7 / 326 / 33     | 22.5801MHz        | 44.1k, 88.2k (*)    |
+//!
+//! (*) 44.1kHz can't be derived from 16MHz with integer PLL settings.
+//! The closest one is 39ppm (0.07 cent) off, which is far below audible pitch error.
+//!
+//! `embassy_stm32::init` waits for the HSE without a timeout, so a missing or broken crystal
+//! hangs the board before anything is logged. [`init`] checks the crystal first:
+//! ```ignore
+//! let p = daisy_embassy::rcc::init(daisy_embassy::default_rcc());
+//! ```
+use defmt::error;
+use embassy_stm32 as hal;
+use hal::pac::rcc::vals::{Adcsel, Saisel};
+use hal::rcc::*;
+use hal::time::Hertz;
+
+/// Core clock of both profiles.
+pub const CPU_CLOCK: Hertz = Hertz(400_000_000);
+
+const HSI_FREQ: Hertz = Hertz(64_000_000);
+// Polls of HSERDY before giving up on the crystal. The MCU runs on the 64MHz HSI until
+// the clocks are set up, this is about 50ms, the crystal needs 2ms to start.
+const HSE_TIMEOUT_POLLS: u32 = 1_000_000;
+
+/// `embassy_stm32::init` with a check of the HSE crystal first.
+///
+/// If `config` uses the HSE and it doesn't become ready in time, this logs an error and falls back
+/// to the internal HSI at 64MHz, with the PLL dividers scaled so all clocks keep their nominal
+/// frequencies. The HSI is only accurate to about 1%, so the sample rate is off by as much,
+/// and USB may not enumerate. Panics if the dividers can't be scaled for the HSI.
+///
+/// PLL lock isn't checked separately: the PLLs lock within microseconds from a running source,
+/// configurations they can't lock on are rejected by the HAL with a panic.
+pub fn init(mut config: hal::Config) -> hal::Peripherals {
+    let hse = config
+        .rcc
+        .hse
+        .as_ref()
+        .map(|hse| (hse.freq, !matches!(hse.mode, HseMode::Oscillator)));
+    if let Some((hse_freq, bypass)) = hse {
+        if !hse_starts(bypass) {
+            error!(
+                "HSE ({}Hz crystal) not ready after ~50ms, running on the HSI instead. Check the crystal and the RCC config.",
+                hse_freq.0
+            );
+            hsi_fallback(&mut config, hse_freq);
+        }
+    }
+    hal::init(config)
+}
+
+// Start the HSE and wait for it, switching it off again if it doesn't come up.
+fn hse_starts(bypass: bool) -> bool {
+    let rcc = hal::pac::RCC;
+    if !rcc.cr().read().hseon() {
+        // HSEBYP can only be written while the HSE is off
+        rcc.cr().modify(|w| w.set_hsebyp(bypass));
+        rcc.cr().modify(|w| w.set_hseon(true));
+    }
+    for _ in 0..HSE_TIMEOUT_POLLS {
+        if rcc.cr().read().hserdy() {
+            return true;
+        }
+    }
+    rcc.cr().modify(|w| w.set_hseon(false));
+    false
+}
+
+// Move the PLLs from the HSE to the HSI, scaling the pre-dividers so the PLL inputs stay the same.
+fn hsi_fallback(config: &mut hal::Config, hse_freq: Hertz) {
+    assert!(
+        HSI_FREQ.0.is_multiple_of(hse_freq.0),
+        "can't derive the HSE's PLL inputs from the HSI"
+    );
+    let factor = HSI_FREQ.0 / hse_freq.0;
+    config.rcc.hse = None;
+    config.rcc.hsi = Some(HSIPrescaler::DIV1);
+    for pll in [
+        &mut config.rcc.pll1,
+        &mut config.rcc.pll2,
+        &mut config.rcc.pll3,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        if matches!(pll.source, PllSource::HSE) {
+            let prediv = pll.prediv.to_bits() as u32 * factor;
+            assert!(prediv <= 63, "PLL pre-divider out of range on the HSI");
+            pll.source = PllSource::HSI;
+            pll.prediv = PllPreDiv::from_bits(prediv as u8);
+        }
+    }
+}
+
+/// 400MHz core clock, SAI1 kernel clock at 24.576MHz (512 * 48kHz).
+pub fn default_rcc() -> hal::Config {
+    let mut config = common_config();
+    // 16MHz / 5 * 192 / 25 = 24.576MHz
+    config.rcc.pll3 = Some(Pll {
+        source: PllSource::HSE,
+        prediv: PllPreDiv::DIV5,
+        mul: PllMul::MUL192,
+        divp: Some(PllDiv::DIV25),
+        divq: None,
+        divr: None,
+    });
+    config
+}
+
+/// 400MHz core clock, SAI1 kernel clock at 22.5801MHz (512 * 44.1kHz, 39ppm off).
+pub fn rcc_44100() -> hal::Config {
+    let mut config = common_config();
+    // 16MHz / 7 * 326 / 33 = 22.5801MHz
+    config.rcc.pll3 = Some(Pll {
+        source: PllSource::HSE,
+        prediv: PllPreDiv::DIV7,
+        mul: PllMul::MUL326,
+        divp: Some(PllDiv::DIV33),
+        divq: None,
+        divr: None,
+    });
+    config
+}
+
+fn common_config() -> hal::Config {
+    let mut config = hal::Config::default();
+    config.rcc.hsi = Some(HSIPrescaler::DIV1);
+    config.rcc.csi = true;
+    // RNG kernel clock
+    config.rcc.hsi48 = Some(Hsi48Config {
+        sync_from_usb: false,
+    });
+    config.rcc.hse = Some(Hse {
+        freq: Hertz::mhz(16),
+        mode: HseMode::Oscillator,
+    });
+    // 16MHz / 4 * 200 = 800MHz VCO
+    config.rcc.pll1 = Some(Pll {
+        source: PllSource::HSE,
+        prediv: PllPreDiv::DIV4,
+        mul: PllMul::MUL200,
+        divp: Some(PllDiv::DIV2), // 400 Mhz
+        divq: Some(PllDiv::DIV8), // 100 Mhz
+        divr: None,
+    });
+    config.rcc.sys = Sysclk::PLL1_P; // 400 Mhz
+    config.rcc.ahb_pre = AHBPrescaler::DIV2; // 200 Mhz
+    config.rcc.apb1_pre = APBPrescaler::DIV2; // 100 Mhz
+    config.rcc.apb2_pre = APBPrescaler::DIV2; // 100 Mhz
+    config.rcc.apb3_pre = APBPrescaler::DIV2; // 100 Mhz
+    config.rcc.apb4_pre = APBPrescaler::DIV2; // 100 Mhz
+    config.rcc.voltage_scale = VoltageScale::Scale1;
+    config.rcc.mux.sai1sel = Saisel::PLL3_P;
+    // the second codec runs on the same clock, see crate::audio::SecondInterface
+    #[cfg(feature = "sai2")]
+    {
+        config.rcc.mux.sai23sel = Saisel::PLL3_P;
+    }
+    // per_ck defaults to HSI (64MHz), the ADC driver divides it down to its 50MHz limit.
+    config.rcc.mux.adcsel = Adcsel::PER;
+    config
+}
@@ -0,0 +1,117 @@
+//! Why the MCU was reset, to log at boot:
+//! ```ignore
+//! let p = embassy_stm32::init(daisy_embassy::default_rcc());
+//! defmt::info!("reset cause: {}", daisy_embassy::last_reset_cause());
+//! ```
+//! and rebooting into the STM32 system bootloader (USB DFU on the micro USB port), see [`reset_to_bootloader`].
+use core::mem::MaybeUninit;
+use embassy_stm32 as hal;
+
+// System memory bootloader of the STM32H74x/75x, see AN2606
+const SYSTEM_BOOTLOADER: u32 = 0x1FF0_9800;
+// SCB application interrupt and reset control register
+const AIRCR: *mut u32 = 0xE000_ED0C as *mut u32;
+const AIRCR_SYSRESETREQ: u32 = 0x05FA_0004;
+const BOOTLOADER_MAGIC: u32 = 0xB007_DF00;
+
+// not touched by the startup code, so it survives a software reset
+#[link_section = ".uninit.BOOTLOADER_REQUEST"]
+static mut BOOTLOADER_REQUEST: MaybeUninit<u32> = MaybeUninit::uninit();
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, defmt::Format)]
+pub enum ResetCause {
+    /// Power was applied.
+    PowerOn,
+    /// Supply dropped below the brown-out threshold.
+    BrownOut,
+    /// The independent watchdog (IWDG1) expired.
+    IndependentWatchdog,
+    /// The window watchdog (WWDG1) expired.
+    WindowWatchdog,
+    /// `SCB::sys_reset()`, e.g. from a panic handler or after a firmware update.
+    Software,
+    /// Illegal entry into Stop or Standby mode.
+    LowPower,
+    /// The NRST pin, e.g. the RESET button on the Seed.
+    Pin,
+    /// No flag set, or the flags were already cleared.
+    Unknown,
+}
+
+/// Read and clear the RCC reset flags.
+///
+/// The flags accumulate until cleared, so this only reports the last reset if it's called
+/// once per boot. A power-on reset also sets the brown-out and pin flags, and every other
+/// reset also sets the pin flag, so the most specific cause is returned.
+pub fn last_reset_cause() -> ResetCause {
+    let rcc = hal::pac::RCC;
+    let rsr = rcc.rsr().read();
+    rcc.rsr().modify(|w| w.set_rmvf(true));
+    if rsr.lpwrrstf() {
+        ResetCause::LowPower
+    } else if rsr.wwdg1rstf() {
+        ResetCause::WindowWatchdog
+    } else if rsr.iwdg1rstf() {
+        ResetCause::IndependentWatchdog
+    } else if rsr.sftrstf() {
+        ResetCause::Software
+    } else if rsr.porrstf() {
+        ResetCause::PowerOn
+    } else if rsr.borrstf() {
+        ResetCause::BrownOut
+    } else if rsr.pinrstf() {
+        ResetCause::Pin
+    } else {
+        ResetCause::Unknown
+    }
+}
+
+/// Reset into the STM32 system bootloader, which shows up as a USB DFU device (0483:df11)
+/// on the micro USB port, ready for `dfu-util`.
+///
+/// The bootloader can't be entered from a running application with its clocks and peripherals set up,
+/// so this only leaves a note in RAM and resets. [`enter_bootloader_if_requested`] has to be the first
+/// thing `main` calls to pick it up.
+pub fn reset_to_bootloader() -> ! {
+    // Safety: plain store to our own static, then a system reset through the SCB
+    unsafe {
+        core::ptr::addr_of_mut!(BOOTLOADER_REQUEST)
+            .cast::<u32>()
+            .write_volatile(BOOTLOADER_MAGIC);
+        core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+        AIRCR.write_volatile(AIRCR_SYSRESETREQ);
+    }
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+/// Jump to the system bootloader if [`reset_to_bootloader`] asked for it, otherwise return.
+/// Call it before `embassy_stm32::init`, while the MCU still runs as after reset:
+/// ```ignore
+/// #[embassy_executor::main]
+/// async fn main(spawner: Spawner) {
+///     daisy_embassy::reset::enter_bootloader_if_requested();
+///     let p = hal::init(daisy_embassy::default_rcc());
+/// ```
+pub fn enter_bootloader_if_requested() {
+    // Safety: reads our own static, which holds garbage after power up, the magic only after a request
+    let request = core::ptr::addr_of_mut!(BOOTLOADER_REQUEST).cast::<u32>();
+    unsafe {
+        if request.read_volatile() != BOOTLOADER_MAGIC {
+            return;
+        }
+        request.write_volatile(0);
+        // the bootloader's vector table: initial stack pointer, then the reset handler
+        let vectors = SYSTEM_BOOTLOADER as *const u32;
+        let sp = vectors.read_volatile();
+        let reset = vectors.add(1).read_volatile();
+        core::arch::asm!(
+            "msr msp, {sp}",
+            "bx {reset}",
+            sp = in(reg) sp,
+            reset = in(reg) reset,
+            options(noreturn),
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! SPI for external DACs, displays and sensors.
+//!
+//! SPI1 is available on the seed pins without overlapping the QSPI flash (PF6-PF10, PG6)
+//! or the codec's SAI1 (PE2-PE6):
+//!
+//! | signal | seed pin      | MCU pin |
+//! |--------|---------------|---------|
+//! | CS     | `SEED_PIN_7`  | PG10    |
+//! | SCK    | `SEED_PIN_8`  | PG11    |
+//! | MISO   | `SEED_PIN_9`  | PB4     |
+//! | MOSI   | `SEED_PIN_10` | PB5     |
+//!
+//! SPI1 (and SPI6) can also be routed to `SEED_PIN_22`(SCK, PA5), `SEED_PIN_19`(MISO, PA6)
+//! and `SEED_PIN_18`(MOSI, PA7) at the cost of three ADC inputs.
+//! Use `hal::spi::Spi::new` directly for that.
+//!
+//! DMA1_CH1 and DMA1_CH2 are taken by the audio interface, use any other stream.
+//!
+//! CS is a plain GPIO. To share the bus between several devices,
+//! wrap the `Spi` in a mutex and give each device its own CS `Output`,
+//! e.g. with `embassy_embedded_hal::shared_bus::asynch::spi::SpiDevice`.
+use crate::pins::{SeedPin10, SeedPin7, SeedPin8, SeedPin9};
+use embassy_stm32 as hal;
+use hal::gpio::{Level, Output, Speed};
+use hal::peripherals::SPI1;
+use hal::spi::{Config, RxDma, Spi, TxDma};
+use hal::Peripheral;
+
+pub type DaisySpi<'a> = Spi<'a, hal::mode::Async>;
+
+/// SPI1 on `SEED_PIN_8`(SCK), `SEED_PIN_10`(MOSI) and `SEED_PIN_9`(MISO).
+pub fn spi1<'a>(
+    spi1: SPI1,
+    sck: SeedPin8,
+    mosi: SeedPin10,
+    miso: SeedPin9,
+    tx_dma: impl Peripheral<P = impl TxDma<SPI1>> + 'a,
+    rx_dma: impl Peripheral<P = impl RxDma<SPI1>> + 'a,
+    config: Config,
+) -> DaisySpi<'a> {
+    Spi::new(spi1, sck, mosi, miso, tx_dma, rx_dma, config)
+}
+
+/// Chip select on `SEED_PIN_7`, idle high.
+pub fn spi1_cs<'a>(cs: SeedPin7) -> Output<'a> {
+    Output::new(cs, Level::High, Speed::VeryHigh)
+}
@@ -0,0 +1,89 @@
+//! Debounced switch / button input.
+//!
+//! The Daisy Seed has no user button on board.
+//! The BOOT button is wired to the BOOT0 pin, which is not a GPIO and can't be read by the firmware.
+//! (Holding BOOT while resetting enters the system DFU bootloader, so it's not suitable as a user input anyway.)
+//! Wire a momentary switch between a free seed pin and GND instead, and use it with an internal pull-up:
+//! ```ignore
+//! let button = Input::new(board.daisy_pins.SEED_PIN_28, Pull::Up);
+//! let mut button = Switch::new(button, Level::Low);
+//! loop {
+//!     button.wait_for_press().await;
+//!     effect_on = !effect_on;
+//! }
+//! ```
+use embassy_stm32 as hal;
+use embassy_time::{Duration, Instant, Timer};
+use hal::gpio::{Input, Level};
+
+/// Interval `update()` is expected to be called at. A press is then detected after 7ms of stable contact.
+pub const UPDATE_INTERVAL: Duration = Duration::from_millis(1);
+
+pub struct Switch<'a> {
+    pin: Input<'a>,
+    active_level: Level,
+    // last 8 samples, LSB is the latest one
+    state: u8,
+    pressed_at: Instant,
+}
+
+impl<'a> Switch<'a> {
+    /// `active_level` is the pin level while the switch is pressed,
+    /// `Level::Low` for a switch to GND with a pull-up.
+    pub fn new(pin: Input<'a>, active_level: Level) -> Self {
+        Self {
+            pin,
+            active_level,
+            state: 0,
+            pressed_at: Instant::now(),
+        }
+    }
+    /// Sample the pin. Call this at a fixed rate, ideally every [`UPDATE_INTERVAL`].
+    pub fn update(&mut self) {
+        let pressed = self.pin.get_level() == self.active_level;
+        self.state = (self.state << 1) | pressed as u8;
+        if self.rising_edge() {
+            self.pressed_at = Instant::now();
+        }
+    }
+    /// Debounced state, true after 8 consecutive pressed samples.
+    pub fn pressed(&self) -> bool {
+        self.state == 0xff
+    }
+    /// True right after the switch has become pressed.
+    pub fn rising_edge(&self) -> bool {
+        self.state == 0x7f
+    }
+    /// True right after the switch has been released.
+    pub fn falling_edge(&self) -> bool {
+        self.state == 0x80
+    }
+    /// How long the switch has been held, zero if it's not pressed.
+    pub fn time_held(&self) -> Duration {
+        if self.pressed() || self.rising_edge() {
+            Instant::now() - self.pressed_at
+        } else {
+            Duration::from_ticks(0)
+        }
+    }
+    /// Keep updating until the switch gets pressed.
+    pub async fn wait_for_press(&mut self) {
+        loop {
+            self.update();
+            if self.rising_edge() {
+                return;
+            }
+            Timer::after(UPDATE_INTERVAL).await;
+        }
+    }
+    /// Keep updating until the switch gets released.
+    pub async fn wait_for_release(&mut self) {
+        loop {
+            self.update();
+            if self.falling_edge() {
+                return;
+            }
+            Timer::after(UPDATE_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Tempo sync to an external clock on a gate input.
+//!
+//! ```ignore
+//! let gate = ExtiInput::new(p.PG13, p.EXTI13, Pull::None);
+//! let mut clock = ClockInput::new(gate, true, 4);
+//! loop {
+//!     let tick = clock.await_tick().await;
+//!     if let Some(bpm) = clock.bpm() {
+//!         info!("tick {} at {} BPM", tick, bpm);
+//!     }
+//! }
+//! ```
+#[cfg(feature = "hal")]
+use embassy_stm32 as hal;
+#[cfg(feature = "hal")]
+use embassy_time::Instant;
+#[cfg(feature = "hal")]
+use hal::exti::ExtiInput;
+
+// intervals the tempo is estimated from. The median of these rejects single late or early edges.
+const HISTORY: usize = 5;
+// the clock counts as stopped after this many estimated intervals without an edge
+const STOP_INTERVALS: u64 = 4;
+
+/// Tempo estimation from edge timestamps, independent of the hardware.
+pub struct TempoTracker {
+    pulses_per_beat: u32,
+    intervals: [u64; HISTORY],
+    count: usize,
+    last_edge: Option<u64>,
+}
+
+impl TempoTracker {
+    /// `pulses_per_beat`: 1 for quarter notes, 4 for sixteenths, 24 for MIDI style clocks.
+    pub fn new(pulses_per_beat: u32) -> Self {
+        Self {
+            pulses_per_beat: pulses_per_beat.max(1),
+            intervals: [0; HISTORY],
+            count: 0,
+            last_edge: None,
+        }
+    }
+    /// Record a rising edge at `micros`.
+    pub fn edge(&mut self, micros: u64) {
+        if let Some(last) = self.last_edge {
+            let interval = micros.saturating_sub(last);
+            if self.is_stopped(micros) {
+                // restarted after a pause, the gap isn't a tempo
+                self.count = 0;
+            } else if interval > 0 {
+                self.intervals.copy_within(1.., 0);
+                self.intervals[HISTORY - 1] = interval;
+                self.count = (self.count + 1).min(HISTORY);
+            }
+        }
+        self.last_edge = Some(micros);
+    }
+    /// Filtered interval between edges, in microseconds.
+    pub fn interval(&self) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let mut sorted = [0; HISTORY];
+        let recent = &mut sorted[..self.count];
+        recent.copy_from_slice(&self.intervals[HISTORY - self.count..]);
+        recent.sort_unstable();
+        Some(recent[self.count / 2])
+    }
+    /// Beats per minute, `None` until two edges have been seen or after the clock stopped at `now_micros`.
+    pub fn bpm(&self, now_micros: u64) -> Option<f32> {
+        if self.is_stopped(now_micros) {
+            return None;
+        }
+        let interval = self.interval()?;
+        Some(60_000_000.0 / (interval as f32 * self.pulses_per_beat as f32))
+    }
+    fn is_stopped(&self, now_micros: u64) -> bool {
+        match (self.last_edge, self.interval()) {
+            (Some(last), Some(interval)) => {
+                now_micros.saturating_sub(last) > interval * STOP_INTERVALS
+            }
+            _ => false,
+        }
+    }
+}
+
+/// External clock on an EXTI capable gate input.
+#[cfg(feature = "hal")]
+pub struct ClockInput<'a> {
+    input: ExtiInput<'a>,
+    // the gate's input stage inverts (e.g. the Patch SM gate inputs)
+    inverted: bool,
+    tracker: TempoTracker,
+    ticks: u32,
+}
+
+#[cfg(feature = "hal")]
+impl<'a> ClockInput<'a> {
+    pub fn new(input: ExtiInput<'a>, inverted: bool, pulses_per_beat: u32) -> Self {
+        Self {
+            input,
+            inverted,
+            tracker: TempoTracker::new(pulses_per_beat),
+            ticks: 0,
+        }
+    }
+    /// Wait for the next clock pulse and return its number, counting from 0.
+    pub async fn await_tick(&mut self) -> u32 {
+        if self.inverted {
+            self.input.wait_for_falling_edge().await;
+        } else {
+            self.input.wait_for_rising_edge().await;
+        }
+        self.tracker.edge(Instant::now().as_micros());
+        let tick = self.ticks;
+        self.ticks = self.ticks.wrapping_add(1);
+        tick
+    }
+    /// Detected tempo, `None` before the second pulse or when the clock has stopped.
+    pub fn bpm(&self) -> Option<f32> {
+        self.tracker.bpm(Instant::now().as_micros())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bpm_with_jitter_stop_and_restart() {
+        // 120 BPM at 4 pulses per beat is 125ms per pulse
+        let mut tracker = TempoTracker::new(4);
+        let mut now = 0u64;
+        assert_eq!(tracker.bpm(now), None);
+        for i in 0..10 {
+            let jitter = if i == 6 { 20_000 } else { 0 };
+            tracker.edge(now + jitter);
+            now += 125_000;
+        }
+        let bpm = tracker.bpm(now - 125_000).unwrap();
+        assert!((bpm - 120.0).abs() < 0.01, "{bpm}");
+        // stopped
+        assert_eq!(tracker.bpm(now + 1_000_000), None);
+        // restarted at 60 BPM, the pause isn't taken as a tempo
+        let mut now = now + 5_000_000;
+        tracker.edge(now);
+        for _ in 0..3 {
+            now += 250_000;
+            tracker.edge(now);
+        }
+        let bpm = tracker.bpm(now).unwrap();
+        assert!((bpm - 60.0).abs() < 0.01, "{bpm}");
+    }
+}
@@ -1,13 +1,34 @@
+//! USB device drivers.
+//!
+//! The Seed's micro USB connector is USB_OTG_FS ([`init`]). USB_OTG_HS is available on
+//! `SEED_PIN_29`(D-) and `SEED_PIN_30`(D+) through its internal full speed PHY ([`init_hs`]),
+//! e.g. to have a second USB port, or to keep the micro USB free for debugging and DFU.
+//! Both run at full speed (12Mbit/s): the STM32H750 has no internal high speed PHY,
+//! and an external ULPI PHY needs 12 pins including PH4 (the codec's I2C SCL) and
+//! several ADC pins, so high speed is not supported on the Seed.
+//!
+//! Build the `embassy_usb::Builder` on top of either driver with [`device_config`].
+//! [`add_dfu_runtime`] lets a host reboot the Daisy into the DFU bootloader over the same cable.
 use embassy_stm32 as hal;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use embassy_time::Timer;
+use embassy_usb::control::{InResponse, OutResponse, Recipient, Request, RequestType};
+use embassy_usb::msos::{self, windows_version};
+use embassy_usb::types::InterfaceNumber;
+use embassy_usb::Handler;
 use hal::{
-    peripherals::USB_OTG_FS,
+    peripherals::{USB_OTG_FS, USB_OTG_HS},
     usb::{Config, Driver},
 };
 use static_cell::StaticCell;
 
-use crate::{board::Irqs, pins::USB2Pins};
+use crate::{
+    board::Irqs,
+    pins::{USB1Pins, USB2Pins},
+};
 
 pub type DaisyUsb = Driver<'static, USB_OTG_FS>;
+pub type DaisyUsbHs = Driver<'static, USB_OTG_HS>;
 
 pub fn init(usb_otg_fs: USB_OTG_FS, pins: USB2Pins) -> DaisyUsb {
     let mut config = Config::default();
@@ -20,3 +41,150 @@ pub fn init(usb_otg_fs: USB_OTG_FS, pins: USB2Pins) -> DaisyUsb {
     let ep_out_buffer = EP_OUT_BUFFER.init([0; 256]);
     Driver::new_fs(usb_otg_fs, Irqs, pins.DP, pins.DN, ep_out_buffer, config)
 }
+
+/// USB_OTG_HS in full speed mode on `SEED_PIN_29`/`SEED_PIN_30`.
+/// Same settings as [`init`], usable at the same time.
+pub fn init_hs(usb_otg_hs: USB_OTG_HS, pins: USB1Pins) -> DaisyUsbHs {
+    let mut config = Config::default();
+    config.vbus_detection = false;
+    static EP_OUT_BUFFER: StaticCell<[u8; 256]> = StaticCell::new();
+    let ep_out_buffer = EP_OUT_BUFFER.init([0; 256]);
+    Driver::new_fs(usb_otg_hs, Irqs, pins.DP, pins.DN, ep_out_buffer, config)
+}
+
+/// `embassy_usb` device configuration with the descriptor fields set for composite devices
+/// (class 0xEF, interface association descriptors), which Windows needs to load a driver for
+/// each function, e.g. audio plus MIDI. Single function devices work with it as well.
+///
+/// Use your own VID/PID: the pid.codes project hands out free PIDs for open source hardware.
+/// `serial` defaults to the MCU's 96-bit unique ID in hex, so every unit enumerates separately.
+pub fn device_config(
+    vid: u16,
+    pid: u16,
+    manufacturer: &'static str,
+    product: &'static str,
+    serial: Option<&'static str>,
+) -> embassy_usb::Config<'static> {
+    let mut config = embassy_usb::Config::new(vid, pid);
+    config.manufacturer = Some(manufacturer);
+    config.product = Some(product);
+    config.serial_number = Some(serial.unwrap_or_else(hal::uid::uid_hex));
+    config.max_power = 100;
+    config.max_packet_size_0 = 64;
+    // Miscellaneous device class with interface association descriptors
+    config.device_class = 0xEF;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+    config
+}
+
+// DFU 1.1 runtime interface: application specific class, DFU subclass, runtime protocol
+const DFU_CLASS: u8 = 0xFE;
+const DFU_SUBCLASS: u8 = 0x01;
+const DFU_PROTOCOL_RUNTIME: u8 = 0x01;
+const DFU_FUNCTIONAL_DESCRIPTOR: u8 = 0x21;
+// bmAttributes bitWillDetach (0x08) | bitManifestationTolerant (0x04) | bitCanDnload (0x01),
+// no upload, 1000ms detach timeout, 1024 byte transfers, DFU 1.1a.
+// The transfer size is what the ST bootloader supports.
+const DFU_FUNCTIONAL: [u8; 7] = [0x0D, 0xE8, 0x03, 0x00, 0x04, 0x1A, 0x01];
+const DFU_DETACH: u8 = 0x00;
+const DFU_GETSTATUS: u8 = 0x03;
+const DFU_GETSTATE: u8 = 0x05;
+// bStatus OK, bwPollTimeout 0, bState appIDLE, iString 0
+const DFU_STATUS_APP_IDLE: [u8; 6] = [0; 6];
+const DFU_STATE_APP_IDLE: u8 = 0;
+
+static DFU_DETACH_REQUESTED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Request handler of the DFU runtime interface, see [`add_dfu_runtime`].
+pub struct DfuRuntime {
+    interface: Option<InterfaceNumber>,
+}
+
+impl DfuRuntime {
+    pub const fn new() -> Self {
+        Self { interface: None }
+    }
+    fn is_ours(&self, req: &Request) -> bool {
+        req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && Some(req.index as u8) == self.interface.map(|i| i.0)
+    }
+}
+
+impl Default for DfuRuntime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Handler for DfuRuntime {
+    fn control_out(&mut self, req: Request, _data: &[u8]) -> Option<OutResponse> {
+        if !self.is_ours(&req) {
+            return None;
+        }
+        match req.request {
+            DFU_DETACH => {
+                DFU_DETACH_REQUESTED.signal(());
+                Some(OutResponse::Accepted)
+            }
+            _ => Some(OutResponse::Rejected),
+        }
+    }
+    fn control_in<'a>(&'a mut self, req: Request, buf: &'a mut [u8]) -> Option<InResponse<'a>> {
+        if !self.is_ours(&req) {
+            return None;
+        }
+        match req.request {
+            DFU_GETSTATUS => {
+                buf[..DFU_STATUS_APP_IDLE.len()].copy_from_slice(&DFU_STATUS_APP_IDLE);
+                Some(InResponse::Accepted(&buf[..DFU_STATUS_APP_IDLE.len()]))
+            }
+            DFU_GETSTATE => {
+                buf[0] = DFU_STATE_APP_IDLE;
+                Some(InResponse::Accepted(&buf[..1]))
+            }
+            _ => Some(InResponse::Rejected),
+        }
+    }
+}
+
+/// Add a DFU runtime interface, so `dfu-util` can reboot the Daisy into the system bootloader:
+/// ```ignore
+/// static DFU: StaticCell<DfuRuntime> = StaticCell::new();
+/// let mut builder = Builder::new(driver, device_config(..), ..);
+/// add_dfu_runtime(&mut builder, DFU.init(DfuRuntime::new()));
+/// // ... other classes, builder.build()
+/// join(usb.run(), wait_for_dfu_detach()).await;
+/// ```
+/// and in `main`, before `hal::init`, [`crate::reset::enter_bootloader_if_requested`].
+///
+/// On the host, `dfu-util -e` detaches (the Daisy re-enumerates as `0483:df11`), then
+/// `dfu-util -a 0 -s 0x08000000:leave -D firmware.bin` flashes and starts the new firmware.
+/// The interface gets a WinUSB compatible ID, so Windows needs no driver installation for it;
+/// The builder needs an MS OS descriptor buffer for that, and this sets the MS OS descriptor version,
+/// so don't call `msos_descriptor` yourself.
+pub fn add_dfu_runtime<'d, D: embassy_usb::driver::Driver<'d>>(
+    builder: &mut embassy_usb::Builder<'d, D>,
+    dfu: &'d mut DfuRuntime,
+) {
+    builder.msos_descriptor(windows_version::WIN8_1, 0);
+    {
+        let mut function = builder.function(DFU_CLASS, DFU_SUBCLASS, DFU_PROTOCOL_RUNTIME);
+        function.msos_feature(msos::CompatibleIdFeatureDescriptor::new("WINUSB", ""));
+        let mut interface = function.interface();
+        dfu.interface = Some(interface.interface_number());
+        let mut alt = interface.alt_setting(DFU_CLASS, DFU_SUBCLASS, DFU_PROTOCOL_RUNTIME, None);
+        alt.descriptor(DFU_FUNCTIONAL_DESCRIPTOR, &DFU_FUNCTIONAL);
+    }
+    builder.handler(dfu);
+}
+
+/// Wait for a DFU detach request from the host, then reboot into the bootloader.
+pub async fn wait_for_dfu_detach() -> ! {
+    DFU_DETACH_REQUESTED.wait().await;
+    // let the status stage of the request complete before disappearing from the bus
+    Timer::after_millis(10).await;
+    crate::reset::reset_to_bootloader()
+}